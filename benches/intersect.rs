@@ -0,0 +1,246 @@
+//! Times the hot path of the tracer: finding the closest hit for a batch of
+//! rays against a list of spheres. Run with `cargo bench --bench intersect`.
+//!
+//! `before` is the same loop written the way the crate did it before `Vec3`
+//! became `Copy`: three lane vectors borrowed into every operator, hit
+//! records holding a reference counted material, and the old list loop
+//! over trait objects. It's timed alongside as the baseline.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use raytracing::hit::{Hit, HitableList};
+use raytracing::material::{Lambertian, Materials};
+use raytracing::sphere::Sphere;
+use raytracing::{Color, Float, Point3, Ray, Vec3};
+
+const NUM_SPHERES: usize = 64;
+const NUM_RAYS: usize = 100_000;
+const ROUNDS: usize = 10;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(7);
+
+    let mat = Materials::new().add(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+    let spheres: Vec<(Vec3, Float)> = (0..NUM_SPHERES)
+        .map(|_| {
+            let center = Vec3::new(rng.gen_range(-4.0, 4.0), rng.gen_range(-4.0, 4.0), rng.gen_range(-8.0, -2.0));
+            (center, rng.gen_range(0.1, 0.6))
+        })
+        .collect();
+    let world = HitableList::with_vals(
+        spheres.iter().map(|&(center, radius)| Arc::new(Sphere::new(center, radius, mat)) as Arc<dyn Hit>).collect(),
+    );
+    let baseline = before::List(
+        spheres
+            .iter()
+            .map(|&(center, radius)| Arc::new(before::Sphere::new(center, radius)) as Arc<dyn before::Hit>)
+            .collect(),
+    );
+
+    let rays: Vec<Ray> = (0..NUM_RAYS)
+        .map(|_| {
+            let dir = Vec3::new(rng.gen_range(-0.6, 0.6), rng.gen_range(-0.6, 0.6), -1.0);
+            Ray::with_values(Point3::origin(), dir)
+        })
+        .collect();
+
+    let (before_time, before_hits) = best_of(|| {
+        rays.iter()
+            .filter(|r| {
+                let r = before::Ray::new(*r.origin(), r.direction());
+                baseline.hit(&r, 0.001, f64::MAX).is_some()
+            })
+            .count()
+    });
+    let (time, hits) = best_of(|| rays.iter().filter(|r| world.hit(r, 0.001, Float::MAX).is_some()).count());
+    assert_eq!(hits, before_hits, "the baseline hit something different");
+
+    let per_ray = |d: Duration| d.as_nanos() as f64 / NUM_RAYS as f64;
+    println!(
+        "closest hit, {} spheres: {:.1} ns/ray, {:.1} before ({:.2}x, {:.2} ns per sphere test, {} hits)",
+        NUM_SPHERES,
+        per_ray(time),
+        per_ray(before_time),
+        before_time.as_secs_f64() / time.as_secs_f64(),
+        per_ray(time) / NUM_SPHERES as f64,
+        hits
+    );
+}
+
+/// The fastest of a few runs, and what the run returned.
+fn best_of<F: FnMut() -> usize>(mut f: F) -> (Duration, usize) {
+    let mut best = (Duration::from_secs(u64::MAX), 0);
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        let result = f();
+        best = (best.0.min(start.elapsed()), result);
+    }
+    best
+}
+
+mod before {
+    use std::any::Any;
+    use std::ops::{Div, Mul, Sub};
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    pub struct Vec3 {
+        e: [f64; 3],
+    }
+
+    impl Vec3 {
+        fn dot(l: &Vec3, r: &Vec3) -> f64 {
+            l.e[0] * r.e[0] + l.e[1] * r.e[1] + l.e[2] * r.e[2]
+        }
+    }
+
+    impl From<raytracing::Vec3> for Vec3 {
+        #[allow(clippy::unnecessary_cast)]
+        fn from(v: raytracing::Vec3) -> Self {
+            Vec3 {
+                e: [v.x() as f64, v.y() as f64, v.z() as f64],
+            }
+        }
+    }
+
+    impl Sub<&Vec3> for &Vec3 {
+        type Output = Vec3;
+
+        fn sub(self, o: &Vec3) -> Vec3 {
+            Vec3 {
+                e: [self.e[0] - o.e[0], self.e[1] - o.e[1], self.e[2] - o.e[2]],
+            }
+        }
+    }
+
+    impl Sub<&Vec3> for Vec3 {
+        type Output = Vec3;
+
+        fn sub(self, o: &Vec3) -> Vec3 {
+            &self - o
+        }
+    }
+
+    impl Mul<&Vec3> for f64 {
+        type Output = Vec3;
+
+        fn mul(self, v: &Vec3) -> Vec3 {
+            Vec3 {
+                e: [self * v.e[0], self * v.e[1], self * v.e[2]],
+            }
+        }
+    }
+
+    impl Div<f64> for Vec3 {
+        type Output = Vec3;
+
+        fn div(self, t: f64) -> Vec3 {
+            Vec3 {
+                e: [self.e[0] / t, self.e[1] / t, self.e[2] / t],
+            }
+        }
+    }
+
+    pub struct Ray {
+        origin: Vec3,
+        dir: Vec3,
+    }
+
+    impl Ray {
+        pub fn new(origin: raytracing::Vec3, dir: raytracing::Vec3) -> Self {
+            Ray {
+                origin: origin.into(),
+                dir: dir.into(),
+            }
+        }
+
+        fn point_at_param(&self, t: f64) -> Vec3 {
+            let d = t * &self.dir;
+            Vec3 {
+                e: [self.origin.e[0] + d.e[0], self.origin.e[1] + d.e[1], self.origin.e[2] + d.e[2]],
+            }
+        }
+    }
+
+    // built in full like the old records were, for what that cost, but only `t` is read
+    #[allow(dead_code)]
+    pub struct HitRecord {
+        pub t: f64,
+        pub point: Vec3,
+        pub normal: Vec3,
+        pub mat: Arc<dyn Any + Send + Sync>,
+    }
+
+    pub struct Sphere {
+        center: Vec3,
+        radius: f64,
+        mat: Arc<dyn Any + Send + Sync>,
+    }
+
+    impl Sphere {
+        #[allow(clippy::unnecessary_cast)]
+        pub fn new(center: raytracing::Vec3, radius: raytracing::Float) -> Self {
+            Sphere {
+                center: center.into(),
+                radius: radius as f64,
+                mat: Arc::new(()),
+            }
+        }
+    }
+
+    pub trait Hit {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    }
+
+    impl Hit for Sphere {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            let oc = &r.origin - &self.center;
+            let a = Vec3::dot(&r.dir, &r.dir);
+            let b = Vec3::dot(&oc, &r.dir);
+            let c = Vec3::dot(&oc, &oc) - self.radius * self.radius;
+
+            let discriminant = b * b - a * c;
+            if discriminant > 0.0 {
+                for t in [(-b - discriminant.sqrt()) / a, (-b + discriminant.sqrt()) / a] {
+                    if t > t_min && t < t_max {
+                        let point = r.point_at_param(t);
+                        let normal = (r.point_at_param(t) - &self.center) / self.radius;
+                        return Some(HitRecord { t, point, normal, mat: self.mat.clone() });
+                    }
+                }
+            }
+            None
+        }
+    }
+
+    pub struct List(pub Vec<Arc<dyn Hit>>);
+
+    impl List {
+        pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            // the old `HitableList::hit` verbatim: every sphere gets the full
+            // range and the records are compared afterwards
+            let mut closest: Option<HitRecord> = None;
+            for h in self.0.iter() {
+                #[allow(clippy::single_match)]
+                match h.hit(r, t_min, t_max) {
+                    Some(record) => {
+                        if let Some(c) = &closest {
+                            if record.t < c.t {
+                                closest = Some(record)
+                            }
+                        } else {
+                            closest = Some(record)
+                        }
+                    },
+                    None => {},
+                };
+            }
+
+            closest
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Compares tracing camera rays through a BVH one at a time with tracing
+//! them in packets, for a field of spheres and for a triangle mesh. Run with
+//! `cargo bench --bench packet`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use raytracing::bvh::Bvh;
+use raytracing::hit::Hit;
+use raytracing::material::{Lambertian, Materials};
+use raytracing::packet::{PacketHits, RayPacket, WIDTH};
+use raytracing::shape::Triangle;
+use raytracing::sphere::Sphere;
+use raytracing::{Color, Float, Point3, Ray, Vec3};
+
+const NUM_SPHERES: usize = 2000;
+/// Cells along each side of the mesh's grid, which has two triangles a cell.
+const GRID: usize = 64;
+const WIDTH_PX: usize = 640;
+const HEIGHT_PX: usize = 360;
+const ROUNDS: usize = 5;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mat = Materials::new().add(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+    let spheres: Vec<Arc<dyn Hit>> = (0..NUM_SPHERES)
+        .map(|_| {
+            let center = Vec3::new(rng.gen_range(-8.0, 8.0), rng.gen_range(-4.5, 4.5), rng.gen_range(-12.0, -4.0));
+            Arc::new(Sphere::new(center, rng.gen_range(0.05, 0.3), mat)) as Arc<dyn Hit>
+        })
+        .collect();
+
+    // a bumpy height field facing the camera
+    let point = |i: usize, j: usize| {
+        let (x, y) = (i as Float / GRID as Float * 16.0 - 8.0, j as Float / GRID as Float * 9.0 - 4.5);
+        Vec3::new(x, y, -6.0 + 0.5 * (x * 1.3).sin() * (y * 0.7).cos())
+    };
+    let mut triangles: Vec<Arc<dyn Hit>> = Vec::new();
+    for j in 0..GRID {
+        for i in 0..GRID {
+            let (a, b, c, d) = (point(i, j), point(i + 1, j), point(i + 1, j + 1), point(i, j + 1));
+            triangles.push(Arc::new(Triangle::new(a, b, c, mat)));
+            triangles.push(Arc::new(Triangle::new(a, c, d, mat)));
+        }
+    }
+
+    // camera rays in rows, packed left to right like riow does
+    let rays: Vec<Ray> = (0..HEIGHT_PX)
+        .flat_map(|y| (0..WIDTH_PX).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let u = (x as Float + 0.5) / WIDTH_PX as Float * 2.0 - 1.0;
+            let v = 1.0 - (y as Float + 0.5) / HEIGHT_PX as Float * 2.0;
+            Ray::with_values(Point3::origin(), Vec3::new(u * 16.0 / 9.0 * 0.6, v * 0.6, -1.0))
+        })
+        .collect();
+
+    bench("spheres", &Bvh::new(&spheres), &rays);
+    bench("triangles", &Bvh::new(&triangles), &rays);
+}
+
+fn bench(name: &str, bvh: &Bvh, rays: &[Ray]) {
+    let scalar = best_of(|| rays.iter().filter(|r| bvh.hit(r, 0.001, Float::MAX).is_some()).count());
+    let packet = best_of(|| {
+        let mut count = 0;
+        for chunk in rays.chunks(WIDTH) {
+            let packet = RayPacket::new(chunk);
+            let mut hits = PacketHits::new(&packet, Float::MAX);
+            bvh.hit_packet(&packet, 0.001, &mut hits);
+            count += hits.into_records().iter().filter(|h| h.is_some()).count();
+        }
+        count
+    });
+    assert_eq!(scalar.1, packet.1, "packets hit something different");
+
+    let per_ray = |d: Duration| d.as_nanos() as f64 / rays.len() as f64;
+    println!(
+        "{} ({} objects): scalar {:.1} ns/ray, packets of {} {:.1} ns/ray ({:.2}x), {} hits",
+        name,
+        bvh.len(),
+        per_ray(scalar.0),
+        WIDTH,
+        per_ray(packet.0),
+        scalar.0.as_secs_f64() / packet.0.as_secs_f64(),
+        scalar.1
+    );
+}
+
+/// The fastest of a few runs, and what the run returned.
+fn best_of<F: FnMut() -> usize>(mut f: F) -> (Duration, usize) {
+    let mut best = (Duration::from_secs(u64::MAX), 0);
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        let result = f();
+        best = (best.0.min(start.elapsed()), result);
+    }
+    best
+}
@@ -0,0 +1,109 @@
+use super::packet::{Lanes, RayPacket, WIDTH};
+use super::{Float, Ray, Vec3};
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone)]
+pub struct Aabb {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self {
+            min,
+            max,
+        }
+    }
+
+    /// Box around a center point extending `extent` in each direction.
+    pub fn around(center: &Vec3, extent: &Vec3) -> Self {
+        Self::new(*center - *extent, *center + *extent)
+    }
+
+    pub fn min(&self) -> &Vec3 {
+        &self.min
+    }
+
+    pub fn max(&self) -> &Vec3 {
+        &self.max
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Aabb {
+        Aabb::new(
+            Vec3::new(
+                a.min.x().min(b.min.x()),
+                a.min.y().min(b.min.y()),
+                a.min.z().min(b.min.z()),
+            ),
+            Vec3::new(
+                a.max.x().max(b.max.x()),
+                a.max.y().max(b.max.y()),
+                a.max.z().max(b.max.z()),
+            ),
+        )
+    }
+
+    /// The region covered by both boxes, which may be empty.
+    pub fn overlap(a: &Aabb, b: &Aabb) -> Aabb {
+        Aabb::new(
+            Vec3::new(
+                a.min.x().max(b.min.x()),
+                a.min.y().max(b.min.y()),
+                a.min.z().max(b.min.z()),
+            ),
+            Vec3::new(
+                a.max.x().min(b.max.x()),
+                a.max.y().min(b.max.y()),
+                a.max.z().min(b.max.z()),
+            ),
+        )
+    }
+
+    /// Slab test for whether the ray passes through the box within the range.
+    pub fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.clip(r, t_min, t_max).is_some()
+    }
+
+    /// The part of the range where the ray is inside the box, if any.
+    pub fn clip(&self, r: &Ray, mut t_min: Float, mut t_max: Float) -> Option<(Float, Float)> {
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.direction()[axis];
+            let mut t0 = (self.min[axis] - r.origin()[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - r.origin()[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+
+        Some((t_min, t_max))
+    }
+
+    /// Which rays of a packet pass through the box between `t_min` and
+    /// their `t_max`.
+    pub fn hit_packet(&self, packet: &RayPacket, t_min: Float, t_max: &Lanes) -> [bool; WIDTH] {
+        let mut near = [t_min; WIDTH];
+        let mut far = *t_max;
+        for axis in 0..3 {
+            let (origin, inv_d) = (packet.origin(axis), packet.inv_direction(axis));
+            for i in 0..WIDTH {
+                let t0 = (self.min[axis] - origin[i]) * inv_d[i];
+                let t1 = (self.max[axis] - origin[i]) * inv_d[i];
+                near[i] = near[i].max(t0.min(t1));
+                far[i] = far[i].min(t0.max(t1));
+            }
+        }
+
+        let mut hit = [false; WIDTH];
+        for i in 0..WIDTH {
+            hit[i] = far[i] > near[i];
+        }
+        hit
+    }
+}
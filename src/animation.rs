@@ -0,0 +1,419 @@
+//! Keyframed values for animating cameras, objects and materials.
+//!
+//! An `Animation` is a set of named channels, each a `Track` of keys at
+//! times in seconds. Scenes look their parameters up by name for the time
+//! of the frame they're building, falling back to their static values.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+
+use super::{Float, Mat4, Vec3};
+
+/// Values that can be keyframed.
+pub trait Animate: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Float, Output = Self> {}
+
+impl<T> Animate for T where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Float, Output = T> {}
+
+/// How a track gets from a key to the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Holds the key's value until the next key.
+    Step,
+    Linear,
+    /// A cubic Bezier curve through the key's handles.
+    Bezier,
+}
+
+impl Interpolation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "step" => Some(Interpolation::Step),
+            "linear" => Some(Interpolation::Linear),
+            "bezier" => Some(Interpolation::Bezier),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key<T> {
+    pub time: Float,
+    pub value: T,
+    /// Interpolation towards the next key.
+    pub interpolation: Interpolation,
+    /// Bezier control points relative to `value`, before and after the key.
+    /// Without them the curve passes smoothly through its neighbours.
+    pub handles: Option<(T, T)>,
+}
+
+impl<T: Animate> Key<T> {
+    pub fn new(time: Float, value: T, interpolation: Interpolation) -> Self {
+        Key {
+            time,
+            value,
+            interpolation,
+            handles: None,
+        }
+    }
+
+    pub fn bezier(time: Float, value: T, handle_in: T, handle_out: T) -> Self {
+        Key {
+            handles: Some((handle_in, handle_out)),
+            ..Key::new(time, value, Interpolation::Bezier)
+        }
+    }
+}
+
+/// Keys sorted by time. Before the first key and after the last the track
+/// holds their values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    keys: Vec<Key<T>>,
+}
+
+impl<T: Animate> Track<T> {
+    pub fn new() -> Self {
+        Track { keys: Vec::new() }
+    }
+
+    /// A track that's always `value`.
+    pub fn constant(value: T) -> Self {
+        let mut track = Track::new();
+        track.add(Key::new(0.0, value, Interpolation::Step));
+        track
+    }
+
+    /// Adds a key, replacing any at the same time.
+    pub fn add(&mut self, key: Key<T>) {
+        match self.keys.binary_search_by(|k| k.time.total_cmp(&key.time)) {
+            Ok(i) => self.keys[i] = key,
+            Err(i) => self.keys.insert(i, key),
+        }
+    }
+
+    pub fn keys(&self) -> &[Key<T>] {
+        &self.keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The value at `time`. Panics if the track has no keys.
+    pub fn at(&self, time: Float) -> T {
+        let keys = &self.keys;
+        let next = keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return keys[0].value;
+        }
+        if next == keys.len() {
+            return keys[next - 1].value;
+        }
+
+        let (k0, k1) = (&keys[next - 1], &keys[next]);
+        let s = (time - k0.time) / (k1.time - k0.time);
+        match k0.interpolation {
+            Interpolation::Step => k0.value,
+            Interpolation::Linear => k0.value + (k1.value - k0.value) * s,
+            Interpolation::Bezier => {
+                let p1 = k0.value + self.handles(next - 1).1;
+                let p2 = k1.value + self.handles(next).0;
+                let r = 1.0 - s;
+                k0.value * (r * r * r) + p1 * (3.0 * r * r * s) + p2 * (3.0 * r * s * s)
+                    + k1.value * (s * s * s)
+            }
+        }
+    }
+
+    /// Handles of key `i`, by default a third of the way to its neighbours
+    /// along the slope between them, which makes a Catmull-Rom spline.
+    fn handles(&self, i: usize) -> (T, T) {
+        let keys = &self.keys;
+        if let Some(handles) = keys[i].handles {
+            return handles;
+        }
+
+        let prev = &keys[i.saturating_sub(1)];
+        let next = &keys[(i + 1).min(keys.len() - 1)];
+        if next.time == prev.time {
+            let zero = keys[i].value * 0.0;
+            return (zero, zero);
+        }
+        let slope = (next.value - prev.value) * (1.0 / (next.time - prev.time));
+        let before = i.checked_sub(1).map_or(0.0, |p| keys[i].time - keys[p].time);
+        let after = keys.get(i + 1).map_or(0.0, |n| n.time - keys[i].time);
+        (slope * (-before / 3.0), slope * (after / 3.0))
+    }
+}
+
+impl<T: Animate> Default for Track<T> {
+    fn default() -> Self {
+        Track::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Channel {
+    Float(Track<Float>),
+    Vec3(Track<Vec3>),
+}
+
+/// Named tracks, like `camera.position` or `metal.fuzz`.
+///
+/// Animations can be read from text files with a key per line:
+///
+/// ```text
+/// # channel time interpolation value [handle_in handle_out]
+/// camera.position 0 bezier 3,3,2
+/// camera.position 2 linear 0,1,4
+/// metal.fuzz 0 linear 1
+/// metal.fuzz 2 linear 0
+/// ```
+///
+/// Values are numbers or `x,y,z` vectors, and a channel's keys must all be
+/// the same kind.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Animation {
+    channels: HashMap<String, Channel>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Animation::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Animation::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut animation = Animation::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            animation
+                .parse_key(line)
+                .map_err(|e| format!("line {}: {}", n + 1, e))?;
+        }
+        Ok(animation)
+    }
+
+    fn parse_key(&mut self, line: &str) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, time, interpolation, values) = match words.as_slice() {
+            [name, time, interpolation, values @ ..] if values.len() == 1 || values.len() == 3 => {
+                (*name, *time, *interpolation, values)
+            }
+            _ => return Err(String::from("expected channel, time, interpolation and value")),
+        };
+        let time: Float = match time.parse() {
+            Ok(t) if Float::is_finite(t) => t,
+            _ => return Err(format!("invalid time {}", time)),
+        };
+        let interpolation = Interpolation::from_name(interpolation)
+            .ok_or(format!("unknown interpolation {}", interpolation))?;
+
+        let numbers = |s: &str| -> Result<Vec<Float>, String> {
+            s.split(',')
+                .map(|c| match c.trim().parse() {
+                    Ok(v) if Float::is_finite(v) => Ok(v),
+                    _ => Err(format!("invalid number {}", c)),
+                })
+                .collect()
+        };
+        let values: Vec<Vec<Float>> = values.iter().map(|v| numbers(v)).collect::<Result<_, _>>()?;
+        let len = values[0].len();
+        if values.iter().any(|v| v.len() != len) || (len != 1 && len != 3) {
+            return Err(String::from("values must all be numbers or all be x,y,z vectors"));
+        }
+        if values.len() == 3 && interpolation != Interpolation::Bezier {
+            return Err(String::from("only bezier keys have handles"));
+        }
+
+        if len == 1 {
+            let v: Vec<Float> = values.iter().map(|v| v[0]).collect();
+            let mut key = Key::new(time, v[0], interpolation);
+            key.handles = v.get(1).map(|_| (v[1], v[2]));
+            self.add_float(name, key)
+        } else {
+            let v: Vec<Vec3> = values.iter().map(|v| Vec3::new(v[0], v[1], v[2])).collect();
+            let mut key = Key::new(time, v[0], interpolation);
+            key.handles = v.get(1).map(|_| (v[1], v[2]));
+            self.add_vec3(name, key)
+        }
+    }
+
+    pub fn add_float(&mut self, name: &str, key: Key<Float>) -> Result<(), String> {
+        let channel = self
+            .channels
+            .entry(String::from(name))
+            .or_insert_with(|| Channel::Float(Track::new()));
+        match channel {
+            Channel::Float(track) => {
+                track.add(key);
+                Ok(())
+            }
+            Channel::Vec3(_) => Err(format!("{} is animated with vectors", name)),
+        }
+    }
+
+    pub fn add_vec3(&mut self, name: &str, key: Key<Vec3>) -> Result<(), String> {
+        let channel = self
+            .channels
+            .entry(String::from(name))
+            .or_insert_with(|| Channel::Vec3(Track::new()));
+        match channel {
+            Channel::Vec3(track) => {
+                track.add(key);
+                Ok(())
+            }
+            Channel::Float(_) => Err(format!("{} is animated with numbers", name)),
+        }
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&Channel> {
+        self.channels.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.channels.keys().map(String::as_str)
+    }
+
+    /// The number a channel takes at `time`, or `default` if it isn't
+    /// animated.
+    pub fn float_at(&self, name: &str, time: Float, default: Float) -> Float {
+        match self.channels.get(name) {
+            Some(Channel::Float(track)) => track.at(time),
+            _ => default,
+        }
+    }
+
+    pub fn vec3_at(&self, name: &str, time: Float, default: Vec3) -> Vec3 {
+        match self.channels.get(name) {
+            Some(Channel::Vec3(track)) => track.at(time),
+            _ => default,
+        }
+    }
+
+    /// Where `<object>.translate`, `<object>.rotate` and `<object>.scale`
+    /// put an object modelled around the origin at `time`, or `None` if
+    /// none of them are animated. The object stays at `position` unless
+    /// it's translated, and rotations are in degrees around x, then y, then
+    /// z.
+    pub fn transform_at(&self, object: &str, time: Float, position: Vec3) -> Option<Mat4> {
+        let name = |channel: &str| format!("{}.{}", object, channel);
+        let (translate, rotate, scale) = (name("translate"), name("rotate"), name("scale"));
+        if ![&translate, &rotate, &scale].iter().any(|n| self.channels.contains_key(n.as_str())) {
+            return None;
+        }
+
+        let t = self.vec3_at(&translate, time, position);
+        let r = self.vec3_at(&rotate, time, Vec3::origin());
+        let s = self.vec3_at(&scale, time, Vec3::new(1.0, 1.0, 1.0));
+        let rotation = Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), r.z())
+            * Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), r.y())
+            * Mat4::rotation(Vec3::new(1.0, 0.0, 0.0), r.x());
+        Some(Mat4::translation(t) * rotation * Mat4::scale(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn tracks_hold_their_ends_and_interpolate_between_keys() {
+        let mut track = Track::new();
+        track.add(Key::new(2.0, 4.0, Interpolation::Linear));
+        track.add(Key::new(0.0, 0.0, Interpolation::Linear));
+        track.add(Key::new(4.0, 0.0, Interpolation::Step));
+        assert_eq!(track.keys().len(), 3);
+        assert_eq!(track.at(-1.0), 0.0);
+        assert!(close(track.at(1.0), 2.0));
+        assert!(close(track.at(3.0), 2.0));
+        assert_eq!(track.at(5.0), 0.0);
+
+        track.add(Key::new(2.0, 2.0, Interpolation::Step));
+        assert_eq!(track.keys().len(), 3);
+        assert_eq!(track.at(3.9), 2.0);
+    }
+
+    #[test]
+    fn bezier_keys_follow_their_handles() {
+        let mut track = Track::new();
+        track.add(Key::bezier(0.0, 0.0, 0.0, 1.0));
+        track.add(Key::bezier(1.0, 1.0, 0.0, 0.0));
+        // control points 0, 1, 1, 1
+        assert!(close(track.at(0.5), 0.875));
+
+        // without handles, evenly spaced keys on a line stay on it
+        let mut smooth = Track::new();
+        for i in 0..4 {
+            smooth.add(Key::new(i as Float, 2.0 * i as Float, Interpolation::Bezier));
+        }
+        assert!(close(smooth.at(1.5), 3.0));
+    }
+
+    #[test]
+    fn parses_keys() {
+        let text = "# a comment\n\
+                    camera.position 0 bezier 3,3,2 0,0,0 1,0,0  # handles\n\
+                    camera.position 2 linear 0,1,4\n\
+                    \n\
+                    metal.fuzz 0 linear 1\n\
+                    metal.fuzz 2 linear 0\n";
+        let animation = Animation::parse(text).unwrap();
+        let mut names: Vec<&str> = animation.names().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["camera.position", "metal.fuzz"]);
+        assert!(close(animation.float_at("metal.fuzz", 1.0, 5.0), 0.5));
+        assert_eq!(animation.float_at("metal.albedo", 1.0, 5.0), 5.0);
+        assert_eq!(animation.vec3_at("camera.position", 3.0, Vec3::origin()), Vec3::new(0.0, 1.0, 4.0));
+        match animation.channel("camera.position") {
+            Some(Channel::Vec3(track)) => {
+                assert_eq!(track.keys()[0].handles, Some((Vec3::origin(), Vec3::new(1.0, 0.0, 0.0))));
+            }
+            other => panic!("expected a vector channel, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_keys() {
+        for text in &[
+            "fov 0 linear",
+            "fov zero linear 1",
+            "fov nan linear 1",
+            "fov 0 cubic 1",
+            "fov 0 linear x",
+            "fov 0 linear inf",
+            "fov 0 linear 1,2",
+            "fov 0 linear 1 2 3",
+            "fov 0 bezier 1 2,2,2 3",
+            "fov 0 linear 1\nfov 1 linear 1,2,3",
+        ] {
+            assert!(Animation::parse(text).is_err(), "{}", text);
+        }
+        let err = Animation::parse("fov 0 linear 1\nfov 1 linear").unwrap_err();
+        assert!(err.starts_with("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn transforms_only_animated_objects() {
+        let mut animation = Animation::new();
+        assert!(animation.transform_at("ball", 0.0, Vec3::origin()).is_none());
+
+        animation.add_vec3("ball.scale", Key::new(0.0, Vec3::new(2.0, 2.0, 2.0), Interpolation::Step)).unwrap();
+        let m = animation.transform_at("ball", 0.0, Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert_eq!(m.transform_point(Vec3::new(1.0, 1.0, 1.0)), Vec3::new(3.0, 2.0, 2.0));
+    }
+}
@@ -0,0 +1,121 @@
+//! Arbitrary output variables: what the first hit along a camera ray saw,
+//! written out alongside the beauty image for compositing.
+
+use super::{Float, Vec3};
+
+/// One of the passes that can be rendered next to the beauty image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    /// Distance along the ray, zero where nothing was hit.
+    Depth,
+    /// World space shading normal, facing the camera.
+    Normal,
+    Albedo,
+    /// Index of the object in the world's list, plus one so that zero is
+    /// left for the background.
+    ObjectId,
+    /// Index of the material in the renderer's `Materials`, plus one.
+    MaterialId,
+    /// World space position.
+    Position,
+    /// Direct light at the first hit from the light with this index, in the
+    /// order they were added to the renderer.
+    Light(usize),
+}
+
+impl Aov {
+    /// Parses names like `depth` or `light:0`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "depth" => Some(Aov::Depth),
+            "normal" => Some(Aov::Normal),
+            "albedo" => Some(Aov::Albedo),
+            "object" => Some(Aov::ObjectId),
+            "material" => Some(Aov::MaterialId),
+            "position" => Some(Aov::Position),
+            _ => {
+                let i = name.strip_prefix("light:")?;
+                i.parse().ok().map(Aov::Light)
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Aov::Depth => String::from("depth"),
+            Aov::Normal => String::from("normal"),
+            Aov::Albedo => String::from("albedo"),
+            Aov::ObjectId => String::from("object"),
+            Aov::MaterialId => String::from("material"),
+            Aov::Position => String::from("position"),
+            Aov::Light(i) => format!("light{}", i),
+        }
+    }
+
+    /// Whether the pass holds labels, which mustn't be blended between
+    /// neighbouring samples like the other passes are.
+    pub fn is_id(&self) -> bool {
+        matches!(self, Aov::ObjectId | Aov::MaterialId)
+    }
+}
+
+/// The values every pass takes for one camera ray.
+#[derive(Debug, Clone, Default)]
+pub struct Aovs {
+    pub depth: Float,
+    pub normal: Vec3,
+    pub albedo: Vec3,
+    pub position: Vec3,
+    pub object: Option<usize>,
+    /// Index of the material hit in the renderer's `Materials`.
+    pub material: Option<usize>,
+    pub lights: Vec<Vec3>,
+}
+
+impl Aovs {
+    /// The value of a pass as a color, with scalars in every channel.
+    pub fn get(&self, aov: Aov) -> Vec3 {
+        let splat = |v: Float| Vec3::new(v, v, v);
+        let id = |id: Option<usize>| splat(id.map_or(0.0, |i| (i + 1) as Float));
+        match aov {
+            Aov::Depth => splat(self.depth),
+            Aov::Normal => self.normal,
+            Aov::Albedo => self.albedo,
+            Aov::ObjectId => id(self.object),
+            Aov::MaterialId => id(self.material),
+            Aov::Position => self.position,
+            Aov::Light(i) => self.lights.get(i).copied().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_by_name() {
+        assert_eq!(Aov::from_name("depth"), Some(Aov::Depth));
+        assert_eq!(Aov::from_name("light:2"), Some(Aov::Light(2)));
+        assert_eq!(Aov::from_name("light:x"), None);
+        assert_eq!(Aov::from_name("specular"), None);
+        assert_eq!(Aov::Light(2).name(), "light2");
+        assert!(Aov::ObjectId.is_id() && !Aov::Albedo.is_id());
+    }
+
+    #[test]
+    fn ids_leave_zero_for_the_background() {
+        let hit = Aovs {
+            object: Some(0),
+            material: Some(4),
+            depth: 2.5,
+            ..Aovs::default()
+        };
+        assert_eq!(hit.get(Aov::ObjectId), Vec3::splat(1.0));
+        assert_eq!(hit.get(Aov::MaterialId), Vec3::splat(5.0));
+        assert_eq!(hit.get(Aov::Depth), Vec3::splat(2.5));
+        assert_eq!(Aovs::default().get(Aov::ObjectId), Vec3::origin());
+        // lights that weren't sampled are black
+        assert_eq!(hit.get(Aov::Light(3)), Vec3::origin());
+    }
+}
@@ -0,0 +1,20 @@
+use crate::Vec3;
+use super::Background;
+
+pub struct Constant {
+    color: Vec3,
+}
+
+impl Constant {
+    pub fn new(color: Vec3) -> Self {
+        Self {
+            color,
+        }
+    }
+}
+
+impl Background for Constant {
+    fn radiance(&self, _dir: &Vec3) -> Vec3 {
+        self.color
+    }
+}
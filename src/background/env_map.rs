@@ -0,0 +1,212 @@
+use crate::consts::PI;
+
+use crate::Vec3;
+use crate::image::HdrImage;
+use super::{Background, EnvSample, Float};
+
+/// An equirectangular (latitude/longitude) environment map. The top row of
+/// the image is straight up (+y) and the center column looks down -z.
+pub struct EnvMap {
+    image: HdrImage,
+    marginal: Distribution,
+    conditional: Vec<Distribution>,
+}
+
+impl EnvMap {
+    pub fn new(image: HdrImage) -> Self {
+        let (w, h) = (image.width(), image.height());
+
+        // weight by sin(theta) since rows near the poles cover less solid angle
+        let conditional: Vec<Distribution> = (0..h)
+            .map(|y| {
+                let sin_theta = (PI * (y as Float + 0.5) / h as Float).sin();
+                let row: Vec<Float> = (0..w)
+                    .map(|x| luminance(image.pixel(x, y)) * sin_theta)
+                    .collect();
+                Distribution::new(&row)
+            })
+            .collect();
+        let marginal = Distribution::new(
+            &conditional.iter().map(|d| d.integral).collect::<Vec<Float>>(),
+        );
+
+        Self {
+            image,
+            marginal,
+            conditional,
+        }
+    }
+
+    fn texel(&self, u: Float, v: Float) -> (usize, usize) {
+        let x = ((u * self.image.width() as Float) as usize).min(self.image.width() - 1);
+        let y = ((v * self.image.height() as Float) as usize).min(self.image.height() - 1);
+        (x, y)
+    }
+}
+
+impl Background for EnvMap {
+    fn radiance(&self, dir: &Vec3) -> Vec3 {
+        let (u, v) = dir_to_uv(dir);
+        let (x, y) = self.texel(u, v);
+        *self.image.pixel(x, y)
+    }
+
+    fn sample(&self, u: Float, v: Float) -> Option<EnvSample> {
+        if self.marginal.integral == 0.0 {
+            return None;
+        }
+
+        let (sv, row) = self.marginal.sample(v);
+        let (su, _) = self.conditional[row].sample(u);
+        let dir = uv_to_dir(su, sv);
+        let pdf = self.pdf(&dir);
+        if pdf == 0.0 {
+            return None;
+        }
+
+        Some(EnvSample {
+            radiance: self.radiance(&dir),
+            dir,
+            pdf,
+        })
+    }
+
+    fn is_sampled(&self) -> bool {
+        true
+    }
+
+    fn pdf(&self, dir: &Vec3) -> Float {
+        let (u, v) = dir_to_uv(dir);
+        let sin_theta = (v * PI).sin();
+        if sin_theta == 0.0 || self.marginal.integral == 0.0 {
+            return 0.0;
+        }
+
+        let (x, y) = self.texel(u, v);
+        let uv_pdf = self.conditional[y].func[x] / self.marginal.integral;
+        uv_pdf / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+fn luminance(c: &Vec3) -> Float {
+    0.2126 * c.r() + 0.7152 * c.g() + 0.0722 * c.b()
+}
+
+fn dir_to_uv(dir: &Vec3) -> (Float, Float) {
+    let d = Vec3::unit_vector(*dir);
+    let theta = d.y().clamp(-1.0, 1.0).acos();
+    let phi = d.x().atan2(-d.z());
+    ((phi + PI) / (2.0 * PI), theta / PI)
+}
+
+fn uv_to_dir(u: Float, v: Float) -> Vec3 {
+    let (sin_theta, cos_theta) = (v * PI).sin_cos();
+    let (sin_phi, cos_phi) = (u * 2.0 * PI - PI).sin_cos();
+    Vec3::new(sin_theta * sin_phi, cos_theta, -sin_theta * cos_phi)
+}
+
+/// Piecewise constant 1D distribution over [0, 1).
+struct Distribution {
+    func: Vec<Float>,
+    cdf: Vec<Float>,
+    integral: Float,
+}
+
+impl Distribution {
+    fn new(func: &[Float]) -> Self {
+        let n = func.len() as Float;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for f in func {
+            let last = cdf[cdf.len() - 1];
+            cdf.push(last + f / n);
+        }
+
+        let integral = cdf[func.len()];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as Float / n
+            };
+        }
+
+        Self {
+            func: func.to_vec(),
+            cdf,
+            integral,
+        }
+    }
+
+    /// Returns a continuous sample in [0, 1) along with the bucket it fell in.
+    fn sample(&self, u: Float) -> (Float, usize) {
+        // last cdf entry <= u
+        let i = (self.cdf.partition_point(|c| *c <= u) - 1).min(self.func.len() - 1);
+
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let du = if width > 0.0 {
+            (u - self.cdf[i]) / width
+        } else {
+            0.0
+        };
+
+        ((i as Float + du) / self.func.len() as Float, i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_bright_texel(x: usize, y: usize) -> EnvMap {
+        let (w, h) = (8, 4);
+        let mut pixels = vec![Vec3::origin(); w * h];
+        pixels[y * w + x] = Vec3::new(4.0, 4.0, 4.0);
+        EnvMap::new(HdrImage::new(w, h, pixels))
+    }
+
+    #[test]
+    fn uv_and_directions_round_trip() {
+        for &(u, v) in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7), (0.25, 0.95)] {
+            let (u2, v2) = dir_to_uv(&uv_to_dir(u, v));
+            assert!((u - u2).abs() < 1e-5 && (v - v2).abs() < 1e-5, "{:?} became {:?}", (u, v), (u2, v2));
+        }
+    }
+
+    #[test]
+    fn distribution_skips_empty_buckets() {
+        let d = Distribution::new(&[0.0, 2.0, 0.0, 2.0]);
+        assert_eq!(d.integral, 1.0);
+        assert_eq!(d.sample(0.0).1, 1);
+        assert_eq!(d.sample(0.49).1, 1);
+        assert_eq!(d.sample(0.5).1, 3);
+        let (x, _) = d.sample(0.25);
+        assert!((x - 0.375).abs() < 1e-6);
+    }
+
+    #[test]
+    fn all_black_distribution_is_uniform() {
+        let d = Distribution::new(&[0.0; 4]);
+        assert_eq!(d.integral, 0.0);
+        assert_eq!(d.sample(0.6).1, 2);
+    }
+
+    #[test]
+    fn samples_land_on_the_only_bright_texel() {
+        let env = single_bright_texel(5, 2);
+        for &(u, v) in &[(0.0, 0.0), (0.3, 0.8), (0.99, 0.5)] {
+            let s = env.sample(u, v).expect("a bright map can be sampled");
+            let (su, sv) = dir_to_uv(&s.dir);
+            assert_eq!(env.texel(su, sv), (5, 2));
+            assert_eq!(s.radiance, Vec3::new(4.0, 4.0, 4.0));
+            assert!((s.pdf - env.pdf(&s.dir)).abs() < 1e-3 * s.pdf);
+        }
+    }
+
+    #[test]
+    fn black_map_isnt_sampled() {
+        let env = EnvMap::new(HdrImage::new(2, 2, vec![Vec3::origin(); 4]));
+        assert!(env.sample(0.5, 0.5).is_none());
+        assert_eq!(env.pdf(&Vec3::new(0.0, 0.0, -1.0)), 0.0);
+    }
+}
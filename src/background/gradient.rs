@@ -0,0 +1,32 @@
+use crate::Vec3;
+use super::Background;
+
+/// Blends linearly from `bottom` to `top` along the y axis.
+pub struct Gradient {
+    bottom: Vec3,
+    top: Vec3,
+}
+
+impl Gradient {
+    pub fn new(bottom: Vec3, top: Vec3) -> Self {
+        Self {
+            bottom,
+            top,
+        }
+    }
+}
+
+impl Default for Gradient {
+    /// The white to light blue sky used throughout the book.
+    fn default() -> Self {
+        Self::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for Gradient {
+    fn radiance(&self, dir: &Vec3) -> Vec3 {
+        let unit_dir = Vec3::unit_vector(*dir);
+        let t = 0.5 * (unit_dir.y() + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
@@ -0,0 +1,104 @@
+mod constant;
+mod gradient;
+mod env_map;
+
+use std::sync::Arc;
+
+use super::{Float, Mat3, Vec3};
+
+pub use constant::Constant;
+pub use gradient::Gradient;
+pub use env_map::EnvMap;
+
+/// Radiance arriving from infinitely far away, looked up by direction.
+pub trait Background: Send + Sync {
+    /// Radiance arriving along the (not necessarily unit) direction `dir`.
+    fn radiance(&self, dir: &Vec3) -> Vec3;
+
+    /// Picks a direction proportional to the emitted radiance from two
+    /// uniform numbers in [0, 1). Backgrounds that can't be importance
+    /// sampled return `None` and are only picked up by escaping rays.
+    fn sample(&self, _u: Float, _v: Float) -> Option<EnvSample> {
+        None
+    }
+
+    fn is_sampled(&self) -> bool {
+        false
+    }
+
+    /// Solid angle density that `sample` would pick `dir` with.
+    fn pdf(&self, _dir: &Vec3) -> Float {
+        0.0
+    }
+}
+
+pub struct EnvSample {
+    pub dir: Vec3,
+    pub radiance: Vec3,
+    pub pdf: Float,
+}
+
+/// A `Background` placed in the world with a rotation and intensity scale.
+#[derive(Clone)]
+pub struct Environment {
+    background: Arc<dyn Background>,
+    intensity: Float,
+    // local to world
+    rotation: Mat3,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new(Arc::new(Gradient::default()), 1.0)
+    }
+}
+
+impl Environment {
+    pub fn new(background: Arc<dyn Background>, intensity: Float) -> Self {
+        Self {
+            background,
+            intensity,
+            rotation: Mat3::identity(),
+        }
+    }
+
+    /// Rotates the background `degrees` counter-clockwise around `axis`.
+    pub fn set_rotation(&mut self, axis: &Vec3, degrees: Float) {
+        self.rotation = Mat3::rotation(*axis, degrees);
+    }
+
+    pub fn set_intensity(&mut self, intensity: Float) {
+        self.intensity = intensity;
+    }
+
+    /// Whether direct lighting should sample the environment instead of
+    /// waiting for diffuse bounces to escape.
+    pub fn is_sampled(&self) -> bool {
+        self.background.is_sampled()
+    }
+
+    pub fn radiance(&self, dir: &Vec3) -> Vec3 {
+        self.intensity * self.background.radiance(&self.to_local(dir))
+    }
+
+    pub fn sample(&self, u: Float, v: Float) -> Option<EnvSample> {
+        self.background.sample(u, v).map(|s| EnvSample {
+            dir: self.to_world(&s.dir),
+            radiance: self.intensity * s.radiance,
+            pdf: s.pdf,
+        })
+    }
+
+    pub fn pdf(&self, dir: &Vec3) -> Float {
+        self.background.pdf(&self.to_local(dir))
+    }
+
+    fn to_world(&self, v: &Vec3) -> Vec3 {
+        self.rotation * *v
+    }
+
+    fn to_local(&self, v: &Vec3) -> Vec3 {
+        // the inverse of a rotation is its transpose
+        self.rotation.transpose() * *v
+    }
+}
@@ -21,7 +21,7 @@ fn main() {
             let ig = (255.99 * g) as u8;
             let ib = (255.99 * b) as u8;
 
-            write!(f, "{} {} {}\n", ir, ig, ib)
+            writeln!(f, "{} {} {}", ir, ig, ib)
                 .expect("couldn't write pixel value");
         }
     }
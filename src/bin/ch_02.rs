@@ -2,7 +2,7 @@
 use std::fs::File;
 use std::io::Write;
 
-use raytracing::Vec3;
+use raytracing::{Float, Vec3};
 
 fn main() {
     let mut f = File::create("ch-02.ppm")
@@ -17,8 +17,8 @@ fn main() {
     for y in (0..height).rev() {
         for x in 0..width {
             let color = Vec3::new(
-                x as f64 / width as f64,
-                y as f64 / height as f64,
+                x as Float / width as Float,
+                y as Float / height as Float,
                 0.2
             );
 
@@ -26,7 +26,7 @@ fn main() {
             let ig = (255.99 * color[1]) as u8;
             let ib = (255.99 * color[2]) as u8;
 
-            write!(f, "{} {} {}\n", ir, ig, ib)
+            writeln!(f, "{} {} {}", ir, ig, ib)
                 .expect("unable to write pixel");
         }
     }
@@ -3,9 +3,9 @@ use std::io::Write;
 use std::sync::Arc;
 
 use raytracing::hit::{Hit, HitableList};
-use raytracing::material::Lambertian;
+use raytracing::material::{Lambertian, Materials};
 use raytracing::sphere::Sphere;
-use raytracing::{Ray, Vec3};
+use raytracing::{Color, Float, Point3, Ray, Vec3};
 
 fn main() {
     let mut f = File::create("ch-05-2.ppm").expect("couldn't open file");
@@ -16,20 +16,20 @@ fn main() {
     let lower_left = Vec3::new(-2.0, -1.0, -1.0);
     let horizontal = Vec3::new(4.0, 0.0, 0.0);
     let vertical = Vec3::new(0.0, 2.0, 0.0);
-    let origin = Vec3::origin();
+    let origin = Point3::origin();
 
-    let world = spheres();
+    let world = spheres(&mut Materials::new());
 
     write!(f, "P3\n{} {}\n255\n", width, height).expect("couldn't write header");
 
     for y in (0..height).rev() {
         for x in 0..width {
-            let u = x as f64 / width as f64;
-            let v = y as f64 / height as f64;
+            let u = x as Float / width as Float;
+            let v = y as Float / height as Float;
 
             let r = Ray::with_values(
-                origin.clone(),
-                &lower_left + u * &horizontal + v * &vertical,
+                origin,
+                lower_left + u * horizontal + v * vertical,
             );
             let color = color(&r, &world);
 
@@ -37,22 +37,22 @@ fn main() {
             let ig = (255.99 * color[1]) as u8;
             let ib = (255.99 * color[2]) as u8;
 
-            write!(f, "{} {} {}\n", ir, ig, ib).expect("unable to write pixel");
+            writeln!(f, "{} {} {}", ir, ig, ib).expect("unable to write pixel");
         }
     }
 }
 
-fn spheres() -> HitableList {
+fn spheres(materials: &mut Materials) -> HitableList {
     let h: Vec<Arc<dyn Hit>> = vec![
         Arc::new(Sphere::new(
             Vec3::new(0.0, 0.0, -1.0),
             0.5,
-            Arc::new(Lambertian::new(Vec3::new(0.8, 0.3, 0.3))),
+            materials.add(Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.3)))),
         )),
         Arc::new(Sphere::new(
             Vec3::new(0.0, -100.5, -1.0),
             100.0,
-            Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.0))),
+            materials.add(Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)))),
         )),
     ];
 
@@ -60,7 +60,7 @@ fn spheres() -> HitableList {
 }
 
 fn color(r: &Ray, world: &HitableList) -> Vec3 {
-    match world.hit(r, 0.0, std::f64::MAX) {
+    match world.hit(r, 0.0, Float::MAX) {
         Some(h) => 0.5 * Vec3::new(h.normal.x() + 1.0, h.normal.y() + 1.0, h.normal.z() + 1.0),
         None => {
             let unit_dir = Vec3::unit_vector(r.direction());
@@ -6,9 +6,9 @@ use rand::Rng;
 
 use raytracing::camera::Camera;
 use raytracing::hit::{Hit, HitableList};
-use raytracing::material::Lambertian;
+use raytracing::material::{Lambertian, Materials};
 use raytracing::sphere::Sphere;
-use raytracing::{Ray, Vec3};
+use raytracing::{Color, Float, Ray, Vec3};
 
 fn main() {
     let mut f = File::create("ch-06.ppm").expect("couldn't open file");
@@ -17,13 +17,13 @@ fn main() {
     let height = 400;
     let num_samples = 100;
 
-    let world = spheres();
+    let world = spheres(&mut Materials::new());
 
-    let aspect = width as f64/height as f64;
+    let aspect = width as Float/height as Float;
     let pos = Vec3::origin();
     let look_at = Vec3::new(0.0, 0.0, -1.0);
     let v_up = Vec3::new(0.0, 1.0, 0.0);
-    let focus_dist = (&pos - &look_at).len();
+    let focus_dist = (pos - look_at).len();
     let aperture = 0.01;
     let camera = Camera::new(pos, look_at, v_up, 90.0, aspect, aperture, focus_dist);
 
@@ -36,37 +36,37 @@ fn main() {
             let mut avg_color = Vec3::origin();
 
             for _ in 0..num_samples {
-                let xr: f64 = rng.gen();
-                let yr: f64 = rng.gen();
-                let u = (x as f64 + xr) / width as f64;
-                let v = (y as f64 + yr) / height as f64;
+                let xr: Float = rng.gen();
+                let yr: Float = rng.gen();
+                let u = (x as Float + xr) / width as Float;
+                let v = (y as Float + yr) / height as Float;
 
                 let r = camera.get_ray(u, v);
                 //let p = r.point_at_param(2.0);
                 avg_color += color(&r, &world);
             }
-            avg_color /= num_samples as f64;
+            avg_color /= num_samples as Float;
 
             let ir = (255.99 * avg_color[0]) as u8;
             let ig = (255.99 * avg_color[1]) as u8;
             let ib = (255.99 * avg_color[2]) as u8;
 
-            write!(f, "{} {} {}\n", ir, ig, ib).expect("unable to write pixel");
+            writeln!(f, "{} {} {}", ir, ig, ib).expect("unable to write pixel");
         }
     }
 }
 
-fn spheres() -> HitableList {
+fn spheres(materials: &mut Materials) -> HitableList {
     let h: Vec<Arc<dyn Hit>> = vec![
         Arc::new(Sphere::new(
             Vec3::new(0.0, 0.0, -1.0),
             0.5,
-            Arc::new(Lambertian::new(Vec3::new(0.8, 0.3, 0.3))),
+            materials.add(Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.3)))),
         )),
         Arc::new(Sphere::new(
             Vec3::new(0.0, -100.5, -1.0),
             100.0,
-            Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.0))),
+            materials.add(Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)))),
         )),
     ];
 
@@ -74,7 +74,7 @@ fn spheres() -> HitableList {
 }
 
 fn color(r: &Ray, world: &HitableList) -> Vec3 {
-    match world.hit(r, 0.0, std::f64::MAX) {
+    match world.hit(r, 0.0, Float::MAX) {
         Some(h) => 0.5 * Vec3::new(h.normal.x() + 1.0, h.normal.y() + 1.0, h.normal.z() + 1.0),
         None => {
             let unit_dir = Vec3::unit_vector(r.direction());
@@ -5,10 +5,11 @@ use std::sync::Arc;
 use rand::Rng;
 
 use raytracing::camera::Camera;
+use raytracing::color::Pipeline;
 use raytracing::hit::{Hit, HitableList};
-use raytracing::material::Lambertian;
+use raytracing::material::{Lambertian, Materials};
 use raytracing::sphere::Sphere;
-use raytracing::{Ray, Vec3};
+use raytracing::{Color, Float, Ray, Vec3};
 
 const MAX_BOUNCE: u8 = 10;
 
@@ -19,18 +20,19 @@ fn main() {
     let height = 400;
     let num_samples = 20;
 
-    let world = spheres();
+    let world = spheres(&mut Materials::new());
 
-    let aspect = width as f64/height as f64;
+    let aspect = width as Float/height as Float;
     let pos = Vec3::origin();
     let look_at = Vec3::new(0.0, 0.0, -1.0);
     let v_up = Vec3::new(0.0, 1.0, 0.0);
-    let focus_dist = (&pos - &look_at).len();
+    let focus_dist = (pos - look_at).len();
     let aperture = 0.01;
     let camera = Camera::new(pos, look_at, v_up, 90.0, aspect, aperture, focus_dist);
 
     let mut rng = rand::thread_rng();
 
+    let display = Pipeline::default();
     write!(f, "P3\n{} {}\n255\n", width, height).expect("couldn't write header");
 
     for y in (0..height).rev() {
@@ -38,42 +40,35 @@ fn main() {
             let mut avg_color = Vec3::origin();
 
             for _ in 0..num_samples {
-                let xr: f64 = rng.gen();
-                let yr: f64 = rng.gen();
-                let u = (x as f64 + xr) / width as f64;
-                let v = (y as f64 + yr) / height as f64;
+                let xr: Float = rng.gen();
+                let yr: Float = rng.gen();
+                let u = (x as Float + xr) / width as Float;
+                let v = (y as Float + yr) / height as Float;
 
                 let r = camera.get_ray(u, v);
                 //let p = r.point_at_param(2.0);
                 avg_color += color(&r, &world, 0);
             }
-            avg_color /= num_samples as f64;
-            avg_color = Vec3::new(
-                avg_color[0].sqrt(),
-                avg_color[1].sqrt(),
-                avg_color[2].sqrt(),
-            );
-
-            let ir = (255.99 * avg_color[0]) as u8;
-            let ig = (255.99 * avg_color[1]) as u8;
-            let ib = (255.99 * avg_color[2]) as u8;
-
-            write!(f, "{} {} {}\n", ir, ig, ib).expect("unable to write pixel");
+            avg_color /= num_samples as Float;
+
+            let [ir, ig, ib] = display.to_rgb8(avg_color);
+
+            writeln!(f, "{} {} {}", ir, ig, ib).expect("unable to write pixel");
         }
     }
 }
 
-fn spheres() -> HitableList {
+fn spheres(materials: &mut Materials) -> HitableList {
     let h: Vec<Arc<dyn Hit>> = vec![
         Arc::new(Sphere::new(
             Vec3::new(0.0, 0.0, -1.0),
             0.5,
-            Arc::new(Lambertian::new(Vec3::new(0.8, 0.3, 0.3))),
+            materials.add(Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.3)))),
         )),
         Arc::new(Sphere::new(
             Vec3::new(0.0, -100.5, -1.0),
             100.0,
-            Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.0))),
+            materials.add(Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)))),
         )),
     ];
 
@@ -88,11 +83,11 @@ fn color(r: &Ray, world: &HitableList, bounces: u8) -> Vec3 {
         return (1.0 - t) * Vec3::new(1.0, 1.0, 1.0) + t * Vec3::new(0.5, 0.7, 1.0);
     }
 
-    match world.hit(r, 0.001, std::f64::MAX) {
+    match world.hit(r, 0.0, Float::MAX) {
         Some(h) => {
-            let target = &h.point + &h.normal + random_in_unit_sphere();
+            let target = h.point + *h.normal + random_in_unit_sphere();
             0.5 * color(
-                &Ray::with_values(h.point.clone(), &target - &h.point),
+                &h.spawn_ray(target - h.point),
                 world,
                 bounces + 1,
             )
@@ -109,7 +104,7 @@ fn random_in_unit_sphere() -> Vec3 {
     let mut rng = rand::thread_rng();
     loop {
         let v = 2.0 * Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::new(1.0, 1.0, 1.0);
-        if v.sq_len() >= 1.0 {
+        if v.sq_len() < 1.0 {
             return v;
         }
     }
@@ -0,0 +1,58 @@
+use std::env;
+use std::process;
+
+use raytracing::denoise::Denoiser;
+use raytracing::image::HdrImage;
+
+const USAGE: &str = "usage: denoise [options] <color> <albedo> <normal> <output.pfm>
+
+Denoises a render given its albedo and normal passes, which can be PFM or
+Radiance HDR files such as the ones `riow --aov` writes.
+
+options:
+    --iterations <n>         filter passes, each twice as wide (default 5)";
+
+fn main() {
+    let mut denoiser = Denoiser::new();
+    let mut paths = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--iterations" => {
+                let n = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage());
+                denoiser.set_iterations(n);
+            }
+            a if a.starts_with('-') => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 4 {
+        usage();
+    }
+
+    let load = |path: &str| {
+        HdrImage::load(path).unwrap_or_else(|e| {
+            eprintln!("couldn't load {}: {}", path, e);
+            process::exit(1);
+        })
+    };
+    let (color, albedo, normal) = (load(&paths[0]), load(&paths[1]), load(&paths[2]));
+    if (albedo.width(), albedo.height()) != (color.width(), color.height())
+        || (normal.width(), normal.height()) != (color.width(), color.height())
+    {
+        eprintln!("the passes must be the same size as the image");
+        process::exit(1);
+    }
+
+    let out = denoiser.denoise(&color, &albedo, &normal);
+    out.save_pfm(&paths[3]).unwrap_or_else(|e| {
+        eprintln!("couldn't write {}: {}", paths[3], e);
+        process::exit(1);
+    });
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
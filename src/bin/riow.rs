@@ -0,0 +1,1076 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{self, Command};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use rayon::prelude::*;
+
+use raytracing::animation::{Animation, Channel};
+use raytracing::aov::{Aov, Aovs};
+use raytracing::background::{Background, Constant, EnvMap, Environment, Gradient};
+use raytracing::camera::Camera;
+use raytracing::checkpoint::Checkpoint;
+use raytracing::denoise::Denoiser;
+use raytracing::distributed::{self, Job, Setup, Worker};
+use raytracing::color::{ColorSpace, Pipeline, ToneMap};
+use raytracing::film::{
+    BoxFilter, Film, Filter, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter,
+};
+use raytracing::hit::{Hit, HitRecord};
+use raytracing::image::HdrImage;
+use raytracing::light::{DirectionalLight, Light, PointLight, SpotLight};
+use raytracing::material::{Dielectric, Ior, Lambertian, Metal};
+use raytracing::packet::{RayPacket, WIDTH};
+use raytracing::preview::{Command as PreviewCommand, Preview, View};
+use raytracing::render::{MaxDepth, Renderer};
+use raytracing::scene::{NodeId, SceneGraph, Visibility};
+use raytracing::shape::Plane;
+use raytracing::sphere::Sphere;
+use raytracing::stats::{self, Stats};
+use raytracing::random;
+use raytracing::{Color, Float, Mat4, Ray, Vec3};
+
+const USAGE: &str = "usage: riow [options] [output.ppm]
+
+Writes linear radiance instead if the output ends in .pfm, or a PNG if it
+ends in .png.
+
+options:
+    --width <px>             image width (default 800)
+    --height <px>            image height (default 400)
+    --samples <n>            samples per pixel (default 100)
+    --env <file>             equirectangular .hdr or .pfm environment map
+    --env-color <r,g,b>      constant environment color
+    --env-rotate <degrees>   rotate the environment around the y axis
+    --env-intensity <x>      scale the environment radiance
+    --glass <ior>            refractive index of the glass sphere, or bk7 or
+                             sf11 for real glasses with dispersion
+    --spectral               trace wavelengths instead of RGB, which is
+                             needed to see dispersion
+    --exposure <stops>       brighten or darken the image
+    --white-balance <K>      color temperature that should come out white
+    --tonemap <curve>        none, reinhard, aces or agx (default none)
+    --filter <name[:radius]> pixel filter: box, tent, gaussian, mitchell or
+                             lanczos (default box:0.5)
+    --aov <pass>             also write a pass next to the output as
+                             <output>.<pass>.pfm, may be repeated: depth,
+                             normal, albedo, object, material, position or
+                             light:<index>
+    --denoise                filter out noise guided by albedo and normals
+    --seed <n>               seed for the random numbers (default 0)
+    --checkpoint <file>      save progress to file every so often, and after
+                             the last sample
+    --checkpoint-interval <s>
+                             seconds between checkpoints (default 60)
+    --resume <file>          carry on from a checkpoint up to --samples,
+                             given the same scene options as before
+    --spawn <n>              render on n worker processes on this machine
+    --connect <host:port>    render on a worker started with --listen, may
+                             be repeated and combined with --spawn
+    --listen <host:port>     be a worker for coordinators that connect, with
+                             the scene options they send
+    --worker                 be a worker talking over stdin and stdout
+    --animation <file>       keyframes for the camera, objects and materials
+    --frames <first-last>    render these frames of the animation, writing
+                             each to the output with its number in place of
+                             any ### or else before the extension
+    --fps <n>                frames per second of the animation (default 24)
+    --time <seconds>         time in the animation to render a single frame
+    --preview <host:port>    serve the render as it refines over HTTP, with
+                             endpoints to move the camera, until killed
+    --stats                  print where rendering time went: rays by depth,
+                             intersection tests, BVH nodes visited, how
+                             paths ended and time per tile
+    --stats-json <file>      also write the statistics as JSON, numbered
+                             like the output when rendering --frames
+    --max-depth <d,s,t>      most diffuse, specular and transmission bounces
+                             in a path, or one number for all three
+                             (default 10,32,32)
+    --roulette <depth|off>   bounces before Russian roulette starts ending
+                             paths that carry little light (default 3)
+    --packets                trace camera rays through the BVH in packets,
+                             which is faster for scenes with many objects
+    --visible <node>=<rays>  which rays see an object and those under it:
+                             camera, shadow and reflection separated by
+                             commas, or none; may be repeated. Nodes are
+                             diffuse, ground, metal, glass and air, which
+                             is inside glass
+    --color-space <name>     working space the scene colors are in: srgb,
+                             p3, rec2020 or acescg (default srgb)
+    --light <spec>           add a light, may be repeated:
+                               point:<x,y,z>:<r,g,b>
+                               spot:<x,y,z>:<look x,y,z>:<r,g,b>:<inner deg>,<outer deg>
+                               sun:<dir x,y,z>:<r,g,b>";
+
+struct Options {
+    output: String,
+    width: usize,
+    height: usize,
+    num_samples: usize,
+    environment: Environment,
+    lights: Vec<Arc<dyn Light>>,
+    glass: Ior,
+    spectral: bool,
+    display: Pipeline,
+    filter: Arc<dyn Filter>,
+    aovs: Vec<Aov>,
+    denoise: bool,
+    seed: u64,
+    checkpoint: Option<String>,
+    checkpoint_interval: Duration,
+    resume: Option<String>,
+    worker: bool,
+    listen: Option<String>,
+    spawn: usize,
+    connect: Vec<String>,
+    animation: Animation,
+    frames: Option<(u32, u32)>,
+    fps: Float,
+    time: Float,
+    preview: Option<String>,
+    stats: bool,
+    stats_json: Option<String>,
+    max_depth: MaxDepth,
+    roulette_depth: Option<u32>,
+    packets: bool,
+    visibility: Vec<(String, Visibility)>,
+}
+
+/// Rows of pixels rendered together by one thread.
+const TILE_ROWS: usize = 16;
+
+/// Rows of pixels in each job handed to a worker.
+const JOB_ROWS: usize = 32;
+
+/// Time between images published to the preview server.
+const PREVIEW_INTERVAL: Duration = Duration::from_millis(250);
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let opts = parse_args(args.clone()).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    stats::enable(opts.stats || opts.stats_json.is_some());
+    if opts.worker {
+        let stdin = io::stdin();
+        distributed::serve(stdin.lock(), io::stdout(), start_worker).unwrap_or_else(|e| {
+            eprintln!("worker stopped: {}", e);
+            process::exit(1);
+        });
+        return;
+    }
+    if let Some(addr) = &opts.preview {
+        preview(&opts, addr).unwrap_or_else(|e| {
+            eprintln!("preview server failed on {}: {}", addr, e);
+            process::exit(1);
+        });
+        return;
+    }
+    if let Some(addr) = &opts.listen {
+        listen(addr).unwrap_or_else(|e| {
+            eprintln!("couldn't listen on {}: {}", addr, e);
+            process::exit(1);
+        });
+        return;
+    }
+
+    match opts.frames {
+        Some((first, last)) => {
+            for frame in first..=last {
+                let output = frame_path(&opts.output, frame);
+                let stats = opts.stats_json.as_ref().map(|p| frame_path(p, frame));
+                render_frame(&opts, &args, frame as Float / opts.fps, &output, stats.as_deref());
+            }
+        }
+        None => render_frame(&opts, &args, opts.time, &opts.output, opts.stats_json.as_deref()),
+    }
+}
+
+/// Renders the scene as it is at `time` and writes it to `output`, and the
+/// statistics to `stats_path` if given.
+fn render_frame(opts: &Options, args: &[String], time: Float, output: &str, stats_path: Option<&str>) {
+    let start = Instant::now();
+    let scene = Scene::new(opts, time, &view(opts, time));
+    let (width, height, num_samples) = (scene.width, scene.height, opts.num_samples as u64);
+
+    // the beauty image followed by the passes
+    let mut films = scene.films(&opts.filter);
+    let mut progress = Checkpoint {
+        seed: opts.seed,
+        samples: 0,
+        scene: scene_args(args, time).join(" "),
+    };
+    if let Some(path) = &opts.resume {
+        progress = Checkpoint::load(path, &progress.scene, &mut films).unwrap_or_else(|e| {
+            eprintln!("couldn't resume from {}: {}", path, e);
+            process::exit(1);
+        });
+    }
+    let seed = progress.seed;
+
+    if opts.spawn > 0 || !opts.connect.is_empty() {
+        let workers = start_workers(opts).unwrap_or_else(|e| {
+            eprintln!("couldn't start workers: {}", e);
+            process::exit(1);
+        });
+        let setup = Setup {
+            seed,
+            args: scene_args(args, time),
+        };
+        let jobs = Job::split(height, JOB_ROWS, (progress.samples.min(num_samples), num_samples));
+        distributed::render(workers, &setup, &jobs, &mut films).unwrap_or_else(|e| {
+            eprintln!("distributed render failed: {}", e);
+            process::exit(1);
+        });
+
+        progress.samples = progress.samples.max(num_samples);
+        if let Some(path) = &opts.checkpoint {
+            progress
+                .save(path, &films)
+                .unwrap_or_else(|e| eprintln!("couldn't write checkpoint {}: {}", path, e));
+        }
+    }
+
+    // every pixel gets one sample per pass, so the films add up the same way
+    // however many runs the render is split over
+    let mut last_save = Instant::now();
+    for s in progress.samples..num_samples {
+        for tile in &scene.render_rows(&films, (0, height), TILE_ROWS, (s, s + 1), seed) {
+            for (f, t) in films.iter_mut().zip(tile.iter()) {
+                f.merge(t);
+            }
+        }
+
+        progress.samples = s + 1;
+        if let Some(path) = &opts.checkpoint {
+            if last_save.elapsed() >= opts.checkpoint_interval || progress.samples == num_samples {
+                progress
+                    .save(path, &films)
+                    .unwrap_or_else(|e| eprintln!("couldn't write checkpoint {}: {}", path, e));
+                last_save = Instant::now();
+            }
+        }
+    }
+
+    let passes = &scene.passes;
+    if stats::is_enabled() {
+        let mut stats = scene.stats.lock().unwrap();
+        stats.wall_time = start.elapsed();
+        if opts.stats {
+            eprintln!("{}", stats);
+        }
+        if let Some(path) = stats_path {
+            File::create(path)
+                .and_then(|mut f| stats.write_json(&mut f))
+                .unwrap_or_else(|e| eprintln!("couldn't write {}: {}", path, e));
+        }
+    }
+
+    let mut beauty = films[0].to_image();
+    let pass = |aov| films[1 + passes.iter().position(|p| *p == aov).unwrap()].to_image();
+    if opts.denoise {
+        beauty = Denoiser::new().denoise(&beauty, &pass(Aov::Albedo), &pass(Aov::Normal));
+    }
+
+    if output.ends_with(".pfm") {
+        beauty.save_pfm(output).expect("couldn't write image");
+    } else if output.ends_with(".png") {
+        beauty.save_png(output, &opts.display).expect("couldn't write image");
+    } else {
+        write_ppm(output, &beauty, &opts.display).expect("couldn't write image");
+    }
+
+    let ids = if opts.aovs.iter().any(|a| a.is_id()) {
+        first_hit_ids(&scene.renderer, &scene.camera, (width, height), seed)
+    } else {
+        Vec::new()
+    };
+    for aov in &opts.aovs {
+        let image = if aov.is_id() {
+            id_image(*aov, &ids, width, height)
+        } else {
+            pass(*aov)
+        };
+        let path = Path::new(output).with_extension(format!("{}.pfm", aov.name()));
+        image
+            .save_pfm(&path)
+            .unwrap_or_else(|e| eprintln!("couldn't write {}: {}", path.display(), e));
+    }
+}
+
+/// Where the camera is at `time`.
+fn view(opts: &Options, time: Float) -> View {
+    let animation = &opts.animation;
+    View {
+        position: animation.vec3_at("camera.position", time, Vec3::new(3.0, 3.0, 2.0)),
+        look_at: animation.vec3_at("camera.look_at", time, Vec3::new(0.0, 0.0, -1.0)),
+        fov: animation.float_at("camera.fov", time, 20.0),
+    }
+}
+
+/// Renders progressively for a preview server until killed, starting over
+/// whenever a client moves the camera.
+fn preview(opts: &Options, addr: &str) -> io::Result<()> {
+    let mut view = view(opts, opts.time);
+    let server = Preview::start(addr, view)?;
+    eprintln!("preview at http://{}/", server.local_addr());
+
+    let num_samples = opts.num_samples as u64;
+    let mut scene = Scene::new(opts, opts.time, &view);
+    let mut films = scene.films(&opts.filter);
+    let mut s = 0;
+    let mut last_publish = Instant::now();
+    loop {
+        match server.command(s == num_samples) {
+            Some(PreviewCommand::Move(v)) => {
+                view = v;
+                scene = Scene::new(opts, opts.time, &view);
+                films = scene.films(&opts.filter);
+                s = 0;
+            }
+            Some(PreviewCommand::Restart) => {
+                films = scene.films(&opts.filter);
+                s = 0;
+            }
+            None if s == num_samples => return Ok(()),
+            None => {}
+        }
+        if s == num_samples {
+            continue;
+        }
+
+        for tile in &scene.render_rows(&films, (0, scene.height), TILE_ROWS, (s, s + 1), opts.seed) {
+            for (f, t) in films.iter_mut().zip(tile.iter()) {
+                f.merge(t);
+            }
+        }
+        s += 1;
+
+        // encoding every pass of a small image would take longer than it
+        if s == 1 || s == num_samples || last_publish.elapsed() >= PREVIEW_INTERVAL {
+            server.publish(&films[0].to_image(), &opts.display, s, num_samples);
+            last_publish = Instant::now();
+        }
+    }
+}
+
+/// What the options describe for rendering samples.
+struct Scene {
+    renderer: Renderer,
+    camera: Camera,
+    /// Passes that blend like the beauty image, which get a film each. IDs
+    /// are taken from the first sample in each pixel instead.
+    passes: Vec<Aov>,
+    width: usize,
+    height: usize,
+    packets: bool,
+    /// Counted while rendering bands, if `stats` is enabled.
+    stats: Mutex<Stats>,
+}
+
+impl Scene {
+    fn new(opts: &Options, time: Float, view: &View) -> Scene {
+        let (width, height) = (opts.width, opts.height);
+        let animation = &opts.animation;
+
+        let mut graph = scene_graph(opts.glass, animation, time);
+        for (name, visibility) in &opts.visibility {
+            let node = graph.find(name).expect("--visible names are checked against NODES");
+            graph.set_visibility(node, *visibility);
+        }
+        let mut renderer = graph.compile(opts.environment.clone()).expect("the scene only uses its own materials");
+        renderer.set_spectral(opts.spectral);
+        renderer.set_max_depth(opts.max_depth);
+        renderer.set_roulette_depth(opts.roulette_depth);
+        for light in &opts.lights {
+            renderer.add_light(light.clone());
+        }
+
+        let aspect = width as Float / height as Float;
+        let View { position: pos, look_at, fov } = *view;
+        let v_up = View::UP;
+        let focus_dist = animation.float_at("camera.focus_distance", time, (pos - look_at).len());
+        let aperture = animation.float_at("camera.aperture", time, 0.1);
+        let camera = Camera::new(pos, look_at, v_up, fov, aspect, aperture, focus_dist);
+
+        let mut passes: Vec<Aov> = opts.aovs.iter().copied().filter(|a| !a.is_id()).collect();
+        if opts.denoise {
+            for aov in [Aov::Albedo, Aov::Normal] {
+                if !passes.contains(&aov) {
+                    passes.push(aov);
+                }
+            }
+        }
+
+        Scene {
+            renderer,
+            camera,
+            passes,
+            width,
+            height,
+            packets: opts.packets,
+            stats: Mutex::new(Stats::default()),
+        }
+    }
+
+    /// Empty films for the beauty image followed by the passes.
+    fn films(&self, filter: &Arc<dyn Filter>) -> Vec<Film> {
+        vec![Film::new(self.width, self.height, filter.clone()); self.passes.len() + 1]
+    }
+
+    /// Renders samples `s0..s1` of the rows `y0..y1` in parallel, in bands
+    /// of `band_rows` rows. Returns tiles of `films` for every band, to be
+    /// merged in order.
+    fn render_rows(
+        &self,
+        films: &[Film],
+        (y0, y1): (usize, usize),
+        band_rows: usize,
+        (s0, s1): (u64, u64),
+        seed: u64,
+    ) -> Vec<Vec<Film>> {
+        let (width, height) = (self.width, self.height);
+        let bands: Vec<usize> = (y0..y1.min(height)).step_by(band_rows).collect();
+        bands
+            .par_iter()
+            .map(|&b0| {
+                let b1 = (b0 + band_rows).min(y1).min(height);
+                let job = Job {
+                    rows: (b0, b1),
+                    samples: (s0, s1),
+                };
+                let mut tiles = job.tiles(films);
+                let start = Instant::now();
+                // counts from outside bands, like the ID pass, don't belong
+                stats::take();
+
+                for s in s0..s1 {
+                    for y in b0..b1 {
+                        if self.packets {
+                            self.render_packets(&mut tiles, y, seed, s);
+                            continue;
+                        }
+                        for x in 0..width {
+                            let (fx, fy, ray) = camera_sample(&self.camera, (x, y), (width, height), seed, s);
+                            self.add_sample(&mut tiles, (fx, fy), &ray, self.renderer.first_hit(&ray));
+                        }
+                    }
+                }
+
+                if stats::is_enabled() {
+                    let mut stats = self.stats.lock().unwrap();
+                    stats.merge(&stats::take());
+                    stats.add_tile((b0, b1), start.elapsed());
+                }
+                tiles
+            })
+            .collect()
+    }
+
+    /// Renders sample `s` of row `y` with the camera rays of neighbouring
+    /// pixels traced as packets. Each pixel's random numbers are put aside
+    /// while the packet is traced, so this gives the same image as tracing
+    /// them one at a time.
+    fn render_packets(&self, tiles: &mut [Film], y: usize, seed: u64, s: u64) {
+        let (width, height) = (self.width, self.height);
+        for x0 in (0..width).step_by(WIDTH) {
+            let samples: Vec<_> = (x0..(x0 + WIDTH).min(width))
+                .map(|x| (camera_sample(&self.camera, (x, y), (width, height), seed, s), random::save()))
+                .collect();
+            let rays: Vec<Ray> = samples.iter().map(|((_, _, ray), _)| *ray).collect();
+            let hits = self.renderer.hit_packet(&RayPacket::new(&rays));
+            for (((fx, fy, ray), rng), hit) in samples.into_iter().zip(hits) {
+                random::restore(rng);
+                self.add_sample(tiles, (fx, fy), &ray, hit);
+            }
+        }
+    }
+
+    /// Traces a camera sample whose first hit is `hit` and adds its color
+    /// and passes to the tiles.
+    fn add_sample(&self, tiles: &mut [Film], (fx, fy): (Float, Float), ray: &Ray, hit: Option<HitRecord>) {
+        if self.passes.is_empty() {
+            tiles[0].add_sample(fx, fy, self.renderer.color_from(ray, hit));
+            return;
+        }
+
+        let (color, aovs) = self.renderer.color_and_aovs(ray, hit);
+        tiles[0].add_sample(fx, fy, color);
+        for (aov, f) in self.passes.iter().zip(tiles[1..].iter_mut()) {
+            f.add_sample(fx, fy, aovs.get(*aov));
+        }
+    }
+}
+
+/// Builds the scene a coordinator sets up, and renders its jobs a row at a
+/// time so every thread has work even for small jobs.
+fn start_worker(setup: &Setup) -> Result<impl FnMut(&Job) -> Result<Vec<Film>, String>, String> {
+    let opts = parse_args(setup.args.clone())?;
+    let scene = Scene::new(&opts, opts.time, &view(&opts, opts.time));
+    let films = scene.films(&opts.filter);
+    let seed = setup.seed;
+
+    Ok(move |job: &Job| {
+        job.check(films[0].height())?;
+        let mut tiles = job.tiles(&films);
+        for band in scene.render_rows(&films, job.rows, 1, job.samples, seed) {
+            for (t, b) in tiles.iter_mut().zip(band.iter()) {
+                t.merge(b);
+            }
+        }
+        Ok(tiles)
+    })
+}
+
+/// Serves coordinators one after another.
+fn listen(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream.peer_addr()?;
+        let reader = BufReader::new(stream.try_clone()?);
+        if let Err(e) = distributed::serve(reader, stream, start_worker) {
+            eprintln!("lost coordinator {}: {}", peer, e);
+        }
+    }
+    Ok(())
+}
+
+fn start_workers(opts: &Options) -> io::Result<Vec<Worker>> {
+    let mut workers = Vec::new();
+    for _ in 0..opts.spawn {
+        let mut command = Command::new(env::current_exe()?);
+        command.arg("--worker");
+        workers.push(Worker::spawn(command)?);
+    }
+    for addr in &opts.connect {
+        let worker = Worker::connect(addr.as_str())
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", addr, e)))?;
+        workers.push(worker);
+    }
+    Ok(workers)
+}
+
+/// Options that don't take a value.
+const FLAGS: &[&str] = &["--spectral", "--denoise", "--worker", "--stats", "--packets", "-h", "--help"];
+
+/// The options that decide what the films of the frame at `time` hold,
+/// which workers are set up with and checkpoints are saved with: all but
+/// those for how many samples to take, where to write the image and how the
+/// render is run.
+fn scene_args(args: &[String], time: Float) -> Vec<String> {
+    let mut scene = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let takes_value = arg.starts_with('-') && !FLAGS.contains(&arg.as_str());
+        let value = if takes_value { args.next() } else { None };
+        match arg.as_str() {
+            "--samples" | "--checkpoint" | "--checkpoint-interval" | "--resume" | "--spawn" | "--connect"
+            | "--listen" | "--worker" | "--preview" | "--stats" | "--stats-json" | "--frames" | "--fps"
+            | "--time" => {}
+            // the output
+            a if !a.starts_with('-') => {}
+            _ => {
+                scene.push(arg.clone());
+                scene.extend(value.cloned());
+            }
+        }
+    }
+    scene.extend([String::from("--time"), time.to_string()]);
+    scene
+}
+
+/// `output` with the frame number in place of a run of `#`, or before the
+/// extension if there isn't one.
+fn frame_path(output: &str, frame: u32) -> String {
+    if let Some(start) = output.rfind('#') {
+        let start = output[..start].trim_end_matches('#').len();
+        let end = output[start..].find(|c| c != '#').map_or(output.len(), |e| start + e);
+        return format!("{}{:0width$}{}", &output[..start], frame, &output[end..], width = end - start);
+    }
+    let path = Path::new(output);
+    match path.extension() {
+        Some(ext) => path.with_extension(format!("{:04}.{}", frame, ext.to_string_lossy())),
+        None => path.with_extension(format!("{:04}", frame)),
+    }
+    .to_string_lossy()
+    .into_owned()
+}
+
+/// Sample `s` of a pixel: where it lands on the film and its camera ray.
+/// It only depends on the seed and which sample it is.
+fn camera_sample(
+    camera: &Camera,
+    (x, y): (usize, usize),
+    (width, height): (usize, usize),
+    seed: u64,
+    s: u64,
+) -> (Float, Float, Ray) {
+    random::seed_sample(seed, (y * width + x) as u64, s);
+    let mut rng = random::rng();
+
+    let fx = x as Float + rng.gen::<Float>();
+    let fy = y as Float + rng.gen::<Float>();
+    let u = fx / width as Float;
+    let v = 1.0 - fy / height as Float;
+    (fx, fy, camera.get_ray(u, v))
+}
+
+/// Object and material hit by the first sample of every pixel.
+fn first_hit_ids(
+    renderer: &Renderer,
+    camera: &Camera,
+    (width, height): (usize, usize),
+    seed: u64,
+) -> Vec<(Option<usize>, Option<usize>)> {
+    (0..width * height)
+        .into_par_iter()
+        .map(|p| {
+            let (_, _, ray) = camera_sample(camera, (p % width, p / width), (width, height), seed, 0);
+            let hit = renderer.first_hit(&ray);
+            (hit.map(|h| h.object), hit.map(|h| h.material.index()))
+        })
+        .collect()
+}
+
+/// Writes the image for display as a plain text PPM.
+fn write_ppm(path: &str, image: &HdrImage, display: &Pipeline) -> io::Result<()> {
+    let mut f = BufWriter::new(File::create(path)?);
+    write!(f, "P3\n{} {}\n255\n", image.width(), image.height())?;
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let [ir, ig, ib] = display.to_rgb8(*image.pixel(x, y));
+            writeln!(f, "{} {} {}", ir, ig, ib)?;
+        }
+    }
+    f.flush()
+}
+
+/// An object or material ID pass.
+fn id_image(aov: Aov, ids: &[(Option<usize>, Option<usize>)], width: usize, height: usize) -> HdrImage {
+    let pixels = ids
+        .iter()
+        .map(|&(object, material)| {
+            let aovs = Aovs {
+                object,
+                material,
+                ..Aovs::default()
+            };
+            aovs.get(aov)
+        })
+        .collect();
+    HdrImage::new(width, height, pixels)
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut output = String::from("riow.ppm");
+    let (mut width, mut height, mut num_samples) = (800, 400, 100);
+    let mut background: Arc<dyn Background> = Arc::new(Gradient::default());
+    let (mut rotation, mut intensity) = (0.0, 1.0);
+    let mut lights = Vec::new();
+    let mut glass = Ior::Constant(1.5);
+    let mut spectral = false;
+    let mut display = Pipeline::default();
+    let mut filter: Arc<dyn Filter> = Arc::new(BoxFilter::default());
+    let mut aovs = Vec::new();
+    let mut denoise = false;
+    let mut seed = 0;
+    let (mut checkpoint, mut resume) = (None, None);
+    let mut checkpoint_interval = Duration::from_secs(60);
+    let (mut worker, mut listen, mut spawn, mut connect) = (false, None, 0, Vec::new());
+    let mut animation = Animation::new();
+    let (mut frames, mut fps, mut time) = (None, 24.0, 0.0);
+    let mut preview = None;
+    let (mut stats, mut stats_json) = (false, None);
+    let (mut max_depth, mut roulette_depth) = (MaxDepth::default(), Some(3));
+    let mut packets = false;
+    let mut visibility = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--width" => width = parse_num(&value()?)?,
+            "--height" => height = parse_num(&value()?)?,
+            "--samples" => num_samples = parse_num(&value()?)?,
+            "--env" => {
+                let path = value()?;
+                let image = HdrImage::load(&path)
+                    .map_err(|e| format!("couldn't load {}: {}", path, e))?;
+                background = Arc::new(EnvMap::new(image));
+            }
+            "--env-color" => background = Arc::new(Constant::new(parse_vec3(&value()?)?)),
+            "--env-rotate" => rotation = parse_num(&value()?)?,
+            "--env-intensity" => intensity = parse_num(&value()?)?,
+            "--light" => lights.push(parse_light(&value()?)?),
+            "--glass" => glass = parse_ior(&value()?)?,
+            "--spectral" => spectral = true,
+            "--exposure" => display.set_exposure(parse_num(&value()?)?),
+            "--white-balance" => display.set_white_balance(Some(parse_num(&value()?)?)),
+            "--tonemap" => {
+                let name = value()?;
+                let curve = ToneMap::from_name(&name)
+                    .ok_or(format!("unknown tone mapping curve {}", name))?;
+                display.set_tone_map(curve);
+            }
+            "--filter" => filter = parse_filter(&value()?)?,
+            "--denoise" => denoise = true,
+            "--seed" => seed = parse_num(&value()?)?,
+            "--checkpoint" => checkpoint = Some(value()?),
+            "--checkpoint-interval" => {
+                let secs = value()?;
+                checkpoint_interval = Duration::try_from_secs_f64(parse_num(&secs)?)
+                    .map_err(|e| format!("bad checkpoint interval {}: {}", secs, e))?;
+            }
+            "--resume" => resume = Some(value()?),
+            "--worker" => worker = true,
+            "--listen" => listen = Some(value()?),
+            "--spawn" => spawn = parse_num(&value()?)?,
+            "--connect" => connect.push(value()?),
+            "--animation" => {
+                let path = value()?;
+                animation = Animation::load(&path)
+                    .map_err(|e| format!("couldn't load {}: {}", path, e))?;
+            }
+            "--frames" => frames = Some(parse_frames(&value()?)?),
+            "--fps" => fps = parse_num(&value()?)?,
+            "--time" => time = parse_num(&value()?)?,
+            "--preview" => preview = Some(value()?),
+            "--stats" => stats = true,
+            "--stats-json" => stats_json = Some(value()?),
+            "--max-depth" => {
+                let spec = value()?;
+                let d: Vec<u32> = spec.split(',').map(parse_num).collect::<Result<_, _>>()?;
+                max_depth = match d.as_slice() {
+                    [diffuse, specular, transmission] => MaxDepth {
+                        diffuse: *diffuse,
+                        specular: *specular,
+                        transmission: *transmission,
+                    },
+                    [all] => MaxDepth {
+                        diffuse: *all,
+                        specular: *all,
+                        transmission: *all,
+                    },
+                    _ => return Err(format!("expected one or three depths in {}", spec)),
+                };
+            }
+            "--roulette" => {
+                roulette_depth = match value()?.as_str() {
+                    "off" => None,
+                    depth => Some(parse_num(depth)?),
+                }
+            }
+            "--packets" => packets = true,
+            "--visible" => visibility.push(parse_visibility(&value()?)?),
+            "--aov" => {
+                let name = value()?;
+                aovs.push(Aov::from_name(&name).ok_or(format!("unknown pass {}", name))?);
+            }
+            "--color-space" => {
+                let name = value()?;
+                let space = ColorSpace::from_name(&name)
+                    .ok_or(format!("unknown color space {}", name))?;
+                display.set_working_space(space);
+            }
+            "-h" | "--help" => return Err(String::from("riow: a small path tracer")),
+            a if a.starts_with('-') => return Err(format!("unknown option {}", a)),
+            _ => output = arg,
+        }
+    }
+
+    for name in animation.names() {
+        match (CHANNELS.iter().find(|c| c.0 == name), animation.channel(name)) {
+            (Some((_, true)), Some(Channel::Vec3(_))) | (Some((_, false)), Some(Channel::Float(_))) => {}
+            (Some((_, true)), _) => return Err(format!("{} should be animated with x,y,z vectors", name)),
+            (Some((_, false)), _) => return Err(format!("{} should be animated with numbers", name)),
+            (None, _) => return Err(format!("nothing to animate called {}", name)),
+        }
+    }
+    if animation.channel("glass.ior").is_some() && !matches!(glass, Ior::Constant(_)) {
+        return Err(String::from("glass.ior can't be animated for dispersive glass"));
+    }
+    if (stats || stats_json.is_some()) && (spawn > 0 || !connect.is_empty() || listen.is_some()) {
+        // workers keep their counts to themselves
+        return Err(String::from("--stats and --stats-json only count renders on this process"));
+    }
+    if frames.is_some() && (checkpoint.is_some() || resume.is_some()) {
+        return Err(String::from("checkpoints are for single frames, not --frames"));
+    }
+
+    let mut environment = Environment::new(background, intensity);
+    environment.set_rotation(&Vec3::new(0.0, 1.0, 0.0), rotation);
+
+    Ok(Options {
+        output,
+        width,
+        height,
+        num_samples,
+        environment,
+        lights,
+        glass,
+        spectral,
+        display,
+        filter,
+        aovs,
+        denoise,
+        seed,
+        checkpoint,
+        checkpoint_interval,
+        resume,
+        worker,
+        listen,
+        spawn,
+        connect,
+        animation,
+        frames,
+        fps,
+        time,
+        preview,
+        stats,
+        stats_json,
+        max_depth,
+        roulette_depth,
+        packets,
+        visibility,
+    })
+}
+
+fn parse_filter(spec: &str) -> Result<Arc<dyn Filter>, String> {
+    let (name, radius) = match spec.split_once(':') {
+        Some((name, r)) => (name, Some(parse_num(r)?)),
+        None => (spec, None),
+    };
+    Ok(match name {
+        "box" => Arc::new(BoxFilter::new(radius.unwrap_or(0.5))),
+        "tent" => Arc::new(TentFilter::new(radius.unwrap_or(1.0))),
+        "gaussian" => {
+            let r = radius.unwrap_or(1.5);
+            Arc::new(GaussianFilter::new(r, r / 3.0))
+        }
+        "mitchell" => Arc::new(MitchellFilter::new(radius.unwrap_or(2.0), 1.0 / 3.0, 1.0 / 3.0)),
+        "lanczos" => Arc::new(LanczosFilter::new(radius.unwrap_or(3.0))),
+        _ => return Err(format!("unknown filter {}", name)),
+    })
+}
+
+/// A frame like `7`, or a range of them like `1-48`.
+fn parse_frames(range: &str) -> Result<(u32, u32), String> {
+    let (first, last) = match range.split_once('-') {
+        Some((first, last)) => (parse_num(first)?, parse_num(last)?),
+        None => (parse_num(range)?, parse_num(range)?),
+    };
+    if first > last {
+        return Err(format!("frames {} end before they start", range));
+    }
+    Ok((first, last))
+}
+
+fn parse_ior(s: &str) -> Result<Ior, String> {
+    match s {
+        "bk7" => Ok(Ior::BK7),
+        "sf11" => Ok(Ior::SF11),
+        _ => Ok(Ior::Constant(parse_num(s)?)),
+    }
+}
+
+fn parse_light(spec: &str) -> Result<Arc<dyn Light>, String> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    match parts.as_slice() {
+        ["point", pos, color] => Ok(Arc::new(PointLight::new(
+            parse_vec3(pos)?,
+            parse_vec3(color)?,
+        ))),
+        ["spot", pos, look_at, color, angles] => {
+            let (inner, outer) = angles
+                .split_once(',')
+                .ok_or(format!("invalid spot light angles {}", angles))?;
+            Ok(Arc::new(SpotLight::new(
+                parse_vec3(pos)?,
+                parse_vec3(look_at)?,
+                parse_vec3(color)?,
+                parse_num(inner)?,
+                parse_num(outer)?,
+            )))
+        }
+        ["sun", dir, color] => Ok(Arc::new(DirectionalLight::new(
+            parse_vec3(dir)?,
+            parse_vec3(color)?,
+        ))),
+        _ => Err(format!("invalid light {}", spec)),
+    }
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let c: Vec<Float> = s.split(',').map(parse_num).collect::<Result<_, _>>()?;
+    if c.len() != 3 {
+        return Err(format!("expected three components in {}", s));
+    }
+    Ok(Vec3::new(c[0], c[1], c[2]))
+}
+
+fn parse_visibility(s: &str) -> Result<(String, Visibility), String> {
+    let (node, rays) = s.split_once('=').ok_or(format!("expected <node>=<rays> in {}", s))?;
+    if !NODES.contains(&node) {
+        return Err(format!("no node called {}", node));
+    }
+    let mut visibility = Visibility::none();
+    if rays != "none" {
+        for kind in rays.split(',') {
+            match kind {
+                "camera" => visibility.camera = true,
+                "shadow" => visibility.shadow = true,
+                "reflection" => visibility.reflection = true,
+                _ => return Err(format!("unknown kind of ray {}", kind)),
+            }
+        }
+    }
+    Ok((String::from(node), visibility))
+}
+
+fn parse_num<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.trim().parse().map_err(|_| format!("invalid number {}", s))
+}
+
+/// Channels of the scene that can be animated, and whether they're vectors.
+const CHANNELS: &[(&str, bool)] = &[
+    ("camera.position", true),
+    ("camera.look_at", true),
+    ("camera.fov", false),
+    ("camera.aperture", false),
+    ("camera.focus_distance", false),
+    ("diffuse.translate", true),
+    ("diffuse.rotate", true),
+    ("diffuse.scale", true),
+    ("diffuse.albedo", true),
+    ("ground.translate", true),
+    ("ground.rotate", true),
+    ("ground.scale", true),
+    ("ground.albedo", true),
+    ("metal.translate", true),
+    ("metal.rotate", true),
+    ("metal.scale", true),
+    ("metal.albedo", true),
+    ("metal.fuzz", false),
+    ("glass.translate", true),
+    ("glass.rotate", true),
+    ("glass.scale", true),
+    ("glass.ior", false),
+];
+
+/// Nodes of the scene graph, which `--visible` can refer to. The air inside
+/// the glass sphere is a child of it.
+const NODES: &[&str] = &["diffuse", "ground", "metal", "glass", "air"];
+
+/// The scene, with a node and a material for each object, named alike.
+fn scene_graph(glass: Ior, animation: &Animation, time: Float) -> SceneGraph {
+    let color = |name: &str, default: Vec3| Color(animation.vec3_at(name, time, default));
+    let glass = match animation.channel("glass.ior") {
+        Some(_) => Ior::Constant(animation.float_at("glass.ior", time, 1.5)),
+        None => glass,
+    };
+
+    let mut graph = SceneGraph::new();
+    let materials = graph.materials_mut();
+    let diffuse = materials.insert(
+        "diffuse",
+        Arc::new(Lambertian::new(color("diffuse.albedo", Vec3::new(0.8, 0.3, 0.3)))),
+    );
+    let ground = materials.insert(
+        "ground",
+        Arc::new(Lambertian::new(color("ground.albedo", Vec3::new(0.8, 0.8, 0.0)))),
+    );
+    let metal = materials.insert(
+        "metal",
+        Arc::new(Metal::new(
+            color("metal.albedo", Vec3::new(0.8, 0.6, 0.2)),
+            animation.float_at("metal.fuzz", time, 1.0),
+        )),
+    );
+    let glass = materials.insert("glass", Arc::new(Dielectric::with_ior(glass, 0)));
+    // air outranking the glass around it makes the sphere hollow
+    let air = materials.insert("air", Arc::new(Dielectric::with_priority(1.0, 1)));
+
+    // objects are built around `position` unless they're animated, when
+    // they're built around the origin and placed by their node's transform
+    let placed = |name: &str, position: Vec3| match animation.transform_at(name, time, position) {
+        Some(to_world) => (Vec3::origin(), to_world),
+        None => (position, Mat4::identity()),
+    };
+    let add = |graph: &mut SceneGraph, parent: NodeId, name: &str, object: Arc<dyn Hit>, transform: Mat4| {
+        let node = graph.add_object(parent, name, object).expect("riow's nodes have different names");
+        graph.set_transform(node, transform);
+        node
+    };
+
+    let root = graph.root();
+    let (p, to_world) = placed("diffuse", Vec3::new(0.0, 0.0, -1.0));
+    add(&mut graph, root, "diffuse", Arc::new(Sphere::new(p, 0.5, diffuse)), to_world);
+    let (p, to_world) = placed("ground", Vec3::new(0.0, -0.5, 0.0));
+    add(&mut graph, root, "ground", Arc::new(Plane::new(p, Vec3::new(0.0, 1.0, 0.0), ground)), to_world);
+    let (p, to_world) = placed("metal", Vec3::new(1.0, 0.0, -1.0));
+    add(&mut graph, root, "metal", Arc::new(Sphere::new(p, 0.5, metal)), to_world);
+    let (p, to_world) = placed("glass", Vec3::new(-1.0, 0.0, -1.0));
+    let glass = add(&mut graph, root, "glass", Arc::new(Sphere::new(p, 0.5, glass)), to_world);
+    add(&mut graph, glass, "air", Arc::new(Sphere::new(p, 0.45, air)), Mat4::identity());
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&a| String::from(a)).collect()
+    }
+
+    #[test]
+    fn parses_frame_ranges() {
+        assert_eq!(parse_frames("7"), Ok((7, 7)));
+        assert_eq!(parse_frames("1-48"), Ok((1, 48)));
+        assert_eq!(parse_frames("3-3"), Ok((3, 3)));
+        assert!(parse_frames("2-1").is_err());
+        assert!(parse_frames("1-").is_err());
+        assert!(parse_frames("-1").is_err());
+        assert!(parse_args(args(&["--frames", "2-1"])).is_err());
+    }
+
+    #[test]
+    fn stats_are_only_for_local_renders() {
+        assert!(parse_args(args(&["--stats"])).is_ok());
+        assert!(parse_args(args(&["--stats", "--spawn", "2"])).is_err());
+        assert!(parse_args(args(&["--stats-json", "s.json", "--connect", "host:1"])).is_err());
+        assert!(parse_args(args(&["--listen", "localhost:1", "--stats"])).is_err());
+    }
+
+    #[test]
+    fn animates_only_undispersed_glass() {
+        let path = env::temp_dir().join(format!("riow-{}-glass.anim", process::id()));
+        fs::write(&path, "glass.ior 0 linear 1.3\nglass.ior 1 linear 1.7\n").unwrap();
+        let path = path.to_str().unwrap();
+        let animated = parse_args(args(&["--animation", path, "--glass", "1.5"]));
+        let dispersive = parse_args(args(&["--animation", path, "--glass", "bk7"]));
+        fs::remove_file(path).unwrap();
+        assert!(animated.is_ok());
+        assert!(dispersive.is_err());
+    }
+
+    #[test]
+    fn scene_args_leave_out_how_the_render_runs() {
+        let all = args(&[
+            "--width", "64", "--samples", "8", "--spawn", "2", "--stats", "--glass", "bk7", "--checkpoint", "c",
+            "--spectral", "out.ppm", "--time", "3",
+        ]);
+        let expected = args(&["--width", "64", "--glass", "bk7", "--spectral", "--time", "0.5"]);
+        assert_eq!(scene_args(&all, 0.5), expected);
+    }
+}
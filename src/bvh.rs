@@ -0,0 +1,611 @@
+//! A bounding volume hierarchy, so rays only test the objects whose boxes
+//! they pass through.
+
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::MaterialId;
+use super::packet::{PacketHits, RayPacket, WIDTH};
+use super::stats;
+use super::{Float, Ray, Vec3};
+
+/// Objects per leaf below which nodes aren't split.
+const MAX_LEAF: usize = 2;
+
+/// Buckets the centroids are sorted into when looking for the cheapest
+/// split.
+const BUCKETS: usize = 12;
+
+/// Depth from which nodes are split at the median rather than where it's
+/// cheapest, so no tree gets too deep for a traversal `Stack`.
+const MAX_SAH_DEPTH: usize = 32;
+
+/// A flattened BVH built with the surface area heuristic. Children of a node
+/// follow it depth first, with the first child right after its parent.
+/// Unbounded objects like planes can't go in the tree, so every ray tests
+/// them as well.
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// Objects in leaf order, with their index in the list the BVH was
+    /// built from.
+    objects: Vec<(usize, Arc<dyn Hit>)>,
+    unbounded: Vec<(usize, Arc<dyn Hit>)>,
+}
+
+struct Node {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Leaf { start: usize, count: usize },
+    /// The second child's index, and the axis the children were split on.
+    Interior { second: usize, axis: usize },
+}
+
+/// Nodes still to visit in a traversal. A node's pending siblings up the
+/// tree and its two children are all it ever holds, which `MAX_SAH_DEPTH`
+/// keeps well under its size.
+struct Stack {
+    nodes: [usize; 64],
+    len: usize,
+}
+
+impl Stack {
+    /// A stack holding the root.
+    fn new() -> Self {
+        Stack { nodes: [0; 64], len: 1 }
+    }
+
+    fn push(&mut self, node: usize) {
+        self.nodes[self.len] = node;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.nodes[self.len])
+    }
+}
+
+struct Primitive {
+    index: usize,
+    object: Arc<dyn Hit>,
+    bounds: Aabb,
+    centroid: Vec3,
+}
+
+impl Bvh {
+    pub fn new(objects: &[Arc<dyn Hit>]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            objects: Vec::with_capacity(objects.len()),
+            unbounded: Vec::new(),
+        };
+
+        let mut primitives = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(bounds) => primitives.push(Primitive {
+                    index,
+                    object: object.clone(),
+                    centroid: 0.5 * (*bounds.min() + *bounds.max()),
+                    bounds,
+                }),
+                None => bvh.unbounded.push((index, object.clone())),
+            }
+        }
+        if !primitives.is_empty() {
+            bvh.build(primitives, 0);
+        }
+        bvh
+    }
+
+    /// Objects in the BVH, counting unbounded ones.
+    pub fn len(&self) -> usize {
+        self.objects.len() + self.unbounded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn build(&mut self, mut primitives: Vec<Primitive>, depth: usize) -> usize {
+        let bounds = surrounding(primitives.iter().map(|p| &p.bounds));
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            kind: NodeKind::Leaf { start: 0, count: 0 },
+        });
+
+        let split = if primitives.len() <= MAX_LEAF {
+            None
+        } else if depth >= MAX_SAH_DEPTH {
+            Some(split_median(&mut primitives))
+        } else {
+            split(&mut primitives)
+        };
+        match split {
+            Some((axis, mid)) => {
+                let second_half = primitives.split_off(mid);
+                self.build(primitives, depth + 1);
+                let second = self.build(second_half, depth + 1);
+                self.nodes[node].kind = NodeKind::Interior { second, axis };
+            }
+            None => {
+                let start = self.objects.len();
+                let count = primitives.len();
+                self.objects.extend(primitives.into_iter().map(|p| (p.index, p.object)));
+                self.nodes[node].kind = NodeKind::Leaf { start, count };
+            }
+        }
+        node
+    }
+
+    /// Tests an object, keeping the hit if it's closer than `closest`.
+    /// Objects `visible` rejects are skipped.
+    fn test<F: Fn(usize) -> bool>(
+        &self,
+        (index, object): &(usize, Arc<dyn Hit>),
+        r: &Ray,
+        (t_min, t_max): (Float, Float),
+        closest: &mut Option<HitRecord>,
+        visible: &F,
+    ) {
+        if !visible(*index) {
+            return;
+        }
+        let t_max = closest.as_ref().map_or(t_max, |c| c.t);
+        let hit = object.hit(r, t_min, t_max);
+        stats::count(|s| {
+            s.intersection_tests += 1;
+            s.intersection_hits += hit.is_some() as u64;
+        });
+        if let Some(mut record) = hit {
+            record.object = *index;
+            *closest = Some(record);
+        }
+    }
+
+    /// Tests an object against the rays of a packet, noting it as the
+    /// object hit by any that hit it.
+    fn test_packet<F: Fn(usize) -> bool>(
+        &self,
+        (index, object): &(usize, Arc<dyn Hit>),
+        packet: &RayPacket,
+        t_min: Float,
+        hits: &mut PacketHits,
+        visible: &F,
+    ) {
+        if !visible(*index) {
+            return;
+        }
+        let before = *hits.t_max();
+        object.hit_packet(packet, t_min, hits);
+        let mut tests = 0;
+        let mut found = 0;
+        for (lane, t) in before.iter().enumerate() {
+            tests += (*t > t_min) as u64;
+            if hits.t_max()[lane] != *t {
+                found += 1;
+                if let Some(record) = hits.get_mut(lane) {
+                    record.object = *index;
+                }
+            }
+        }
+        stats::count(|s| {
+            s.intersection_tests += tests;
+            s.intersection_hits += found;
+        });
+    }
+
+    /// Like `hit`, but only for objects `visible` accepts, by their index
+    /// in the list the tree was built from.
+    pub fn hit_visible<F: Fn(usize) -> bool>(
+        &self,
+        r: &Ray,
+        t_min: Float,
+        t_max: Float,
+        visible: F,
+    ) -> Option<HitRecord> {
+        let mut closest = None;
+        for object in &self.unbounded {
+            self.test(object, r, (t_min, t_max), &mut closest, &visible);
+        }
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let mut visited = 0;
+        let mut stack = Stack::new();
+        while let Some(i) = stack.pop() {
+            visited += 1;
+            let node = &self.nodes[i];
+            if !node.bounds.hit(r, t_min, closest.as_ref().map_or(t_max, |c| c.t)) {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { start, count } => {
+                    for object in &self.objects[start..start + count] {
+                        self.test(object, r, (t_min, t_max), &mut closest, &visible);
+                    }
+                }
+                // visit the child nearer the ray's origin first, so hits
+                // there can cull the other one
+                NodeKind::Interior { second, axis } => {
+                    if r.direction()[axis] < 0.0 {
+                        stack.push(i + 1);
+                        stack.push(second);
+                    } else {
+                        stack.push(second);
+                        stack.push(i + 1);
+                    }
+                }
+            }
+        }
+        stats::count(|s| s.bvh_nodes_visited += visited);
+        closest
+    }
+
+    /// Whether a ray hits anything `blocks` accepts between `t_min` and
+    /// `t_max`, among objects `visible` accepts. Unlike `hit_visible` this
+    /// stops at the first such hit it comes across rather than looking for
+    /// the closest, which is all shadow rays need. Hits `blocks` rejects
+    /// don't stop the search, but it only sees the closest hit on each
+    /// object.
+    pub fn any_hit_visible<F, B>(&self, r: &Ray, t_min: Float, t_max: Float, visible: F, mut blocks: B) -> bool
+    where
+        F: Fn(usize) -> bool,
+        B: FnMut(&HitRecord) -> bool,
+    {
+        let mut test = |(index, object): &(usize, Arc<dyn Hit>)| {
+            if !visible(*index) {
+                return false;
+            }
+            let hit = object.hit(r, t_min, t_max);
+            stats::count(|s| {
+                s.intersection_tests += 1;
+                s.intersection_hits += hit.is_some() as u64;
+            });
+            hit.is_some_and(|mut record| {
+                record.object = *index;
+                blocks(&record)
+            })
+        };
+        if self.unbounded.iter().any(&mut test) {
+            return true;
+        }
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut visited = 0;
+        let mut stack = Stack::new();
+        let mut found = false;
+        while let Some(i) = stack.pop() {
+            visited += 1;
+            let node = &self.nodes[i];
+            if !node.bounds.hit(r, t_min, t_max) {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { start, count } => {
+                    if self.objects[start..start + count].iter().any(&mut test) {
+                        found = true;
+                        break;
+                    }
+                }
+                // the nearer child is likelier to hold a blocker close to
+                // the ray's origin, like the surface it left
+                NodeKind::Interior { second, axis } => {
+                    if r.direction()[axis] < 0.0 {
+                        stack.push(i + 1);
+                        stack.push(second);
+                    } else {
+                        stack.push(second);
+                        stack.push(i + 1);
+                    }
+                }
+            }
+        }
+        stats::count(|s| s.bvh_nodes_visited += visited);
+        found
+    }
+
+    /// Like `hit_packet`, but only for objects `visible` accepts.
+    pub fn hit_packet_visible<F: Fn(usize) -> bool>(
+        &self,
+        packet: &RayPacket,
+        t_min: Float,
+        hits: &mut PacketHits,
+        visible: F,
+    ) {
+        for object in &self.unbounded {
+            self.test_packet(object, packet, t_min, hits, &visible);
+        }
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut visited = 0;
+        let mut stack = Stack::new();
+        while let Some(i) = stack.pop() {
+            visited += 1;
+            let node = &self.nodes[i];
+            if !node.bounds.hit_packet(packet, t_min, hits.t_max()).contains(&true) {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { start, count } => {
+                    for object in &self.objects[start..start + count] {
+                        self.test_packet(object, packet, t_min, hits, &visible);
+                    }
+                }
+                // the rays of a coherent packet all head the same way along
+                // the axis, and pick the same nearer child; otherwise most
+                // of those still looking for a hit get their way
+                NodeKind::Interior { second, axis } => {
+                    let dir = packet.direction(axis);
+                    let looking = (0..WIDTH).filter(|&lane| hits.is_active(lane, t_min));
+                    let backward = looking.clone().filter(|&lane| dir[lane] < 0.0).count();
+                    if 2 * backward > looking.count() {
+                        stack.push(i + 1);
+                        stack.push(second);
+                    } else {
+                        stack.push(second);
+                        stack.push(i + 1);
+                    }
+                }
+            }
+        }
+        stats::count(|s| s.bvh_nodes_visited += visited);
+    }
+}
+
+impl Hit for Bvh {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        self.hit_visible(r, t_min, t_max, |_| true)
+    }
+
+    /// Traverses the tree once for the whole packet, visiting nodes any of
+    /// its rays pass through.
+    fn hit_packet(&self, packet: &RayPacket, t_min: Float, hits: &mut PacketHits) {
+        self.hit_packet_visible(packet, t_min, hits, |_| true)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.nodes.first().map(|n| n.bounds.clone())
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        self.objects.iter().chain(&self.unbounded).flat_map(|(_, o)| o.material_ids()).collect()
+    }
+}
+
+/// Sorts `primitives` so the cheapest split by the surface area heuristic
+/// falls at the returned index, along the returned axis. `None` means
+/// keeping them in one leaf is cheaper.
+fn split(primitives: &mut [Primitive]) -> Option<(usize, usize)> {
+    let (axis, lo, extent) = widest_axis(primitives);
+    if extent[axis] <= 0.0 {
+        // every centroid is in the same place, so halve the list
+        let mid = primitives.len() / 2;
+        return Some((axis, mid));
+    }
+
+    let bucket = |p: &Primitive| {
+        let offset = (p.centroid[axis] - lo[axis]) / extent[axis];
+        ((offset * BUCKETS as Float) as usize).min(BUCKETS - 1)
+    };
+    let mut counts = [0; BUCKETS];
+    let mut boxes: Vec<Option<Aabb>> = vec![None; BUCKETS];
+    for p in primitives.iter() {
+        let b = bucket(p);
+        counts[b] += 1;
+        boxes[b] = Some(match &boxes[b] {
+            Some(bounds) => Aabb::surrounding(bounds, &p.bounds),
+            None => p.bounds.clone(),
+        });
+    }
+
+    // the cost of splitting after each bucket, relative to testing every
+    // object against the node's box
+    let area = |b: &Option<Aabb>| b.as_ref().map_or(0.0, surface_area);
+    let grow = |acc: &Option<Aabb>, b: &Option<Aabb>| match (acc, b) {
+        (Some(a), Some(b)) => Some(Aabb::surrounding(a, b)),
+        (a, b) => a.clone().or_else(|| b.clone()),
+    };
+    let total = surface_area(&surrounding(primitives.iter().map(|p| &p.bounds)));
+    let mut best = (Float::MAX, 0);
+    for s in 0..BUCKETS - 1 {
+        let (below, above) = boxes.split_at(s + 1);
+        let lo = below.iter().fold(None, |acc, b| grow(&acc, b));
+        let hi = above.iter().fold(None, |acc, b| grow(&acc, b));
+        let (n_lo, n_hi): (usize, usize) = (counts[..=s].iter().sum(), counts[s + 1..].iter().sum());
+        let cost = 0.125 + (n_lo as Float * area(&lo) + n_hi as Float * area(&hi)) / total.max(Float::MIN_POSITIVE);
+        if cost < best.0 {
+            best = (cost, s);
+        }
+    }
+    if primitives.len() <= MAX_LEAF * 2 && best.0 >= primitives.len() as Float {
+        return None;
+    }
+
+    primitives.sort_by_key(bucket);
+    let mid = primitives.iter().position(|p| bucket(p) > best.1).unwrap_or(primitives.len() / 2);
+    let mid = if mid == 0 || mid == primitives.len() { primitives.len() / 2 } else { mid };
+    Some((axis, mid))
+}
+
+/// Halves the list along the axis its centroids spread furthest on.
+fn split_median(primitives: &mut [Primitive]) -> (usize, usize) {
+    let (axis, _, _) = widest_axis(primitives);
+    let mid = primitives.len() / 2;
+    primitives.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    (axis, mid)
+}
+
+/// The axis the centroids spread furthest along, with the low corner and
+/// size of their bounds.
+fn widest_axis(primitives: &[Primitive]) -> (usize, Vec3, Vec3) {
+    let (lo, hi) = primitives.iter().fold((primitives[0].centroid, primitives[0].centroid), |(lo, hi), p| {
+        (Vec3::min(lo, p.centroid), Vec3::max(hi, p.centroid))
+    });
+    let extent = hi - lo;
+    let axis = (0..3).fold(0, |a, b| if extent[b] > extent[a] { b } else { a });
+    (axis, lo, extent)
+}
+
+fn surrounding<'a, I: Iterator<Item = &'a Aabb>>(mut boxes: I) -> Aabb {
+    let first = boxes.next().expect("no boxes to surround").clone();
+    boxes.fold(first, |acc, b| Aabb::surrounding(&acc, b))
+}
+
+fn surface_area(b: &Aabb) -> Float {
+    let d = *b.max() - *b.min();
+    2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_materials;
+    use crate::shape::Plane;
+    use crate::sphere::Sphere;
+    use crate::Point3;
+
+    /// A grid of spheres of varying sizes above a floor, which can't go in
+    /// the tree.
+    fn objects() -> Vec<Arc<dyn Hit>> {
+        let (_, mat) = test_materials();
+        let mut objects: Vec<Arc<dyn Hit>> = Vec::new();
+        for i in 0..48 {
+            let center = Vec3::new((i % 4) as Float, (i / 4 % 4) as Float, -((i / 16) as Float) - 3.0);
+            let radius = 0.1 + 0.05 * (i % 7) as Float;
+            objects.push(Arc::new(Sphere::new(center, radius, mat)));
+        }
+        objects.push(Arc::new(Plane::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), mat)));
+        objects
+    }
+
+    fn rays() -> impl Iterator<Item = Ray> {
+        (0..400).map(|i| {
+            let (u, v) = ((i % 20) as Float / 19.0, (i / 20) as Float / 19.0);
+            let dir = Vec3::new(4.0 * u - 0.5, 4.0 * v - 1.5, -4.0);
+            Ray::with_values(Point3::new(1.5, 1.5, 2.0), dir)
+        })
+    }
+
+    /// The closest hit and its object's index, testing every object.
+    fn brute_force(objects: &[Arc<dyn Hit>], r: &Ray, visible: impl Fn(usize) -> bool) -> Option<(Float, usize)> {
+        let hits = objects.iter().enumerate().filter(|(i, _)| visible(*i));
+        hits.filter_map(|(i, o)| o.hit(r, 0.0, Float::MAX).map(|h| (h.t, i)))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    #[test]
+    fn finds_the_same_hits_as_testing_everything() {
+        let objects = objects();
+        let bvh = Bvh::new(&objects);
+        assert_eq!(bvh.len(), 49);
+        assert!(bvh.node_count() > 1);
+        assert!(bvh.bounding_box().is_none());
+
+        let mut hits = 0;
+        for r in rays() {
+            let found = bvh.hit(&r, 0.0, Float::MAX).map(|h| (h.t, h.object));
+            assert_eq!(found, brute_force(&objects, &r, |_| true));
+            hits += found.is_some_and(|(_, i)| i < 48) as usize;
+        }
+        assert!(hits > 50, "only {} rays hit spheres", hits);
+    }
+
+    #[test]
+    fn skips_hidden_objects() {
+        let objects = objects();
+        let bvh = Bvh::new(&objects);
+        let visible = |i: usize| i % 3 != 0;
+        for r in rays() {
+            let found = bvh.hit_visible(&r, 0.0, Float::MAX, visible).map(|h| (h.t, h.object));
+            assert_eq!(found, brute_force(&objects, &r, visible));
+        }
+    }
+
+    #[test]
+    fn any_hit_agrees_with_the_closest() {
+        let objects = objects();
+        let bvh = Bvh::new(&objects);
+        for r in rays() {
+            for &t_max in &[1.0, 4.0, Float::MAX] {
+                let closest = bvh.hit(&r, 0.0, t_max).is_some();
+                assert_eq!(bvh.any_hit_visible(&r, 0.0, t_max, |_| true, |_| true), closest);
+            }
+            // hits that don't block are seen but let the ray through
+            let mut seen = 0;
+            assert!(!bvh.any_hit_visible(&r, 0.0, Float::MAX, |_| true, |_| {
+                seen += 1;
+                false
+            }));
+            assert_eq!(seen > 0, bvh.hit(&r, 0.0, Float::MAX).is_some());
+        }
+    }
+
+    #[test]
+    fn any_hit_skips_hidden_objects() {
+        let objects = objects();
+        let bvh = Bvh::new(&objects);
+        let spheres_only = |i: usize| i < 48;
+        for r in rays() {
+            let expected = brute_force(&objects, &r, spheres_only).is_some();
+            assert_eq!(bvh.any_hit_visible(&r, 0.0, Float::MAX, spheres_only, |_| true), expected);
+        }
+    }
+
+    fn depth(bvh: &Bvh, node: usize) -> usize {
+        match bvh.nodes[node].kind {
+            NodeKind::Leaf { .. } => 0,
+            NodeKind::Interior { second, .. } => 1 + depth(bvh, node + 1).max(depth(bvh, second)),
+        }
+    }
+
+    #[test]
+    fn lopsided_scenes_stay_shallow() {
+        // each sphere dwarfs all the ones before it, so the cheapest split
+        // only ever peels off the biggest
+        let (_, mat) = test_materials();
+        // as many as fit with their squared sizes in range
+        let count = (Float::MAX.sqrt().ln() / (12.0 as Float).ln()) as i32 - 4;
+        let size = |i: i32| (12.0 as Float).powi(i);
+        let objects: Vec<Arc<dyn Hit>> = (0..count)
+            .map(|i| Arc::new(Sphere::new(Vec3::new(size(i), 0.0, 0.0), 0.05 * size(i), mat)) as Arc<dyn Hit>)
+            .collect();
+        let bvh = Bvh::new(&objects);
+        assert!(depth(&bvh, 0) < 64, "depth {}", depth(&bvh, 0));
+
+        let r = Ray::with_values(Point3::origin(), Vec3::new(1.0, 0.0, 0.0));
+        for i in 0..count {
+            let found = bvh.hit(&r, 0.9 * size(i), Float::MAX).map(|h| h.object);
+            assert_eq!(found, Some(i as usize));
+        }
+    }
+
+    #[test]
+    fn empty_trees_hit_nothing() {
+        let bvh = Bvh::new(&[]);
+        assert!(bvh.is_empty());
+        let r = Ray::with_values(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        assert!(bvh.hit(&r, 0.0, Float::MAX).is_none());
+        assert!(!bvh.any_hit_visible(&r, 0.0, Float::MAX, |_| true, |_| true));
+    }
+}
@@ -1,8 +1,8 @@
-use std::f64::consts::PI;
+use crate::consts::PI;
 
 use rand::Rng;
 
-use super::Ray;
+use super::{Float, Onb, Point3, Ray};
 use super::Vec3;
 
 pub struct Camera {
@@ -10,10 +10,8 @@ pub struct Camera {
     lower_left: Vec3,
     horizontal: Vec3,
     vertical: Vec3,
-    lens_radius: f64,
-    u: Vec3,
-    v: Vec3,
-    w: Vec3,
+    lens_radius: Float,
+    onb: Onb,
 }
 
 impl Camera {
@@ -21,52 +19,76 @@ impl Camera {
         pos: Vec3,
         look_at: Vec3,
         v_up: Vec3,
-        v_fov: f64,
-        aspect: f64,
-        aperture: f64,
-        focus_dist: f64,
+        v_fov: Float,
+        aspect: Float,
+        aperture: Float,
+        focus_dist: Float,
     ) -> Self {
         let lens_radius = aperture / 2.0;
         let theta = v_fov * PI / 180.0;
         let half_height = (theta / 2.0).tan();
         let half_width = aspect * half_height;
 
-        let w = Vec3::unit_vector(&(&pos - &look_at));
-        let u = Vec3::unit_vector(&Vec3::cross(&v_up, &w));
-        let v = Vec3::cross(&w, &u);
+        let onb = Onb::from_w_up(pos - look_at, v_up);
+        let Onb { u, v, w } = onb;
 
         let lower_left =
-            &pos - half_width * focus_dist * &u - half_height * focus_dist * &v - focus_dist * &w;
-        let horizontal = 2.0 * half_width * focus_dist * &u;
-        let vertical = 2.0 * half_height * focus_dist * &v;
+            pos - half_width * focus_dist * u - half_height * focus_dist * v - focus_dist * w;
+        let horizontal = 2.0 * half_width * focus_dist * u;
+        let vertical = 2.0 * half_height * focus_dist * v;
         Self {
             pos,
             lower_left,
             horizontal,
             vertical,
             lens_radius,
-            u,
-            v,
-            w,
+            onb,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+    pub fn get_ray(&self, s: Float, t: Float) -> Ray {
         let rd = self.lens_radius * random_in_unit_disk();
-        let offset = &self.u * rd.x() + &self.v * rd.y();
+        let offset = self.onb.local(rd);
         Ray::with_values(
-            self.pos.clone() + &offset,
-            &self.lower_left + s * &self.horizontal + t * &self.vertical - &self.pos - &offset,
+            Point3(self.pos + offset),
+            self.lower_left + s * self.horizontal + t * self.vertical - self.pos - offset,
         )
     }
 }
 
 fn random_in_unit_disk() -> Vec3 {
-    let mut rng = rand::thread_rng();
+    let mut rng = crate::random::rng();
     loop {
         let v = 2.0 * Vec3::new(rng.gen(), rng.gen(), 0.0) - Vec3::new(1.0, 1.0, 0.0);
-        if Vec3::dot(&v, &v) >- 1.0 {
+        if Vec3::dot(v, v) < 1.0 {
             return v;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lens_samples_land_inside_the_disk() {
+        crate::random::seed_sample(1, 0, 0);
+        for _ in 0..1000 {
+            let v = random_in_unit_disk();
+            assert!(v.z() == 0.0 && Vec3::dot(v, v) < 1.0, "{:?}", v);
+        }
+    }
+
+    #[test]
+    fn rays_leave_from_the_lens_and_meet_at_the_focus() {
+        let (pos, look_at) = (Vec3::new(0.0, 0.0, 4.0), Vec3::origin());
+        let camera = Camera::new(pos, look_at, Vec3::new(0.0, 1.0, 0.0), 40.0, 1.5, 2.0, 4.0);
+        crate::random::seed_sample(1, 0, 0);
+        for _ in 0..1000 {
+            let r = camera.get_ray(0.5, 0.5);
+            assert!((*r.origin() - pos).len() < 1.0, "{:?}", r.origin());
+            let focus = *r.origin() + r.direction();
+            assert!((focus - look_at).len() < 1e-5, "{:?}", focus);
+        }
+    }
+}
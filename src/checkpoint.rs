@@ -0,0 +1,177 @@
+//! Saving renders in progress so they can be resumed after the process
+//! stops.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::Path;
+
+use super::film::Film;
+use super::Float;
+
+const MAGIC: &[u8; 8] = b"RTCKPT2\n";
+
+/// Longest scene description a checkpoint is trusted to hold.
+const MAX_SCENE_LEN: u64 = 1 << 20;
+
+/// How far a render got. Renders reseed the generator in `random` from the
+/// seed for every sample, so this and the films are all the state there is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub seed: u64,
+    /// Samples per pixel already in the films.
+    pub samples: u64,
+    /// Whatever decides what the films hold, like the options that set up
+    /// the scene, so a render can't resume from another's checkpoint.
+    pub scene: String,
+}
+
+impl Checkpoint {
+    /// Saves the checkpoint with the films it describes. The file is written
+    /// next to `path` first and then renamed over it, so a crash while
+    /// saving doesn't lose the previous checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P, films: &[Film]) -> io::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        {
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            w.write_all(MAGIC)?;
+            let (width, height) = films.first().map_or((0, 0), |f| (f.width(), f.height()));
+            let header = [
+                mem::size_of::<Float>() as u64,
+                width as u64,
+                height as u64,
+                films.len() as u64,
+                self.seed,
+                self.samples,
+                self.scene.len() as u64,
+            ];
+            for v in &header {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            w.write_all(self.scene.as_bytes())?;
+            for film in films {
+                film.save_state(&mut w)?;
+            }
+            w.flush()?;
+        }
+        fs::rename(&tmp, path)
+    }
+
+    /// Loads a checkpoint of `scene` into `films`, which must be set up the
+    /// same way as the ones that were saved.
+    pub fn load<P: AsRef<Path>>(path: P, scene: &str, films: &mut [Film]) -> io::Result<Checkpoint> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a checkpoint"));
+        }
+
+        let mut header = [0u64; 7];
+        for v in header.iter_mut() {
+            let mut buf = [0u8; 8];
+            r.read_exact(&mut buf)?;
+            *v = u64::from_le_bytes(buf);
+        }
+        let [float_size, width, height, count, seed, samples, scene_len] = header;
+        if float_size != mem::size_of::<Float>() as u64 {
+            return Err(invalid_data("checkpoint was saved with a different float precision"));
+        }
+        let matches = |f: &Film| f.width() as u64 == width && f.height() as u64 == height;
+        if count != films.len() as u64 || !films.iter().all(matches) {
+            return Err(invalid_data("checkpoint doesn't match the render's size or passes"));
+        }
+        if scene_len > MAX_SCENE_LEN {
+            return Err(invalid_data("checkpoint's scene is too long"));
+        }
+        let mut saved = vec![0u8; scene_len as usize];
+        r.read_exact(&mut saved)?;
+        if saved != scene.as_bytes() {
+            let saved = String::from_utf8_lossy(&saved);
+            return Err(invalid_data(&format!("checkpoint is of a different scene, rendered with: {}", saved)));
+        }
+
+        for film in films.iter_mut() {
+            film.load_state(&mut r)?;
+        }
+        Ok(Checkpoint {
+            seed,
+            samples,
+            scene: String::from(scene),
+        })
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::BoxFilter;
+    use crate::math::Vec3;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn film() -> Film {
+        let mut film = Film::new(4, 3, Arc::new(BoxFilter::new(0.5)));
+        film.add_sample(1.5, 2.5, Vec3::new(1.0, 2.0, 3.0));
+        film
+    }
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("raytracing-{}-{}.ckpt", std::process::id(), name))
+    }
+
+    fn saved(name: &str) -> PathBuf {
+        let path = temp(name);
+        let checkpoint = Checkpoint {
+            seed: 7,
+            samples: 16,
+            scene: String::from("--scene cornell"),
+        };
+        checkpoint.save(&path, &[film()]).unwrap();
+        path
+    }
+
+    #[test]
+    fn round_trips() {
+        let path = saved("round-trip");
+        let mut films = [Film::new(4, 3, Arc::new(BoxFilter::new(0.5)))];
+        let loaded = Checkpoint::load(&path, "--scene cornell", &mut films).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((loaded.seed, loaded.samples), (7, 16));
+        assert_eq!(films[0].pixel(1, 2), film().pixel(1, 2));
+    }
+
+    #[test]
+    fn rejects_another_scene() {
+        let path = saved("other-scene");
+        let mut films = [Film::new(4, 3, Arc::new(BoxFilter::new(0.5)))];
+        let err = Checkpoint::load(&path, "--scene riow", &mut films).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_another_size() {
+        let path = saved("other-size");
+        let mut films = [Film::new(3, 4, Arc::new(BoxFilter::new(0.5)))];
+        let err = Checkpoint::load(&path, "--scene cornell", &mut films).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let path = saved("truncated");
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let mut films = [Film::new(4, 3, Arc::new(BoxFilter::new(0.5)))];
+        let result = Checkpoint::load(&path, "--scene cornell", &mut films);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
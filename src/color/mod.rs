@@ -0,0 +1,150 @@
+//! Turning linear radiance into displayable colors.
+
+mod space;
+mod tonemap;
+
+use super::{Float, Mat3, Vec3};
+
+pub use space::{ColorSpace, D60, D65, chromatic_adaptation, white_point};
+pub use tonemap::ToneMap;
+
+/// Post-processes rendered radiance for display: exposure, white balance,
+/// conversion from the working space to sRGB, tone mapping and finally the
+/// sRGB transfer function.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    working_space: ColorSpace,
+    exposure: Float,
+    white_balance: Option<Float>,
+    tone_map: ToneMap,
+    to_output: Mat3,
+}
+
+impl Pipeline {
+    /// A pipeline for renders whose colors are linear in `working_space`.
+    /// Spectral renders come out in sRGB.
+    pub fn new(working_space: ColorSpace) -> Self {
+        let mut p = Pipeline {
+            working_space,
+            exposure: 0.0,
+            white_balance: None,
+            tone_map: ToneMap::default(),
+            to_output: Mat3::identity(),
+        };
+        p.update();
+        p
+    }
+
+    /// Brightens the image by `stops`, or darkens it if negative.
+    pub fn set_exposure(&mut self, stops: Float) {
+        self.exposure = stops;
+    }
+
+    /// Makes light of the color temperature `kelvin` come out white, or
+    /// leaves colors alone for `None`.
+    pub fn set_white_balance(&mut self, kelvin: Option<Float>) {
+        self.white_balance = kelvin;
+        self.update();
+    }
+
+    pub fn set_tone_map(&mut self, tone_map: ToneMap) {
+        self.tone_map = tone_map;
+    }
+
+    pub fn set_working_space(&mut self, space: ColorSpace) {
+        self.working_space = space;
+        self.update();
+    }
+
+    fn update(&mut self) {
+        let srgb = ColorSpace::srgb();
+        let space = &self.working_space;
+        let balance = match self.white_balance {
+            Some(k) => chromatic_adaptation(white_point(k), space.white()),
+            None => Mat3::identity(),
+        };
+        self.to_output = space.conversion(&srgb) * space.from_xyz() * balance * space.to_xyz();
+    }
+
+    /// Maps linear radiance to sRGB encoded values in [0, 1].
+    pub fn apply(&self, c: Vec3) -> Vec3 {
+        let c = self.to_output * (c * (2.0 as Float).powf(self.exposure));
+        let c = self.tone_map.apply(c);
+        Vec3::new(srgb_oetf(c.x()), srgb_oetf(c.y()), srgb_oetf(c.z()))
+    }
+
+    /// `apply` rounded to 8 bits per channel.
+    pub fn to_rgb8(&self, c: Vec3) -> [u8; 3] {
+        let c = self.apply(c);
+        let q = |v: Float| (v * 255.0).round() as u8;
+        [q(c.x()), q(c.y()), q(c.z())]
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline::new(ColorSpace::default())
+    }
+}
+
+/// The sRGB transfer function, encoding linear values in [0, 1].
+pub fn srgb_oetf(v: Float) -> Float {
+    if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `srgb_oetf`.
+pub fn srgb_eotf(v: Float) -> Float {
+    if v <= 0.040_45 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_transfer_functions_invert_each_other() {
+        for i in 0..=20 {
+            let v = i as Float / 20.0;
+            assert!((srgb_eotf(srgb_oetf(v)) - v).abs() < 1e-6);
+        }
+        assert!((srgb_oetf(0.18) - 0.4614).abs() < 1e-3);
+    }
+
+    #[test]
+    fn the_default_pipeline_maps_white_to_white() {
+        let p = Pipeline::default();
+        assert_eq!(p.to_rgb8(Vec3::new(1.0, 1.0, 1.0)), [255, 255, 255]);
+        assert_eq!(p.to_rgb8(Vec3::new(5.0, 0.0, -1.0)), [255, 0, 0]);
+        assert_eq!(p.to_rgb8(Vec3::origin()), [0, 0, 0]);
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let mut p = Pipeline::default();
+        let mid = Vec3::splat(0.1);
+        let plain = p.apply(mid * 2.0);
+        p.set_exposure(1.0);
+        assert!((p.apply(mid) - plain).len() < 1e-6);
+    }
+
+    #[test]
+    fn balancing_for_the_working_white_changes_nothing() {
+        let mut p = Pipeline::default();
+        let c = Vec3::new(0.2, 0.5, 0.7);
+        let plain = p.apply(c);
+        p.set_white_balance(Some(6504.0));
+        assert!((p.apply(c) - plain).len() < 1e-3);
+        // warm light comes out neutral, so a neutral surface turns blue
+        p.set_white_balance(Some(3000.0));
+        let gray = p.apply(Vec3::splat(0.5));
+        assert!(gray.z() > gray.x());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{Float, Mat3, Vec3};
+
+/// An RGB color space given by its primaries and white point, as CIE xy
+/// chromaticities.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorSpace {
+    to_xyz: Mat3,
+    white: [Float; 2],
+}
+
+pub const D65: [Float; 2] = [0.3127, 0.3290];
+pub const D60: [Float; 2] = [0.32168, 0.33767];
+
+impl ColorSpace {
+    pub fn new(r: [Float; 2], g: [Float; 2], b: [Float; 2], white: [Float; 2]) -> Self {
+        // scale the primaries so that (1, 1, 1) lands on the white point
+        let m = Mat3::from_cols(xy_to_xyz(r), xy_to_xyz(g), xy_to_xyz(b));
+        let s = m.inverse().unwrap_or_default() * xy_to_xyz(white);
+        ColorSpace {
+            to_xyz: m * diagonal(s),
+            white,
+        }
+    }
+
+    /// Linear sRGB, which is also Rec. 709.
+    pub fn srgb() -> Self {
+        ColorSpace::new([0.64, 0.33], [0.30, 0.60], [0.15, 0.06], D65)
+    }
+
+    pub fn display_p3() -> Self {
+        ColorSpace::new([0.680, 0.320], [0.265, 0.690], [0.150, 0.060], D65)
+    }
+
+    pub fn rec2020() -> Self {
+        ColorSpace::new([0.708, 0.292], [0.170, 0.797], [0.131, 0.046], D65)
+    }
+
+    /// ACES AP1, the usual working space of ACES pipelines.
+    pub fn aces_cg() -> Self {
+        ColorSpace::new([0.713, 0.293], [0.165, 0.830], [0.128, 0.044], D60)
+    }
+
+    /// Looks a space up by name, for command line options.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "srgb" | "rec709" => Some(ColorSpace::srgb()),
+            "p3" | "display-p3" => Some(ColorSpace::display_p3()),
+            "rec2020" => Some(ColorSpace::rec2020()),
+            "acescg" => Some(ColorSpace::aces_cg()),
+            _ => None,
+        }
+    }
+
+    pub fn to_xyz(&self) -> Mat3 {
+        self.to_xyz
+    }
+
+    pub fn from_xyz(&self) -> Mat3 {
+        self.to_xyz.inverse().unwrap_or_default()
+    }
+
+    pub fn white(&self) -> [Float; 2] {
+        self.white
+    }
+
+    /// The matrix taking colors in this space to `other`, adapting between
+    /// their white points.
+    pub fn conversion(&self, other: &ColorSpace) -> Mat3 {
+        other.from_xyz() * chromatic_adaptation(self.white, other.white) * self.to_xyz
+    }
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        ColorSpace::srgb()
+    }
+}
+
+fn xy_to_xyz(xy: [Float; 2]) -> Vec3 {
+    let [x, y] = xy;
+    Vec3::new(x / y, 1.0, (1.0 - x - y) / y)
+}
+
+fn diagonal(d: Vec3) -> Mat3 {
+    Mat3::new([
+        [d.x(), 0.0, 0.0],
+        [0.0, d.y(), 0.0],
+        [0.0, 0.0, d.z()],
+    ])
+}
+
+const BRADFORD: Mat3 = Mat3::new([
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+]);
+
+/// Bradford transform of XYZ colors seen under white `from` to how they'd
+/// look under `to`.
+pub fn chromatic_adaptation(from: [Float; 2], to: [Float; 2]) -> Mat3 {
+    if from == to {
+        return Mat3::identity();
+    }
+    let src = BRADFORD * xy_to_xyz(from);
+    let dst = BRADFORD * xy_to_xyz(to);
+    BRADFORD.inverse().unwrap_or_default() * diagonal(dst / src) * BRADFORD
+}
+
+/// Chromaticity of light with color temperature `kelvin`: a black body
+/// below 4000K (Kim et al. 2002) and CIE daylight above, so 6504K is D65.
+/// Clamped to 1667-25000K.
+pub fn white_point(kelvin: Float) -> [Float; 2] {
+    let t = kelvin.clamp(1667.0, 25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+    if t > 4000.0 {
+        let x = if t <= 7000.0 {
+            -4.607e9 / t3 + 2.967_8e6 / t2 + 0.099_11e3 / t + 0.244_063
+        } else {
+            -2.006_4e9 / t3 + 1.901_8e6 / t2 + 0.247_48e3 / t + 0.237_04
+        };
+        return [x, -3.0 * x * x + 2.87 * x - 0.275];
+    }
+
+    let x = -0.266_123_9e9 / t3 - 0.234_358_9e6 / t2 + 0.877_695_6e3 / t + 0.179_910;
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.106_381_4 * x3 - 1.348_110_2 * x2 + 2.185_558_3 * x - 0.202_196_8
+    } else {
+        -0.954_947_6 * x3 - 1.374_185_9 * x2 + 2.091_37 * x - 0.167_488_7
+    };
+    [x, y]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3, eps: Float) {
+        assert!((a - b).len() < eps, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn srgb_matches_the_published_matrix() {
+        let m = ColorSpace::srgb().to_xyz();
+        assert_near(m.row(0), Vec3::new(0.4124, 0.3576, 0.1805), 1e-3);
+        assert_near(m.row(1), Vec3::new(0.2126, 0.7152, 0.0722), 1e-3);
+        assert_near(m.row(2), Vec3::new(0.0193, 0.1192, 0.9505), 1e-3);
+    }
+
+    #[test]
+    fn white_has_a_luminance_of_one() {
+        let spaces = [ColorSpace::srgb(), ColorSpace::display_p3(), ColorSpace::rec2020(), ColorSpace::aces_cg()];
+        for space in spaces.iter() {
+            let white = space.to_xyz() * Vec3::new(1.0, 1.0, 1.0);
+            assert!((white.y() - 1.0).abs() < 1e-5);
+            assert_near(space.from_xyz() * white, Vec3::new(1.0, 1.0, 1.0), 1e-5);
+        }
+    }
+
+    #[test]
+    fn conversions_keep_white_white() {
+        let (srgb, aces) = (ColorSpace::srgb(), ColorSpace::aces_cg());
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        assert_near(srgb.conversion(&aces) * white, white, 1e-3);
+        let c = Vec3::new(0.1, 0.5, 0.9);
+        assert_near(aces.conversion(&srgb) * (srgb.conversion(&aces) * c), c, 1e-4);
+        assert_eq!(srgb.conversion(&srgb), srgb.from_xyz() * srgb.to_xyz());
+    }
+
+    #[test]
+    fn adaptation_moves_one_white_onto_the_other() {
+        let m = chromatic_adaptation(D60, D65);
+        let d60 = xy_to_xyz(D60);
+        let d65 = xy_to_xyz(D65);
+        assert_near(m * d60, d65, 1e-4);
+        assert_eq!(chromatic_adaptation(D65, D65), Mat3::identity());
+    }
+
+    #[test]
+    fn color_temperatures() {
+        let [x, y] = white_point(6504.0);
+        assert!((x - D65[0]).abs() < 1e-3 && (y - D65[1]).abs() < 1e-3);
+        // lower temperatures are redder
+        assert!(white_point(2700.0)[0] > white_point(5000.0)[0]);
+        assert_eq!(white_point(100.0), white_point(1667.0));
+    }
+
+    #[test]
+    fn spaces_by_name() {
+        assert_eq!(ColorSpace::from_name("ACEScg"), Some(ColorSpace::aces_cg()));
+        assert_eq!(ColorSpace::from_name("rec709"), Some(ColorSpace::srgb()));
+        assert_eq!(ColorSpace::from_name("cmyk"), None);
+    }
+}
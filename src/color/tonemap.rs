@@ -0,0 +1,132 @@
+use crate::{Float, Mat3, Vec3};
+
+/// Curves compressing scene radiance into the displayable range. They all
+/// take and return linear Rec. 709 values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMap {
+    /// Clips everything above one.
+    #[default]
+    Clamp,
+    /// `x / (1 + x)` on each channel.
+    Reinhard,
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output
+    /// transforms.
+    Aces,
+    /// Troy Sobotka's AgX, which desaturates bright colors towards white
+    /// instead of skewing their hue. Uses Benjamin Wrensch's polynomial fit
+    /// of the default contrast curve.
+    Agx,
+}
+
+impl ToneMap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" | "clamp" => Some(ToneMap::Clamp),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "aces" => Some(ToneMap::Aces),
+            "agx" => Some(ToneMap::Agx),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, c: Vec3) -> Vec3 {
+        let c = Vec3::max(c, Vec3::origin());
+        let c = match self {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => c / (c + Vec3::new(1.0, 1.0, 1.0)),
+            ToneMap::Aces => aces(c),
+            ToneMap::Agx => agx(c),
+        };
+        // the fitted curves dip just below zero near black
+        Vec3::max(Vec3::min(c, Vec3::new(1.0, 1.0, 1.0)), Vec3::origin())
+    }
+}
+
+fn per_channel(c: Vec3, f: impl Fn(Float) -> Float) -> Vec3 {
+    Vec3::new(f(c.x()), f(c.y()), f(c.z()))
+}
+
+const ACES_IN: Mat3 = Mat3::new([
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+]);
+
+const ACES_OUT: Mat3 = Mat3::new([
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+]);
+
+fn aces(c: Vec3) -> Vec3 {
+    let v = ACES_IN * c;
+    let v = per_channel(v, |x| {
+        let a = x * (x + 0.024_578_6) - 0.000_090_537;
+        let b = x * (0.983_729 * x + 0.432_951) + 0.238_081;
+        a / b
+    });
+    ACES_OUT * v
+}
+
+const AGX_IN: Mat3 = Mat3::new([
+    [0.842_479_1, 0.078_433_6, 0.079_223_7],
+    [0.042_328_2, 0.878_468_6, 0.079_166_1],
+    [0.042_375_7, 0.078_433_6, 0.879_143],
+]);
+
+const AGX_OUT: Mat3 = Mat3::new([
+    [1.196_879, -0.098_020_9, -0.099_029_7],
+    [-0.052_896_9, 1.151_903_1, -0.098_961_2],
+    [-0.052_971_6, -0.098_043_5, 1.151_073_7],
+]);
+
+fn agx(c: Vec3) -> Vec3 {
+    const MIN_EV: Float = -12.473_93;
+    const MAX_EV: Float = 4.026_069;
+
+    let v = per_channel(AGX_IN * c, |x| {
+        let x = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2
+            + 0.1191 * x
+            - 0.002_32
+    });
+
+    // the curve bakes in a 2.2 display gamma, undone so the encoding is
+    // left to the pipeline like for the other curves
+    per_channel(AGX_OUT * v, |x| x.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [ToneMap; 4] = [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces, ToneMap::Agx];
+
+    #[test]
+    fn curves_stay_in_range_and_rise() {
+        for tm in ALL.iter() {
+            let mut last = -1.0;
+            for i in 0..100 {
+                let v = tm.apply(Vec3::splat(i as Float * 0.1)).y();
+                assert!((0.0..=1.0).contains(&v), "{:?} gave {}", tm, v);
+                assert!(v >= last - 1e-6, "{:?} isn't monotonic", tm);
+                last = v;
+            }
+            assert_eq!(tm.apply(Vec3::new(-1.0, -1.0, -1.0)).y(), tm.apply(Vec3::origin()).y());
+        }
+    }
+
+    #[test]
+    fn reinhard_halves_one() {
+        assert_eq!(ToneMap::Reinhard.apply(Vec3::splat(1.0)), Vec3::splat(0.5));
+        assert_eq!(ToneMap::Clamp.apply(Vec3::new(0.25, 2.0, 0.5)), Vec3::new(0.25, 1.0, 0.5));
+    }
+
+    #[test]
+    fn curves_by_name() {
+        assert_eq!(ToneMap::from_name("none"), Some(ToneMap::Clamp));
+        assert_eq!(ToneMap::from_name("AgX"), Some(ToneMap::Agx));
+        assert_eq!(ToneMap::from_name("filmic"), None);
+    }
+}
@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::MaterialId;
+use super::{Float, Ray};
+
+/// A closed object with a well defined inside, so that everywhere a ray
+/// passes through it can be listed rather than just the nearest hit.
+pub trait Solid: Hit {
+    /// The spans of the whole line through `r` (including behind its
+    /// origin) that lie inside the object, sorted and not overlapping.
+    /// Spans of solids that are infinite start or end at an infinite t.
+    fn intervals(&self, r: &Ray) -> Vec<Interval>;
+}
+
+#[derive(Clone)]
+pub struct Interval {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
+impl Interval {
+    pub fn new(enter: HitRecord, exit: HitRecord) -> Self {
+        Self {
+            enter,
+            exit,
+        }
+    }
+}
+
+/// Pairs up sorted surface crossings of a closed surface into intervals.
+pub(crate) fn pair_crossings(crossings: Vec<HitRecord>) -> Vec<Interval> {
+    let mut intervals = Vec::with_capacity(crossings.len() / 2);
+    let mut crossings = crossings.into_iter();
+    while let (Some(enter), Some(exit)) = (crossings.next(), crossings.next()) {
+        intervals.push(Interval::new(enter, exit));
+    }
+    intervals
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    /// everything in the first solid that isn't in the second
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// Combines two solids with a boolean operation. Since the result is also a
+/// `Solid`, nodes can be nested to build up more complicated shapes.
+pub struct Csg {
+    op: CsgOp,
+    a: Arc<dyn Solid>,
+    b: Arc<dyn Solid>,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Arc<dyn Solid>, b: Arc<dyn Solid>) -> Self {
+        Self {
+            op,
+            a,
+            b,
+        }
+    }
+}
+
+impl Solid for Csg {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        // sweep over every boundary of both operands in order, tracking
+        // whether we're inside each one and emitting a boundary whenever
+        // that flips whether we're inside the result
+        let mut events: Vec<(HitRecord, bool, bool)> = Vec::new();
+        for (intervals, from_b) in [(self.a.intervals(r), false), (self.b.intervals(r), true)] {
+            for i in intervals {
+                events.push((i.enter, from_b, true));
+                events.push((i.exit, from_b, false));
+            }
+        }
+        events.sort_by(|l, r| l.0.t.total_cmp(&r.0.t));
+
+        let (mut in_a, mut in_b) = (false, false);
+        let mut boundaries = Vec::new();
+        for (mut rec, from_b, entering) in events {
+            let was_inside = self.op.inside(in_a, in_b);
+            if from_b {
+                in_b = entering;
+            } else {
+                in_a = entering;
+            }
+
+            let now_inside = self.op.inside(in_a, in_b);
+            if now_inside != was_inside {
+                // normals already face the ray, but which side of the result
+                // this is depends on the operation, not the operand
+                rec.front_face = now_inside;
+                boundaries.push(rec);
+            }
+        }
+
+        pair_crossings(boundaries)
+    }
+}
+
+impl Hit for Csg {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        self.intervals(r)
+            .into_iter()
+            .flat_map(|i| vec![i.enter, i.exit])
+            .find(|rec| rec.t > t_min && rec.t < t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self.op {
+            CsgOp::Union => Some(Aabb::surrounding(&self.a.bounding_box()?, &self.b.bounding_box()?)),
+            CsgOp::Intersection => match (self.a.bounding_box(), self.b.bounding_box()) {
+                (Some(a), Some(b)) => Some(Aabb::overlap(&a, &b)),
+                (a, b) => a.or(b),
+            },
+            CsgOp::Difference => self.a.bounding_box(),
+        }
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        [self.a.material_ids(), self.b.material_ids()].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_materials;
+    use crate::shape::Torus;
+    use crate::sphere::Sphere;
+    use crate::Vec3;
+    use crate::Point3;
+
+    fn sphere(x: Float, radius: Float) -> Arc<dyn Solid> {
+        let (_, mat) = test_materials();
+        Arc::new(Sphere::new(Vec3::new(x, 0.0, 0.0), radius, mat))
+    }
+
+    /// Along the x axis from x = -10.
+    fn spans(solid: &dyn Solid) -> Vec<(Float, Float)> {
+        let r = Ray::with_values(Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        solid.intervals(&r).iter().map(|i| (i.enter.t - 10.0, i.exit.t - 10.0)).collect()
+    }
+
+    fn assert_spans(solid: &dyn Solid, expected: &[(Float, Float)]) {
+        let found = spans(solid);
+        let close = |a: Float, b: Float| a == b || (a - b).abs() < 1e-6;
+        assert_eq!(found.len(), expected.len(), "{:?}", found);
+        for (f, e) in found.iter().zip(expected) {
+            assert!(close(f.0, e.0) && close(f.1, e.1), "{:?} isn't {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn overlapping_spheres() {
+        let (a, b) = (sphere(0.0, 1.0), sphere(1.0, 1.0));
+        assert_spans(&Csg::new(CsgOp::Union, a.clone(), b.clone()), &[(-1.0, 2.0)]);
+        assert_spans(&Csg::new(CsgOp::Intersection, a.clone(), b.clone()), &[(0.0, 1.0)]);
+        assert_spans(&Csg::new(CsgOp::Difference, a.clone(), b.clone()), &[(-1.0, 0.0)]);
+        assert_spans(&Csg::new(CsgOp::Difference, b, a), &[(1.0, 2.0)]);
+    }
+
+    #[test]
+    fn disjoint_spheres() {
+        let (a, b) = (sphere(0.0, 1.0), sphere(5.0, 1.0));
+        assert_spans(&Csg::new(CsgOp::Union, a.clone(), b.clone()), &[(-1.0, 1.0), (4.0, 6.0)]);
+        assert_spans(&Csg::new(CsgOp::Intersection, a.clone(), b.clone()), &[]);
+        assert_spans(&Csg::new(CsgOp::Difference, a, b), &[(-1.0, 1.0)]);
+    }
+
+    #[test]
+    fn nested_operations_and_hollows() {
+        // a shell from subtracting a smaller sphere, then cut in half
+        let shell = Arc::new(Csg::new(CsgOp::Difference, sphere(0.0, 2.0), sphere(0.0, 1.0)));
+        assert_spans(shell.as_ref(), &[(-2.0, -1.0), (1.0, 2.0)]);
+        let half = Csg::new(CsgOp::Intersection, shell, sphere(3.0, 2.5));
+        assert_spans(&half, &[(1.0, 2.0)]);
+    }
+
+    #[test]
+    fn hits_report_which_side_of_the_result_they_are() {
+        let lens = Csg::new(CsgOp::Intersection, sphere(0.0, 1.0), sphere(1.0, 1.0));
+        let r = Ray::with_values(Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let enter = lens.hit(&r, 0.0, Float::MAX).unwrap();
+        assert!((enter.t - 10.0).abs() < 1e-6 && enter.front_face);
+        let exit = lens.hit(&r, enter.t + 0.1, Float::MAX).unwrap();
+        assert!((exit.t - 11.0).abs() < 1e-6 && !exit.front_face);
+    }
+
+    #[test]
+    fn inside_out_sphere_is_everything_but_the_ball() {
+        let bubble = sphere(0.0, -1.0);
+        assert_spans(bubble.as_ref(), &[(Float::NEG_INFINITY, -1.0), (1.0, Float::INFINITY)]);
+        let r = Ray::with_values(Point3::new(0.0, 5.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(bubble.intervals(&r).len(), 1);
+
+        // so subtracting one from a bigger sphere leaves the ball
+        let ball = Csg::new(CsgOp::Difference, sphere(0.0, 2.0), bubble);
+        assert_spans(&ball, &[(-1.0, 1.0)]);
+    }
+
+    #[test]
+    fn torus_grazed_on_top_still_has_its_tube() {
+        let (_, mat) = test_materials();
+        let torus = Torus::new(Vec3::origin(), 2.0, 0.5, mat);
+        // tangent to the top of the tube on one side, through it on the other
+        let r = Ray::with_values(Point3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, -0.05, 0.0));
+        let intervals = torus.intervals(&r);
+        assert!(!intervals.is_empty());
+        for i in &intervals {
+            assert!(i.enter.t < i.exit.t);
+            let middle = r.point_at_param(0.5 * (i.enter.t + i.exit.t));
+            assert!(torus.hit(&Ray::with_values(middle, Vec3::new(0.0, 1.0, 0.0)), 0.0, Float::MAX).is_some());
+        }
+
+        let graze = Ray::with_values(Point3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        for i in torus.intervals(&graze) {
+            assert!(i.enter.t <= i.exit.t);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! An edge-avoiding à-trous wavelet denoiser (Dammertz et al. 2010), guided
+//! by albedo and normal passes so it blurs noise without blurring edges or
+//! texture.
+
+use rayon::prelude::*;
+
+use super::image::HdrImage;
+use super::{Float, Vec3};
+
+/// The B3 spline the à-trous filter spreads out with every iteration.
+const KERNEL: [Float; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Albedo below this isn't divided out, as there's no texture to protect.
+const MIN_ALBEDO: Float = 0.01;
+
+#[derive(Debug, Clone)]
+pub struct Denoiser {
+    iterations: u32,
+    sigma_color: Float,
+    sigma_normal: Float,
+    sigma_albedo: Float,
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 0.5,
+            sigma_normal: 0.3,
+            sigma_albedo: 0.1,
+        }
+    }
+
+    /// Passes of the filter, each twice as wide as the last. Five reach 31
+    /// pixels from the center.
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.iterations = iterations;
+    }
+
+    /// How different colors, normals and albedos can be before they stop
+    /// blending. Larger values blur more across edges.
+    pub fn set_sigmas(&mut self, color: Float, normal: Float, albedo: Float) {
+        self.sigma_color = color;
+        self.sigma_normal = normal;
+        self.sigma_albedo = albedo;
+    }
+
+    /// Denoises `color` using `albedo` and `normal` passes of the same size.
+    pub fn denoise(&self, color: &HdrImage, albedo: &HdrImage, normal: &HdrImage) -> HdrImage {
+        let (width, height) = (color.width(), color.height());
+        assert!(
+            albedo.width() == width && albedo.height() == height
+                && normal.width() == width && normal.height() == height,
+            "feature passes don't match the image size"
+        );
+
+        let pixels = |img: &HdrImage| -> Vec<Vec3> {
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| *img.pixel(x, y))
+                .collect()
+        };
+        let albedo = pixels(albedo);
+        let normal = pixels(normal);
+
+        // filter the light arriving at surfaces rather than the color, so
+        // texture doesn't count as noise
+        let divisor: Vec<Vec3> = albedo.iter().map(|a| demodulation(*a)).collect();
+        let mut light: Vec<Vec3> = pixels(color)
+            .iter()
+            .zip(divisor.iter())
+            .map(|(c, d)| *c / *d)
+            .collect();
+
+        let mut sigma_color = self.sigma_color;
+        for i in 0..self.iterations {
+            let step = 1 << i;
+            let input = &light;
+            light = (0..width * height)
+                .into_par_iter()
+                .map(|p| {
+                    let (x, y) = (p % width, p / width);
+                    let (c, n, a) = (compress(input[p]), normal[p], albedo[p]);
+
+                    let mut sum = Vec3::origin();
+                    let mut total = 0.0;
+                    for (j, ky) in KERNEL.iter().enumerate() {
+                        let qy = y as isize + (j as isize - 2) * step;
+                        if qy < 0 || qy >= height as isize {
+                            continue;
+                        }
+                        for (k, kx) in KERNEL.iter().enumerate() {
+                            let qx = x as isize + (k as isize - 2) * step;
+                            if qx < 0 || qx >= width as isize {
+                                continue;
+                            }
+
+                            let q = qy as usize * width + qx as usize;
+                            let w = kx * ky
+                                * edge_stop(c - compress(input[q]), sigma_color)
+                                * edge_stop(n - normal[q], self.sigma_normal)
+                                * edge_stop(a - albedo[q], self.sigma_albedo);
+                            sum += w * input[q];
+                            total += w;
+                        }
+                    }
+                    sum / total
+                })
+                .collect();
+
+            // later passes span more pixels, so should trust color less
+            sigma_color /= 2.0;
+        }
+
+        let pixels = light.iter().zip(divisor.iter()).map(|(l, d)| *l * *d).collect();
+        HdrImage::new(width, height, pixels)
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser::new()
+    }
+}
+
+fn demodulation(albedo: Vec3) -> Vec3 {
+    let d = |a: Float| if a < MIN_ALBEDO { 1.0 } else { a };
+    Vec3::new(d(albedo.x()), d(albedo.y()), d(albedo.z()))
+}
+
+/// Squashes HDR colors into [0, 1) so bright outliers don't dominate the
+/// color differences.
+fn compress(c: Vec3) -> Vec3 {
+    let c = Vec3::max(c, Vec3::origin());
+    c / (c + Vec3::new(1.0, 1.0, 1.0))
+}
+
+fn edge_stop(diff: Vec3, sigma: Float) -> Float {
+    (-diff.sq_len() / (sigma * sigma)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, height: usize, f: impl Fn(usize, usize) -> Vec3) -> HdrImage {
+        let pixels = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| f(x, y)).collect();
+        HdrImage::new(width, height, pixels)
+    }
+
+    fn max_error(a: &HdrImage, f: impl Fn(usize, usize) -> Vec3) -> Float {
+        let mut err: Float = 0.0;
+        for y in 0..a.height() {
+            for x in 0..a.width() {
+                err = err.max((*a.pixel(x, y) - f(x, y)).len());
+            }
+        }
+        err
+    }
+
+    #[test]
+    fn smooths_noise_on_a_flat_surface() {
+        let gray = |_, _| Vec3::splat(0.5);
+        let up = |_, _| Vec3::new(0.0, 1.0, 0.0);
+        // light of 1 with +-0.2 of noise
+        let noisy = image(16, 16, |x, y| Vec3::splat(0.5 * if (x * 7 + y * 3) % 5 < 2 { 1.2 } else { 0.8 }));
+        let out = Denoiser::new().denoise(&noisy, &image(16, 16, gray), &image(16, 16, up));
+        assert!(max_error(&noisy, gray) > 0.15);
+        assert!(max_error(&out, gray) < 0.05, "{}", max_error(&out, gray));
+    }
+
+    #[test]
+    fn keeps_texture_and_geometric_edges() {
+        // a checkered albedo under even light, next to a wall facing away
+        let albedo = image(16, 8, |x, y| Vec3::splat(if (x + y) % 2 == 0 { 0.2 } else { 0.8 }));
+        let normal = image(16, 8, |x, _| if x < 8 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) });
+        let expected = |x: usize, y: usize| {
+            let a = if (x + y) % 2 == 0 { 0.2 } else { 0.8 };
+            Vec3::splat(if x < 8 { a } else { 0.1 * a })
+        };
+        let color = image(16, 8, expected);
+        let out = Denoiser::new().denoise(&color, &albedo, &normal);
+        assert!(max_error(&out, expected) < 1e-3, "{}", max_error(&out, expected));
+    }
+
+    #[test]
+    #[should_panic]
+    fn feature_passes_must_match_the_image() {
+        let small = image(2, 2, |_, _| Vec3::origin());
+        let big = image(4, 2, |_, _| Vec3::origin());
+        Denoiser::new().denoise(&small, &big, &small);
+    }
+}
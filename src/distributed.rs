@@ -0,0 +1,477 @@
+//! Splitting a render across worker processes, on this machine or others.
+//!
+//! A coordinator sends each worker the render's seed and scene options once,
+//! then jobs of rows and sample ranges one at a time. The worker answers
+//! each job with the films for those rows, which the coordinator merges.
+//! Renders reseed the generator in `random` for every sample, so it doesn't
+//! matter which worker renders a job.
+//!
+//! The protocol is lines of text, with the films sent as raw bytes:
+//!
+//! ```text
+//! setup <seed> <count>\n  followed by <count> lines of options
+//! job <y0> <y1> <s0> <s1>\n
+//! done <bytes>\n          followed by the films' state
+//! error <message>\n
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+use super::film::Film;
+
+/// Most options a setup may have.
+const MAX_OPTIONS: usize = 1024;
+/// Longest line either end sends.
+const MAX_LINE: u64 = 64 * 1024;
+/// Most samples of each pixel a job may ask for.
+const MAX_SAMPLES: u64 = 1 << 20;
+
+/// What every worker needs to know before rendering: the seed, and the
+/// options to build the scene from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setup {
+    pub seed: u64,
+    pub args: Vec<String>,
+}
+
+/// Samples `samples.0..samples.1` of every pixel in rows `rows.0..rows.1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Job {
+    pub rows: (usize, usize),
+    pub samples: (u64, u64),
+}
+
+impl Job {
+    /// Splits rendering samples `samples.0..samples.1` of a whole image into
+    /// jobs of `rows` rows each, or none if there are no samples to take.
+    pub fn split(height: usize, rows: usize, samples: (u64, u64)) -> Vec<Job> {
+        if samples.0 >= samples.1 {
+            return Vec::new();
+        }
+        (0..height)
+            .step_by(rows.max(1))
+            .map(|y0| Job {
+                rows: (y0, (y0 + rows).min(height)),
+                samples,
+            })
+            .collect()
+    }
+
+    /// Checks the job's rows are in an image `height` pixels tall.
+    pub fn check(&self, height: usize) -> Result<(), String> {
+        if self.rows.1 > height {
+            return Err(format!("rows {}..{} run past the image's {}", self.rows.0, self.rows.1, height));
+        }
+        Ok(())
+    }
+
+    /// Empty tiles of `films` for the job's samples to go in. The job's rows
+    /// must be in them.
+    pub fn tiles(&self, films: &[Film]) -> Vec<Film> {
+        films
+            .iter()
+            .map(|f| f.tile((0, self.rows.0), (f.width(), self.rows.1)))
+            .collect()
+    }
+
+    /// How many bytes of state the job's tiles of `films` come to.
+    pub fn state_len(&self, films: &[Film]) -> usize {
+        self.tiles(films).iter().map(Film::state_len).sum()
+    }
+}
+
+/// A connection to a worker process.
+pub struct Worker {
+    name: String,
+    reader: BufReader<Box<dyn Read + Send>>,
+    writer: BufWriter<Box<dyn Write + Send>>,
+    child: Option<Child>,
+}
+
+impl Worker {
+    /// Starts a worker that talks over its stdin and stdout.
+    pub fn spawn(mut command: Command) -> io::Result<Worker> {
+        let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let (stdin, stdout) = (child.stdin.take().unwrap(), child.stdout.take().unwrap());
+        Ok(Worker {
+            name: format!("process {}", child.id()),
+            reader: BufReader::new(Box::new(stdout)),
+            writer: BufWriter::new(Box::new(stdin)),
+            child: Some(child),
+        })
+    }
+
+    /// Connects to a worker listening on a TCP port.
+    pub fn connect<A: ToSocketAddrs + Display>(addr: A) -> io::Result<Worker> {
+        let stream = TcpStream::connect(&addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Worker {
+            name: addr.to_string(),
+            reader: BufReader::new(Box::new(stream.try_clone()?)),
+            writer: BufWriter::new(Box::new(stream)),
+            child: None,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn setup(&mut self, setup: &Setup) -> io::Result<()> {
+        if setup.args.iter().any(|a| a.contains('\n')) {
+            return Err(invalid_data("options can't contain line breaks"));
+        }
+        writeln!(self.writer, "setup {} {}", setup.seed, setup.args.len())?;
+        for arg in &setup.args {
+            writeln!(self.writer, "{}", arg)?;
+        }
+        self.writer.flush()
+    }
+
+    /// Renders a job, returning the films' state for `Job::tiles` to load,
+    /// which must come to `len` bytes.
+    pub fn render(&mut self, job: &Job, len: usize) -> io::Result<Vec<u8>> {
+        let Job { rows, samples } = job;
+        writeln!(self.writer, "job {} {} {} {}", rows.0, rows.1, samples.0, samples.1)?;
+        self.writer.flush()?;
+
+        let line = read_line(&mut self.reader)?.ok_or_else(|| closed(&self.name))?;
+        match line.split_once(' ') {
+            Some(("done", reply_len)) => {
+                if reply_len.parse() != Ok(len) {
+                    return Err(invalid_data("invalid reply length"));
+                }
+                let mut state = vec![0; len];
+                self.reader.read_exact(&mut state)?;
+                Ok(state)
+            }
+            Some(("error", msg)) => {
+                // the worker couldn't build the scene from the options
+                Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", self.name, msg)))
+            },
+            _ => Err(invalid_data("invalid reply from worker")),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // closing the connection tells the worker there's nothing more to do,
+        // and closing our end of its output stops it blocking on a reply
+        let _ = self.writer.flush();
+        self.writer = BufWriter::new(Box::new(io::sink()));
+        self.reader = BufReader::new(Box::new(io::empty()));
+        if let Some(child) = &mut self.child {
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Renders `jobs` on `workers` and merges the results into `films`.
+///
+/// Jobs are handed out as workers become free, and the results are merged
+/// in the order of `jobs`, so the films come out the same however many
+/// workers there are. A job whose worker fails goes to another one, and
+/// rendering only fails once no workers are left.
+pub fn render(workers: Vec<Worker>, setup: &Setup, jobs: &[Job], films: &mut [Film]) -> io::Result<()> {
+    let lens: Vec<usize> = jobs.iter().map(|j| j.state_len(films)).collect();
+    let (events_tx, events) = mpsc::channel();
+    thread::scope(|scope| {
+        let mut senders = Vec::new();
+        for (w, mut worker) in workers.into_iter().enumerate() {
+            let (tx, rx) = mpsc::channel::<usize>();
+            let (events, lens) = (events_tx.clone(), &lens);
+            scope.spawn(move || {
+                let mut ready = false;
+                for j in rx {
+                    let result = if ready {
+                        worker.render(&jobs[j], lens[j])
+                    } else {
+                        worker.setup(setup).and_then(|_| worker.render(&jobs[j], lens[j]))
+                    };
+                    ready = true;
+                    let failed = result.is_err();
+                    let _ = events.send((w, j, result));
+                    if failed {
+                        return;
+                    }
+                }
+            });
+            senders.push(Some(tx));
+        }
+        drop(events_tx);
+
+        let mut queue: VecDeque<usize> = (0..jobs.len()).collect();
+        let mut results: Vec<Option<Vec<u8>>> = vec![None; jobs.len()];
+        let (mut merged, mut error) = (0, None);
+        let mut idle: Vec<usize> = (0..senders.len()).collect();
+
+        while merged < jobs.len() {
+            while let (Some(&w), Some(&j)) = (idle.last(), queue.front()) {
+                idle.pop();
+                if senders[w].as_ref().is_some_and(|tx| tx.send(j).is_ok()) {
+                    queue.pop_front();
+                }
+            }
+            if senders.iter().all(Option::is_none) {
+                break;
+            }
+
+            match events.recv() {
+                Ok((w, j, Ok(state))) => {
+                    results[j] = Some(state);
+                    idle.push(w);
+                }
+                Ok((w, j, Err(e))) => {
+                    queue.push_front(j);
+                    senders[w] = None;
+                    error = Some(e);
+                }
+                Err(_) => break,
+            }
+
+            while let Some(state) = results.get_mut(merged).and_then(Option::take) {
+                let mut tiles = jobs[merged].tiles(films);
+                let mut r = state.as_slice();
+                for tile in tiles.iter_mut() {
+                    tile.load_state(&mut r)?;
+                }
+                if !r.is_empty() {
+                    return Err(invalid_data("worker rendered different passes"));
+                }
+                for (f, t) in films.iter_mut().zip(tiles.iter()) {
+                    f.merge(t);
+                }
+                merged += 1;
+            }
+        }
+
+        // lets the worker threads finish
+        senders.clear();
+        if merged < jobs.len() {
+            return Err(error.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "no workers")));
+        }
+        Ok(())
+    })
+}
+
+/// Answers a coordinator until it closes the connection. `start` builds the
+/// scene from the setup, and returns what renders the jobs into tiles, or
+/// rejects those that don't fit the image.
+pub fn serve<R, W, F, S>(reader: R, writer: W, start: F) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+    F: FnOnce(&Setup) -> Result<S, String>,
+    S: FnMut(&Job) -> Result<Vec<Film>, String>,
+{
+    let (mut reader, mut writer) = (reader, BufWriter::new(writer));
+    let setup = match read_line(&mut reader)? {
+        Some(line) => {
+            let mut words = line.split(' ').skip(1);
+            match (words.next(), words.next(), line.starts_with("setup ")) {
+                (Some(seed), Some(count), true) => {
+                    let seed = seed.parse().map_err(|_| invalid_data("invalid seed"))?;
+                    let count = count.parse().map_err(|_| invalid_data("invalid option count"))?;
+                    if count > MAX_OPTIONS {
+                        return Err(invalid_data("too many options"));
+                    }
+                    let mut args = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        args.push(read_line(&mut reader)?.ok_or_else(|| closed("coordinator"))?);
+                    }
+                    Setup { seed, args }
+                }
+                _ => return Err(invalid_data("expected setup")),
+            }
+        }
+        None => return Ok(()),
+    };
+
+    let mut render = start(&setup);
+    while let Some(line) = read_line(&mut reader)? {
+        let job = parse_job(&line).ok_or_else(|| invalid_data("expected a job"))?;
+        match render.as_mut().map_err(|msg| msg.clone()).and_then(|render| render(&job)) {
+            Ok(tiles) => {
+                let mut state = Vec::new();
+                for tile in tiles {
+                    tile.save_state(&mut state)?;
+                }
+                writeln!(writer, "done {}", state.len())?;
+                writer.write_all(&state)?;
+            }
+            Err(msg) => writeln!(writer, "error {}", msg.replace('\n', " "))?,
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn parse_job(line: &str) -> Option<Job> {
+    let mut words = line.strip_prefix("job ")?.split(' ');
+    let mut next = || words.next()?.parse::<u64>().ok();
+    let (y0, y1, s0, s1) = (next()?, next()?, next()?, next()?);
+    if y0 >= y1 || s0 >= s1 || s1 - s0 > MAX_SAMPLES {
+        return None;
+    }
+    Some(Job {
+        rows: (y0 as usize, y1 as usize),
+        samples: (s0, s1),
+    })
+}
+
+/// The next line without its line break, or `None` once the other end has
+/// closed the connection.
+fn read_line<R: BufRead>(r: &mut R) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if r.take(MAX_LINE).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+    } else if line.len() as u64 == MAX_LINE {
+        return Err(invalid_data("line too long"));
+    }
+    Ok(Some(line))
+}
+
+fn closed(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} closed the connection", name))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::BoxFilter;
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    fn films() -> Vec<Film> {
+        vec![Film::new(3, 4, Arc::new(BoxFilter::new(0.5)))]
+    }
+
+    fn worker(reply: Vec<u8>) -> Worker {
+        Worker {
+            name: String::from("test"),
+            reader: BufReader::new(Box::new(Cursor::new(reply))),
+            writer: BufWriter::new(Box::new(io::sink())),
+            child: None,
+        }
+    }
+
+    /// What `serve` answers to `input`, with jobs rendered as tiles of `films`.
+    fn serve_input(input: &str) -> io::Result<(Option<Setup>, Vec<u8>)> {
+        let (mut setup, mut output) = (None, Vec::new());
+        serve(input.as_bytes(), &mut output, |s: &Setup| {
+            setup = Some(s.clone());
+            let films = films();
+            Ok(move |job: &Job| {
+                job.check(films[0].height())?;
+                Ok(job.tiles(&films))
+            })
+        })?;
+        Ok((setup, output))
+    }
+
+    #[test]
+    fn parses_jobs() {
+        let job = Job {
+            rows: (2, 4),
+            samples: (0, 8),
+        };
+        assert_eq!(parse_job("job 2 4 0 8"), Some(job));
+        assert_eq!(parse_job("job 4 2 0 8"), None);
+        assert_eq!(parse_job("job 2 4 8 0"), None);
+        assert_eq!(parse_job("job 2 2 0 8"), None);
+        assert_eq!(parse_job("job 2 4 8 8"), None);
+        assert_eq!(parse_job(&format!("job 2 4 0 {}", MAX_SAMPLES + 1)), None);
+        assert_eq!(parse_job("job 2 4 0"), None);
+        assert_eq!(parse_job("job 2 4 0 x"), None);
+        assert_eq!(parse_job("done 2 4 0 8"), None);
+    }
+
+    #[test]
+    fn splits_rows_into_jobs() {
+        let jobs = Job::split(5, 2, (0, 4));
+        let rows: Vec<_> = jobs.iter().map(|j| j.rows).collect();
+        assert_eq!(rows, [(0, 2), (2, 4), (4, 5)]);
+        assert!(Job::split(5, 2, (4, 4)).is_empty());
+        assert_eq!(jobs[2].state_len(&films()), films()[0].tile((0, 4), (3, 5)).state_len());
+    }
+
+    #[test]
+    fn serves_jobs() {
+        let (setup, output) = serve_input("setup 7 2\n--width\n3\njob 1 3 0 2\n").unwrap();
+        let setup = setup.unwrap();
+        assert_eq!(setup.seed, 7);
+        assert_eq!(setup.args, ["--width", "3"]);
+
+        let len = parse_job("job 1 3 0 2").unwrap().state_len(&films());
+        let header = format!("done {}\n", len);
+        assert!(output.starts_with(header.as_bytes()));
+        assert_eq!(output.len(), header.len() + len);
+    }
+
+    #[test]
+    fn reports_scenes_that_fail_to_build() {
+        let mut output = Vec::new();
+        type Render = fn(&Job) -> Result<Vec<Film>, String>;
+        let start = |_: &Setup| -> Result<Render, String> { Err(String::from("bad\nscene")) };
+        serve(&b"setup 0 0\njob 0 1 0 1\n"[..], &mut output, start).unwrap();
+        assert_eq!(output, b"error bad scene\n");
+    }
+
+    #[test]
+    fn rejects_jobs_past_the_image() {
+        let (_, output) = serve_input("setup 0 0\njob 1000 1001 0 1\njob 3 5 0 1\njob 3 4 0 1\n").unwrap();
+        let output = String::from_utf8_lossy(&output);
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some("error rows 1000..1001 run past the image's 4"));
+        assert_eq!(lines.next(), Some("error rows 3..5 run past the image's 4"));
+        assert!(lines.next().unwrap().starts_with("done "));
+    }
+
+    #[test]
+    fn rejects_bad_setups() {
+        assert!(serve_input("job 0 1 0 1\n").is_err());
+        assert!(serve_input("setup x 0\n").is_err());
+        assert!(serve_input("setup 0 1000000000000\n").is_err());
+        assert!(serve_input("setup 0 2\n--width\n").is_err());
+        assert!(serve_input("setup 0 0\njob 1 0 0 1\n").is_err());
+        assert!(serve_input("").unwrap().0.is_none());
+    }
+
+    #[test]
+    fn rejects_overlong_lines() {
+        let line = "x".repeat(MAX_LINE as usize + 1);
+        assert!(read_line(&mut line.as_bytes()).is_err());
+        assert_eq!(read_line(&mut &b"short\n"[..]).unwrap().as_deref(), Some("short"));
+    }
+
+    #[test]
+    fn checks_reply_lengths() {
+        let job = Job {
+            rows: (0, 1),
+            samples: (0, 1),
+        };
+        assert_eq!(worker(b"done 3\nabc".to_vec()).render(&job, 3).unwrap(), b"abc");
+        let wrong = worker(b"done 4\nabcd".to_vec()).render(&job, 3).unwrap_err();
+        assert_eq!(wrong.kind(), io::ErrorKind::InvalidData);
+        let huge = worker(b"done 18446744073709551615\n".to_vec()).render(&job, 3).unwrap_err();
+        assert_eq!(huge.kind(), io::ErrorKind::InvalidData);
+        let failed = worker(b"error no scene\n".to_vec()).render(&job, 3).unwrap_err();
+        assert_eq!(failed.kind(), io::ErrorKind::InvalidInput);
+        let closed = worker(Vec::new()).render(&job, 3).unwrap_err();
+        assert_eq!(closed.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::consts::PI;
+use crate::Float;
+
+/// A pixel reconstruction filter, weighting samples by their offset in
+/// pixels from a pixel's center.
+pub trait Filter: Send + Sync {
+    /// How far from a pixel's center in each axis samples still count.
+    fn radius(&self) -> Float;
+
+    fn eval(&self, x: Float, y: Float) -> Float;
+}
+
+/// Counts every sample within `radius` equally. A radius of half a pixel
+/// averages each pixel's own samples.
+pub struct BoxFilter {
+    radius: Float,
+}
+
+impl BoxFilter {
+    pub fn new(radius: Float) -> Self {
+        Self {
+            radius,
+        }
+    }
+}
+
+impl Default for BoxFilter {
+    fn default() -> Self {
+        BoxFilter::new(0.5)
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> Float {
+        self.radius
+    }
+
+    fn eval(&self, x: Float, y: Float) -> Float {
+        if x.abs() <= self.radius && y.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Weights fall off linearly to zero at `radius`.
+pub struct TentFilter {
+    radius: Float,
+}
+
+impl TentFilter {
+    pub fn new(radius: Float) -> Self {
+        Self {
+            radius,
+        }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> Float {
+        self.radius
+    }
+
+    fn eval(&self, x: Float, y: Float) -> Float {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+/// A Gaussian with standard deviation `sigma`, shifted down to reach zero at
+/// `radius`.
+pub struct GaussianFilter {
+    radius: Float,
+    sigma: Float,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: Float, sigma: Float) -> Self {
+        Self {
+            radius,
+            sigma,
+        }
+    }
+
+    fn gaussian(&self, x: Float) -> Float {
+        let g = |x: Float| (-x * x / (2.0 * self.sigma * self.sigma)).exp();
+        (g(x) - g(self.radius)).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> Float {
+        self.radius
+    }
+
+    fn eval(&self, x: Float, y: Float) -> Float {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+/// The Mitchell-Netravali cubic. It has small negative lobes that sharpen
+/// edges; `b = c = 1/3` is their recommended balance of blurring and ringing.
+pub struct MitchellFilter {
+    radius: Float,
+    b: Float,
+    c: Float,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: Float, b: Float, c: Float) -> Self {
+        Self {
+            radius,
+            b,
+            c,
+        }
+    }
+
+    // defined over [-2, 2], which the radius is stretched to
+    fn mitchell(&self, x: Float) -> Float {
+        let x = (2.0 * x / self.radius).abs();
+        let (b, c) = (self.b, self.c);
+        let w = if x < 1.0 {
+            (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)
+        } else if x < 2.0 {
+            (-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        } else {
+            0.0
+        };
+        w / 6.0
+    }
+}
+
+impl Default for MitchellFilter {
+    fn default() -> Self {
+        MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0)
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> Float {
+        self.radius
+    }
+
+    fn eval(&self, x: Float, y: Float) -> Float {
+        self.mitchell(x) * self.mitchell(y)
+    }
+}
+
+/// A sinc windowed by a wider sinc that reaches zero at `radius`, the
+/// sharpest of these filters but prone to ringing.
+pub struct LanczosFilter {
+    radius: Float,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: Float) -> Self {
+        Self {
+            radius,
+        }
+    }
+
+    fn lanczos(&self, x: Float) -> Float {
+        if x.abs() >= self.radius {
+            return 0.0;
+        }
+        sinc(x) * sinc(x / self.radius)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> Float {
+        self.radius
+    }
+
+    fn eval(&self, x: Float, y: Float) -> Float {
+        self.lanczos(x) * self.lanczos(y)
+    }
+}
+
+fn sinc(x: Float) -> Float {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> Vec<Box<dyn Filter>> {
+        vec![
+            Box::new(BoxFilter::new(1.0)),
+            Box::new(TentFilter::new(1.5)),
+            Box::new(GaussianFilter::new(1.5, 0.5)),
+            Box::new(MitchellFilter::default()),
+            Box::new(LanczosFilter::new(3.0)),
+        ]
+    }
+
+    #[test]
+    fn filters_peak_at_the_center_and_vanish_past_their_radius() {
+        for f in filters() {
+            let r = f.radius();
+            let center = f.eval(0.0, 0.0);
+            assert!(center > 0.0);
+            for &(x, y) in [(0.3, 0.0), (0.0, -0.6), (0.2, 0.2)].iter() {
+                assert!(f.eval(x, y) <= center);
+                assert_eq!(f.eval(x, y), f.eval(-x, -y));
+            }
+            assert_eq!(f.eval(r + 0.01, 0.0), 0.0);
+            assert_eq!(f.eval(0.0, -r - 0.01), 0.0);
+        }
+    }
+
+    #[test]
+    fn gaussians_reach_zero_at_their_radius() {
+        let f = GaussianFilter::new(2.0, 0.7);
+        assert_eq!(f.eval(2.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn mitchell_weights_sum_to_one() {
+        // at its natural width the cubic reproduces constants
+        let f = MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0);
+        for &offset in [0.0, 0.25, 0.5].iter() {
+            let sum: Float = (-3..=3).map(|i| f.mitchell(i as Float + offset)).sum();
+            assert!((sum - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn lanczos_crosses_zero_at_whole_pixels() {
+        let f = LanczosFilter::new(3.0);
+        assert!(f.lanczos(1.0).abs() < 1e-6 && f.lanczos(2.0).abs() < 1e-6);
+        assert!(f.lanczos(1.5) < 0.0);
+    }
+}
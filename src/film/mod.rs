@@ -0,0 +1,253 @@
+mod filter;
+
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::Arc;
+
+use super::image::HdrImage;
+use super::{Float, Vec3};
+
+pub use filter::{BoxFilter, Filter, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Pixel {
+    sum: Vec3,
+    weight: Float,
+}
+
+/// Accumulates radiance samples into pixels, splatting each one into every
+/// pixel within the filter's radius weighted by the filter.
+///
+/// Sample positions are in raster space: pixel `(x, y)` covers `x..x + 1`
+/// and `y..y + 1`, with row 0 at the top. A film can also stand for a tile
+/// of a larger one, so threads can splat without sharing pixels and then
+/// `merge` their tiles back.
+#[derive(Clone)]
+pub struct Film {
+    filter: Arc<dyn Filter>,
+    width: usize,
+    height: usize,
+    // the pixels actually stored, which is only part of the image for tiles
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Arc<dyn Filter>) -> Self {
+        Film::window(filter, width, height, (0, 0), (width, height))
+    }
+
+    fn window(
+        filter: Arc<dyn Filter>,
+        width: usize,
+        height: usize,
+        (x0, y0): (usize, usize),
+        (x1, y1): (usize, usize),
+    ) -> Self {
+        Film {
+            filter,
+            width,
+            height,
+            x0,
+            y0,
+            x1,
+            y1,
+            pixels: vec![Pixel::default(); (x1 - x0) * (y1 - y0)],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn filter(&self) -> &Arc<dyn Filter> {
+        &self.filter
+    }
+
+    /// An empty tile holding every pixel that samples taken in the pixels
+    /// `x0..x1` and `y0..y1` can reach.
+    pub fn tile(&self, (x0, y0): (usize, usize), (x1, y1): (usize, usize)) -> Film {
+        let r = self.filter.radius();
+        let lo = |p: usize| (p as Float - r).floor().max(0.0) as usize;
+        let hi = |p: usize, max: usize| ((p as Float + r).ceil() as usize).min(max);
+        Film::window(
+            self.filter.clone(),
+            self.width,
+            self.height,
+            (lo(x0), lo(y0)),
+            (hi(x1, self.width), hi(y1, self.height)),
+        )
+    }
+
+    /// Adds a tile's samples to this film.
+    pub fn merge(&mut self, tile: &Film) {
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                if let Some(i) = self.index(x, y) {
+                    let p = tile.pixels[tile.index(x, y).unwrap()];
+                    self.pixels[i].sum += p.sum;
+                    self.pixels[i].weight += p.weight;
+                }
+            }
+        }
+    }
+
+    pub fn add_sample(&mut self, x: Float, y: Float, radiance: Vec3) {
+        // pixel centers are at half integers
+        let (x, y) = (x - 0.5, y - 0.5);
+        let r = self.filter.radius();
+        let range = |p: Float, lo: usize, hi: usize| {
+            let first = (p - r).ceil().max(lo as Float) as usize;
+            let last = ((p + r).floor() + 1.0).clamp(0.0, hi as Float) as usize;
+            first..last
+        };
+
+        for py in range(y, self.y0, self.y1) {
+            for px in range(x, self.x0, self.x1) {
+                let w = self.filter.eval(px as Float - x, py as Float - y);
+                if w == 0.0 {
+                    continue;
+                }
+                let i = self.index(px, py).unwrap();
+                self.pixels[i].sum += w * radiance;
+                self.pixels[i].weight += w;
+            }
+        }
+    }
+
+    /// The filtered radiance of a pixel, black if no samples reached it.
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        match self.index(x, y).map(|i| self.pixels[i]) {
+            Some(p) if p.weight != 0.0 => p.sum / p.weight,
+            _ => Vec3::origin(),
+        }
+    }
+
+    pub fn to_image(&self) -> HdrImage {
+        let pixels = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x, y))
+            .collect();
+        HdrImage::new(self.width, self.height, pixels)
+    }
+
+    /// Writes the accumulated sums and weights exactly, so another run can
+    /// `load_state` them and carry on adding samples.
+    /// How many bytes `save_state` writes.
+    pub fn state_len(&self) -> usize {
+        self.pixels.len() * 4 * mem::size_of::<Float>()
+    }
+
+    pub fn save_state<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for p in &self.pixels {
+            for v in &[p.sum.x(), p.sum.y(), p.sum.z(), p.weight] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_state<R: Read>(&mut self, r: &mut R) -> io::Result<()> {
+        let mut buf = [0u8; mem::size_of::<Float>()];
+        let mut next = || -> io::Result<Float> {
+            r.read_exact(&mut buf)?;
+            Ok(Float::from_le_bytes(buf))
+        };
+        for p in self.pixels.iter_mut() {
+            let sum = Vec3::new(next()?, next()?, next()?);
+            *p = Pixel {
+                sum,
+                weight: next()?,
+            };
+        }
+        Ok(())
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.x0 || x >= self.x1 || y < self.y0 || y >= self.y1 {
+            return None;
+        }
+        Some((y - self.y0) * (self.x1 - self.x0) + (x - self.x0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_filtered_pixels_average_their_samples() {
+        let mut film = Film::new(2, 1, Arc::new(BoxFilter::default()));
+        film.add_sample(0.25, 0.5, Vec3::new(1.0, 0.0, 0.0));
+        film.add_sample(0.75, 0.5, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(film.pixel(0, 0), Vec3::new(0.5, 0.5, 0.0));
+        // nothing reached the second pixel
+        assert_eq!(film.pixel(1, 0), Vec3::origin());
+    }
+
+    #[test]
+    fn wide_filters_spread_samples_to_neighbours() {
+        let mut film = Film::new(3, 3, Arc::new(TentFilter::new(1.5)));
+        film.add_sample(1.5, 1.5, Vec3::new(1.0, 1.0, 1.0));
+        for y in 0..3 {
+            for x in 0..3 {
+                assert_eq!(film.pixel(x, y), Vec3::new(1.0, 1.0, 1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn merged_tiles_match_one_film() {
+        let filter: Arc<dyn Filter> = Arc::new(MitchellFilter::default());
+        let samples: Vec<(Float, Float, Vec3)> = (0..64)
+            .map(|i| {
+                let (x, y) = ((i % 8) as Float * 0.5 + 0.3, (i / 8) as Float * 0.5 + 0.1);
+                (x, y, Vec3::new(x, y, 1.0))
+            })
+            .collect();
+
+        let mut whole = Film::new(4, 4, filter.clone());
+        let mut merged = Film::new(4, 4, filter);
+        for &(x, y, c) in &samples {
+            whole.add_sample(x, y, c);
+        }
+        for &(x0, x1) in [(0, 2), (2, 4)].iter() {
+            let mut tile = merged.tile((x0, 0), (x1, 4));
+            for &(x, y, c) in samples.iter().filter(|s| s.0 >= x0 as Float && s.0 < x1 as Float) {
+                tile.add_sample(x, y, c);
+            }
+            merged.merge(&tile);
+        }
+
+        for y in 0..4 {
+            for x in 0..4 {
+                assert!((whole.pixel(x, y) - merged.pixel(x, y)).len() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn state_round_trips_exactly() {
+        let mut film = Film::new(3, 2, Arc::new(GaussianFilter::new(1.5, 0.5)));
+        film.add_sample(0.7, 1.3, Vec3::new(0.1, 0.2, 0.3));
+        film.add_sample(2.2, 0.4, Vec3::new(5.0, 0.0, 1.0 / 3.0));
+        let mut state = Vec::new();
+        film.save_state(&mut state).unwrap();
+
+        let mut loaded = Film::new(3, 2, film.filter().clone());
+        loaded.load_state(&mut state.as_slice()).unwrap();
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(loaded.pixel(x, y), film.pixel(x, y));
+            }
+        }
+        assert!(loaded.load_state(&mut &state[..state.len() - 1]).is_err());
+    }
+}
@@ -2,29 +2,109 @@
 //use std::rc::Rc;
 use std::sync::Arc;
 
-use super::{Ray, Vec3};
-use super::material::Material;
+use super::{Float, Normal, Point3, Ray, Vec3};
+use super::aabb::Aabb;
+use super::material::MaterialId;
+use super::packet::{PacketHits, RayPacket, WIDTH};
+use super::stats;
 
+#[derive(Clone, Copy)]
 pub struct HitRecord {
-    pub t: f64,
-    pub point: Vec3,
-    pub normal: Vec3,
-    pub mat: Arc<dyn Material>,
+    pub t: Float,
+    pub point: Point3,
+    /// Bound on the absolute error of `point` in each axis.
+    pub error: Vec3,
+    /// Always on the side of the surface the ray came from.
+    pub normal: Normal,
+    /// Whether the ray hit the outside of the surface.
+    pub front_face: bool,
+    /// Refractive index of whatever surrounds the object at this point,
+    /// filled in by the renderer as it tracks which media a path is in.
+    pub outside_ior: Float,
+    /// Hero wavelength of the path in nm when rendering spectrally, which
+    /// dispersive materials use to pick their refractive index.
+    pub wavelength: Option<Float>,
+    pub u: Float,
+    pub v: Float,
+    /// The material of the object hit, in the renderer's `Materials`.
+    pub material: MaterialId,
+    /// Index of the object hit in the outermost `HitableList`.
+    pub object: usize,
 }
 
 impl HitRecord {
-    pub fn new(t: f64, point: Vec3, normal: Vec3, mat: Arc<dyn Material>) -> Self {
+    /// Takes the surface's outward unit `normal` and flips it to face
+    /// against `r`.
+    pub fn new(
+        r: &Ray,
+        t: Float,
+        point: Point3,
+        error: Vec3,
+        normal: Vec3,
+        (u, v): (Float, Float),
+        material: MaterialId,
+    ) -> Self {
+        let front_face = Vec3::dot(r.direction(), normal) < 0.0;
+        let normal = Normal::from_unit(if front_face { normal } else { -normal });
         Self {
-            t: t,
-            point: point,
-            normal: normal,
-            mat: mat,
+            t,
+            point,
+            error,
+            normal,
+            front_face,
+            outside_ior: 1.0,
+            wavelength: None,
+            u,
+            v,
+            material,
+            object: 0,
         }
     }
+
+    /// A ray leaving the hit point in direction `dir` that won't hit the
+    /// same surface again at `t` near zero.
+    pub fn spawn_ray(&self, dir: Vec3) -> Ray {
+        Ray::spawn(self.point, self.error, self.normal, dir)
+    }
 }
 
 pub trait Hit: Sync + Send {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord>;
+
+    /// The `t` of the hit `hit` would return, without building its record.
+    /// Shapes that find it cheaply override this so closest-hit searches
+    /// only build the record of the hit they end up with.
+    fn hit_t(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        self.hit(r, t_min, t_max).map(|h| h.t)
+    }
+
+    /// Adds hits closer than those in `hits` for each ray of a packet. By
+    /// default the rays are tested one at a time; shapes worth it test them
+    /// together.
+    fn hit_packet(&self, packet: &RayPacket, t_min: Float, hits: &mut PacketHits) {
+        for lane in 0..WIDTH {
+            if !hits.is_active(lane, t_min) {
+                continue;
+            }
+            if let Some(record) = self.hit(packet.ray(lane), t_min, hits.t_max()[lane]) {
+                hits.add(lane, record);
+            }
+        }
+    }
+
+    /// Box enclosing the whole object, or `None` if it's unbounded. Objects
+    /// without one are tested against every ray.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    /// The materials the object's surfaces refer to, so a renderer can check
+    /// they're all in its `Materials` before rendering. `Renderer::new` can
+    /// only reject foreign ids that objects report, so those leaving this as
+    /// it is go unchecked until a hit indexes a registry with them.
+    fn material_ids(&self) -> Vec<MaterialId> {
+        Vec::new()
+    }
 }
 
 #[derive(Clone)]
@@ -32,6 +112,12 @@ pub struct HitableList{
     hitables: Arc<Vec<Arc<dyn Hit>>>,
 }
 
+impl Default for HitableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HitableList {
     pub fn new() -> Self {
         HitableList{
@@ -49,23 +135,116 @@ impl HitableList {
         self.hitables.len()
     }
 
-    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut closest: Option<HitRecord> = None;
-        for h in self.hitables.iter() {
-            match h.hit(r, t_min, t_max) {
-                Some(record) => {
-                    if let Some(c) = &closest {
-                        if record.t < c.t {
-                            closest = Some(record)
-                        }
-                    } else {
-                        closest = Some(record)
-                    }
-                },
-                None => {},
-            };
+    pub fn is_empty(&self) -> bool {
+        self.hitables.is_empty()
+    }
+
+    pub fn objects(&self) -> &[Arc<dyn Hit>] {
+        &self.hitables
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        // each object only has to beat the closest hit so far, and only the
+        // one that ends up closest builds a record, asked again with the
+        // range it found that hit in
+        let mut closest = None;
+        let mut closest_so_far = t_max;
+        let mut hits = 0;
+        for (i, h) in self.hitables.iter().enumerate() {
+            if let Some(t) = h.hit_t(r, t_min, closest_so_far) {
+                closest = Some((i, closest_so_far));
+                closest_so_far = t;
+                hits += 1;
+            }
         }
+        stats::count(|s| {
+            s.intersection_tests += self.hitables.len() as u64;
+            s.intersection_hits += hits;
+        });
+        let (i, t_max) = closest?;
+        let mut record = self.hitables[i].hit(r, t_min, t_max)?;
+        record.object = i;
+        Some(record)
+    }
+
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.hitables.iter().map(|h| h.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |acc, b| Some(Aabb::surrounding(&acc, &b?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::material::test_materials;
+    use crate::shape::Plane;
+    use crate::sphere::Sphere;
+
+    fn material() -> MaterialId {
+        test_materials().1
+    }
+
+    #[test]
+    fn normals_face_against_the_ray() {
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let from_above = Ray::with_values(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = HitRecord::new(&from_above, 1.0, Point3::origin(), Vec3::origin(), up, (0.0, 0.0), material());
+        assert!(rec.front_face);
+        assert_eq!(*rec.normal, up);
+
+        let from_below = Ray::with_values(Point3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = HitRecord::new(&from_below, 1.0, Point3::origin(), Vec3::origin(), up, (0.0, 0.0), material());
+        assert!(!rec.front_face);
+        assert_eq!(*rec.normal, -up);
+    }
+
+    #[test]
+    fn rays_spawned_off_a_hit_leave_the_surface() {
+        let s = Sphere::new(Vec3::origin(), 1.0, material());
+        let r = Ray::with_values(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = s.hit(&r, 0.0, Float::MAX).unwrap();
+        // heading back out must not find the surface it started on
+        let out = rec.spawn_ray(Vec3::new(0.3, 0.2, 1.0));
+        assert!(s.hit(&out, 0.0, Float::MAX).is_none());
+        // heading in must find the far side, not this one
+        let inside = rec.spawn_ray(Vec3::new(0.0, 0.0, -1.0));
+        let far = s.hit(&inside, 0.0, Float::MAX).unwrap();
+        assert!((far.t - 2.0).abs() < 1e-4 && !far.front_face);
+    }
+
+    #[test]
+    fn lists_find_the_closest_hit_and_its_object() {
+        let list = HitableList::with_vals(vec![
+            Arc::new(Sphere::new(Vec3::new(0.0, 0.0, -10.0), 1.0, material())) as Arc<dyn Hit>,
+            Arc::new(Sphere::new(Vec3::new(0.0, 0.0, -4.0), 1.0, material())),
+        ]);
+        let r = Ray::with_values(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = list.hit(&r, 0.0, Float::MAX).unwrap();
+        assert_eq!(rec.object, 1);
+        assert!((rec.t - 3.0).abs() < 1e-5);
+        assert!(list.hit(&r, 0.0, 2.0).is_none());
+    }
+
+    #[test]
+    fn lists_build_the_record_of_the_closest_hit_only() {
+        // the plane only reports its t through the default `hit_t`
+        let plane = Plane::new(Vec3::new(0.0, 0.0, -2.5), Vec3::new(0.0, 0.0, 1.0), material());
+        let list = HitableList::with_vals(vec![
+            Arc::new(Sphere::new(Vec3::new(0.0, 0.0, -10.0), 1.0, material())) as Arc<dyn Hit>,
+            Arc::new(plane),
+            Arc::new(Sphere::new(Vec3::new(0.0, 0.0, -4.0), 1.0, material())),
+        ]);
+        let r = Ray::with_values(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = list.hit(&r, 0.0, Float::MAX).unwrap();
+        assert_eq!(rec.object, 1);
+        assert!((rec.t - 2.5).abs() < 1e-5);
+        assert!((*rec.point - Vec3::new(0.0, 0.0, -2.5)).len() < 1e-5);
 
-        closest
+        let rec = list.hit(&r, 3.5, Float::MAX).unwrap();
+        assert_eq!(rec.object, 2);
+        assert!((rec.t - 5.0).abs() < 1e-5 && !rec.front_face);
     }
 }
@@ -0,0 +1,462 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use super::color::Pipeline;
+use super::{Float, Vec3};
+
+/// A floating point RGB image stored row-major from the top row down.
+pub struct HdrImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl HdrImage {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(width * height, pixels.len(), "pixel count doesn't match dimensions");
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Loads a PFM or Radiance HDR file, picking the format from the extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let mut reader = BufReader::new(File::open(path)?);
+
+        match ext.as_deref() {
+            Some("pfm") => read_pfm(&mut reader),
+            Some("hdr") | Some("pic") => read_radiance(&mut reader),
+            _ => Err(invalid_data("unsupported HDR image extension")),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &Vec3 {
+        &self.pixels[y * self.width + x]
+    }
+
+    /// Saves as a little endian PFM file.
+    pub fn save_pfm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        write_pfm(&mut w, self)?;
+        w.flush()
+    }
+
+    /// Saves as an 8 bit PNG for display through `display`.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P, display: &Pipeline) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        write_png(&mut w, self, display)?;
+        w.flush()
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_token<R: BufRead>(r: &mut R) -> io::Result<String> {
+    let mut token = String::new();
+    let mut byte = [0u8; 1];
+    loop {
+        r.read_exact(&mut byte)?;
+        if byte[0].is_ascii_whitespace() {
+            if token.is_empty() {
+                continue;
+            }
+            return Ok(token);
+        }
+        token.push(byte[0] as char);
+    }
+}
+
+/// Reads a Portable Float Map. Scanlines are stored bottom to top.
+pub fn read_pfm<R: BufRead>(r: &mut R) -> io::Result<HdrImage> {
+    let channels = match read_token(r)?.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid_data("not a PFM file")),
+    };
+    let parse_err = |_| invalid_data("malformed PFM header");
+    let width: usize = read_token(r)?.parse().map_err(parse_err)?;
+    let height: usize = read_token(r)?.parse().map_err(parse_err)?;
+    let scale: Float = read_token(r)?.parse().map_err(|_| invalid_data("malformed PFM scale"))?;
+    let little_endian = scale < 0.0;
+
+    let mut data = vec![0u8; width * height * channels * 4];
+    r.read_exact(&mut data)?;
+
+    let mut pixels = vec![Vec3::origin(); width * height];
+    for (i, px) in data.chunks_exact(channels * 4).enumerate() {
+        let mut c = [0.0; 3];
+        for (ch, bytes) in px.chunks_exact(4).enumerate() {
+            let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            c[ch] = if little_endian {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            } as Float;
+        }
+        if channels == 1 {
+            c[1] = c[0];
+            c[2] = c[0];
+        }
+
+        let (x, y) = (i % width, height - 1 - i / width);
+        pixels[y * width + x] = Vec3::new(c[0], c[1], c[2]);
+    }
+
+    Ok(HdrImage::new(width, height, pixels))
+}
+
+/// Writes a color Portable Float Map, bottom scanline first.
+pub fn write_pfm<W: Write>(w: &mut W, image: &HdrImage) -> io::Result<()> {
+    write!(w, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+    for y in (0..image.height).rev() {
+        for x in 0..image.width {
+            let c = image.pixel(x, y);
+            for ch in 0..3 {
+                // a no-op with the f32 feature
+                #[allow(clippy::unnecessary_cast)]
+                let v = c[ch] as f32;
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes an 8 bit RGB PNG for display through `display`. The image data is
+/// stored without compression, which keeps the encoder tiny.
+pub fn write_png<W: Write>(w: &mut W, image: &HdrImage, display: &Pipeline) -> io::Result<()> {
+    // each scanline starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity((image.width * 3 + 1) * image.height);
+    for y in 0..image.height {
+        raw.push(0);
+        for x in 0..image.width {
+            raw.extend_from_slice(&display.to_rgb8(*image.pixel(x, y)));
+        }
+    }
+
+    // a zlib stream of stored deflate blocks
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // 8 bits per channel, truecolor, default compression, filtering and no
+    // interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    w.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_png_chunk(w, b"IHDR", &header)?;
+    write_png_chunk(w, b"IDAT", &zlib)?;
+    write_png_chunk(w, b"IEND", &[])
+}
+
+fn write_png_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = !crc32(crc32(!0, kind), data);
+    w.write_all(&crc.to_be_bytes())
+}
+
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Reads a Radiance RGBE (.hdr) file with either flat or new-style run length
+/// encoded scanlines. Only the standard `-Y h +X w` orientation is supported.
+pub fn read_radiance<R: BufRead>(r: &mut R) -> io::Result<HdrImage> {
+    let mut line = String::new();
+    r.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data("not a Radiance HDR file"));
+    }
+
+    // header lines run until the first blank line
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Err(invalid_data("unexpected end of Radiance header"));
+        }
+        let l = line.trim();
+        if l.is_empty() {
+            break;
+        }
+        if let Some(format) = l.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data("unsupported Radiance pixel format"));
+            }
+        }
+    }
+
+    line.clear();
+    r.read_line(&mut line)?;
+    let dims: Vec<&str> = line.split_whitespace().collect();
+    if dims.len() != 4 || dims[0] != "-Y" || dims[2] != "+X" {
+        return Err(invalid_data("unsupported Radiance image orientation"));
+    }
+    let parse_err = |_| invalid_data("malformed Radiance resolution");
+    let height: usize = dims[1].parse().map_err(parse_err)?;
+    let width: usize = dims[3].parse().map_err(parse_err)?;
+
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        read_rgbe_scanline(r, &mut scanline)?;
+        pixels.extend(scanline.iter().map(rgbe_to_vec3));
+    }
+
+    Ok(HdrImage::new(width, height, pixels))
+}
+
+fn read_rgbe_scanline<R: Read>(r: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut head = [0u8; 4];
+    r.read_exact(&mut head)?;
+
+    let is_rle = (8..0x8000).contains(&width) && head[0] == 2 && head[1] == 2 && head[2] & 0x80 == 0;
+    if !is_rle {
+        scanline[0] = head;
+        for px in scanline.iter_mut().skip(1) {
+            r.read_exact(px)?;
+        }
+        return Ok(());
+    }
+
+    if ((head[2] as usize) << 8 | head[3] as usize) != width {
+        return Err(invalid_data("Radiance scanline width mismatch"));
+    }
+
+    // new-style RLE stores each of the four components as its own run
+    for ch in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            r.read_exact(&mut count)?;
+            let count = count[0] as usize;
+            if count > 128 {
+                let run = count - 128;
+                if x + run > width {
+                    return Err(invalid_data("Radiance run overflows scanline"));
+                }
+                let mut value = [0u8; 1];
+                r.read_exact(&mut value)?;
+                for px in &mut scanline[x..x + run] {
+                    px[ch] = value[0];
+                }
+                x += run;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid_data("bad Radiance literal run"));
+                }
+                let mut values = [0u8; 128];
+                r.read_exact(&mut values[..count])?;
+                for (px, v) in scanline[x..x + count].iter_mut().zip(&values[..count]) {
+                    px[ch] = *v;
+                }
+                x += count;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn rgbe_to_vec3(rgbe: &[u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::origin();
+    }
+    let f = (2.0 as Float).powi(rgbe[3] as i32 - 136);
+    Vec3::new(
+        (rgbe[0] as Float + 0.5) * f,
+        (rgbe[1] as Float + 0.5) * f,
+        (rgbe[2] as Float + 0.5) * f,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> HdrImage {
+        let pixels = (0..width * height)
+            .map(|i| Vec3::new(i as Float, 0.5 * i as Float, -0.25))
+            .collect();
+        HdrImage::new(width, height, pixels)
+    }
+
+    #[test]
+    fn pfm_round_trips() {
+        let image = gradient(3, 2);
+        let mut bytes = Vec::new();
+        write_pfm(&mut bytes, &image).unwrap();
+        let back = read_pfm(&mut &bytes[..]).unwrap();
+        assert_eq!((back.width(), back.height()), (3, 2));
+        assert_eq!(back.pixels, image.pixels);
+    }
+
+    #[test]
+    fn pfm_header_is_checked() {
+        assert!(read_pfm(&mut &b"P6\n1 1\n255\n"[..]).is_err());
+        assert!(read_pfm(&mut &b"PF\nx 1\n-1.0\n"[..]).is_err());
+        // one pixel short
+        assert!(read_pfm(&mut &b"PF\n1 1\n-1.0\n\0\0\0\0"[..]).is_err());
+    }
+
+    #[test]
+    fn reads_flat_radiance_scanlines() {
+        let mut bytes = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+        bytes.extend_from_slice(&[128, 64, 0, 129, 0, 0, 0, 0]);
+        let image = read_radiance(&mut &bytes[..]).unwrap();
+        assert_eq!(*image.pixel(0, 0), Vec3::new(128.5 / 128.0, 64.5 / 128.0, 0.5 / 128.0));
+        assert_eq!(*image.pixel(1, 0), Vec3::origin());
+    }
+
+    #[test]
+    fn reads_run_length_encoded_radiance_scanlines() {
+        let width = 8;
+        let mut bytes = format!("#?RADIANCE\n\n-Y 1 +X {}\n", width).into_bytes();
+        bytes.extend_from_slice(&[2, 2, 0, width as u8]);
+        // a run of 8 for red, green and blue, and literals for the exponent
+        for _ in 0..3 {
+            bytes.extend_from_slice(&[128 + 8, 100]);
+        }
+        bytes.push(8);
+        bytes.extend_from_slice(&[128; 8]);
+        let image = read_radiance(&mut &bytes[..]).unwrap();
+        let f = (2.0 as Float).powi(-8);
+        for x in 0..width {
+            assert_eq!(*image.pixel(x, 0), Vec3::new(100.5 * f, 100.5 * f, 100.5 * f));
+        }
+    }
+
+    #[test]
+    fn radiance_runs_past_the_scanline_are_rejected() {
+        let mut bytes = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+        bytes.extend_from_slice(&[2, 2, 0, 8, 128 + 9, 1]);
+        assert!(read_radiance(&mut &bytes[..]).is_err());
+    }
+
+    /// The chunks of a PNG file, checking their CRCs.
+    fn png_chunks(mut png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        png = &png[8..];
+        let mut chunks = Vec::new();
+        while !png.is_empty() {
+            let len = u32::from_be_bytes([png[0], png[1], png[2], png[3]]) as usize;
+            let (kind, data) = (&png[4..8], &png[8..8 + len]);
+            let crc = u32::from_be_bytes([png[8 + len], png[9 + len], png[10 + len], png[11 + len]]);
+            assert_eq!(crc, !crc32(crc32(!0, kind), data));
+            chunks.push(([kind[0], kind[1], kind[2], kind[3]], data.to_vec()));
+            png = &png[12 + len..];
+        }
+        chunks
+    }
+
+    /// The data in a zlib stream of stored blocks, checking its checksum.
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0);
+        let (mut data, mut rest) = (Vec::new(), &zlib[2..]);
+        loop {
+            let last = rest[0] & 1 == 1;
+            assert_eq!(rest[0] >> 1, 0, "only stored blocks");
+            let len = u16::from_le_bytes([rest[1], rest[2]]);
+            assert_eq!(!len, u16::from_le_bytes([rest[3], rest[4]]));
+            data.extend_from_slice(&rest[5..5 + len as usize]);
+            rest = &rest[5 + len as usize..];
+            if last {
+                break;
+            }
+        }
+        assert_eq!(rest, adler32(&data).to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(!crc32(!0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(&[]), 1);
+        // long enough for the sums to need reducing
+        assert_eq!(adler32(&[0xff; 6000]), 0xa497_59ea);
+    }
+
+    #[test]
+    fn writes_valid_pngs() {
+        let display = Pipeline::default();
+        // big enough to need more than one stored block
+        let image = gradient(200, 120);
+        let mut png = Vec::new();
+        write_png(&mut png, &image, &display).unwrap();
+
+        let chunks = png_chunks(&png);
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|c| &c.0).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 200, 0, 0, 0, 120, 8, 2, 0, 0, 0]);
+
+        let raw = inflate_stored(&chunks[1].1);
+        assert_eq!(raw.len(), 120 * (1 + 200 * 3));
+        for (y, line) in raw.chunks(1 + 200 * 3).enumerate() {
+            assert_eq!(line[0], 0);
+            for x in [0, 199] {
+                assert_eq!(line[1 + 3 * x..4 + 3 * x], display.to_rgb8(*image.pixel(x, y)));
+            }
+        }
+    }
+
+    #[test]
+    fn writes_empty_pngs() {
+        let mut png = Vec::new();
+        write_png(&mut png, &gradient(0, 0), &Pipeline::default()).unwrap();
+        let chunks = png_chunks(&png);
+        assert!(inflate_stored(&chunks[1].1).is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::MaterialId;
+use super::ray::gamma;
+use super::{Float, Mat4, Normal, Point3, Ray, Transform, Vec3};
+
+/// An object placed in the world by an affine transform, which rays are
+/// taken into the object's space to hit.
+pub struct Instance {
+    object: Arc<dyn Hit>,
+    to_world: Transform,
+    to_local: Transform,
+}
+
+impl Instance {
+    /// Returns `None` if `to_world` can't be inverted.
+    pub fn new(object: Arc<dyn Hit>, to_world: Mat4) -> Option<Self> {
+        let to_world = Transform::new(to_world)?;
+        Some(Instance {
+            object,
+            to_world,
+            to_local: to_world.inverse(),
+        })
+    }
+
+    pub fn to_world(&self) -> &Mat4 {
+        self.to_world.matrix()
+    }
+}
+
+impl Hit for Instance {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        // the direction isn't renormalized, so t means the same in both spaces
+        let local = Ray::with_values(
+            Point3(self.to_local.transform_point(*r.origin())),
+            self.to_local.transform_vector(r.direction()),
+        );
+        let mut rec = self.object.hit(&local, t_min, t_max)?;
+
+        let to_world = self.to_world.matrix();
+        let linear = to_world.linear();
+        let abs = |v: Vec3| Vec3::new(
+            Vec3::dot(linear.row(0).abs(), v),
+            Vec3::dot(linear.row(1).abs(), v),
+            Vec3::dot(linear.row(2).abs(), v),
+        );
+        let translation = Vec3::new(to_world.m[0][3], to_world.m[1][3], to_world.m[2][3]);
+        rec.error = abs(rec.error) + gamma(3) * (abs(rec.point.abs()) + translation.abs());
+        rec.point = Point3(self.to_world.transform_point(*rec.point));
+
+        // normals keep facing against the ray, as transforming both keeps the
+        // sign of their dot product
+        rec.normal = Normal::new(self.to_world.transform_normal(*rec.normal));
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let b = self.object.bounding_box()?;
+        let (lo, hi) = (*b.min(), *b.max());
+        let mut min = Vec3::new(Float::INFINITY, Float::INFINITY, Float::INFINITY);
+        let mut max = -min;
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { lo.x() } else { hi.x() },
+                if i & 2 == 0 { lo.y() } else { hi.y() },
+                if i & 4 == 0 { lo.z() } else { hi.z() },
+            );
+            let p = self.to_world.transform_point(corner);
+            min = Vec3::min(min, p);
+            max = Vec3::max(max, p);
+        }
+        Some(Aabb::new(min, max))
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        self.object.material_ids()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_materials;
+    use crate::sphere::Sphere;
+
+    fn unit_sphere() -> Arc<dyn Hit> {
+        let (_, mat) = test_materials();
+        Arc::new(Sphere::new(Vec3::origin(), 1.0, mat))
+    }
+
+    #[test]
+    fn hits_the_transformed_object() {
+        let placed = Mat4::translation(Vec3::new(0.0, 0.0, -5.0)) * Mat4::scale(Vec3::new(1.0, 1.0, 2.0));
+        let instance = Instance::new(unit_sphere(), placed).unwrap();
+        let r = Ray::with_values(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = instance.hit(&r, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-5);
+        assert!((rec.point - Vec3::new(0.0, 0.0, -3.0)).len() < 1e-5);
+        assert!((*rec.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-5);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn mirroring_keeps_normals_facing_the_ray() {
+        let mirrored = Mat4::translation(Vec3::new(3.0, 0.0, 0.0)) * Mat4::scale(Vec3::new(-1.0, 1.0, 1.0));
+        let instance = Instance::new(unit_sphere(), mirrored).unwrap();
+        let r = Ray::with_values(Point3::origin(), Vec3::new(1.0, 0.0, 0.0));
+        let near = instance.hit(&r, 0.0, Float::MAX).unwrap();
+        assert!(near.front_face && near.normal.x() < 0.0);
+        let far = instance.hit(&r, near.t, Float::MAX).unwrap();
+        assert!(!far.front_face && far.normal.x() < 0.0);
+    }
+
+    #[test]
+    fn singular_transforms_are_refused() {
+        assert!(Instance::new(unit_sphere(), Mat4::scale(Vec3::new(0.0, 1.0, 1.0))).is_none());
+    }
+}
@@ -1,10 +1,47 @@
-
-mod vec3;
+mod math;
 mod ray;
 pub mod hit;
 pub mod sphere;
 pub mod camera;
+pub mod aabb;
+pub mod shape;
+pub mod sdf;
+pub mod csg;
 pub mod material;
+pub mod background;
+pub mod light;
+pub mod image;
+pub mod render;
+pub mod spectrum;
+pub mod color;
+pub mod film;
+pub mod aov;
+pub mod denoise;
+pub mod random;
+pub mod checkpoint;
+pub mod distributed;
+pub mod instance;
+pub mod animation;
+pub mod preview;
+pub mod bvh;
+pub mod stats;
+pub mod packet;
+pub mod scene;
 
-pub use vec3::Vec3;
+pub use math::{Vec3, Point3, Normal, Color, Mat3, Mat4, Transform, Quat, Onb};
 pub use ray::Ray;
+pub use float::{Float, consts};
+
+/// The floating point type used for all geometry and color math. Building
+/// with the `f32` feature switches everything to single precision.
+#[cfg(not(feature = "f32"))]
+mod float {
+    pub type Float = f64;
+    pub use std::f64::consts;
+}
+
+#[cfg(feature = "f32")]
+mod float {
+    pub type Float = f32;
+    pub use std::f32::consts;
+}
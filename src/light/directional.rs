@@ -0,0 +1,28 @@
+use crate::Vec3;
+use super::{Float, Light, LightSample};
+
+/// A light infinitely far away, like the sun, shining along `dir` with the
+/// same irradiance everywhere.
+pub struct DirectionalLight {
+    to_light: Vec3,
+    irradiance: Vec3,
+}
+
+impl DirectionalLight {
+    pub fn new(dir: Vec3, irradiance: Vec3) -> Self {
+        Self {
+            to_light: -Vec3::unit_vector(dir),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Vec3) -> Option<LightSample> {
+        Some(LightSample {
+            dir: self.to_light,
+            dist: Float::INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+}
@@ -0,0 +1,68 @@
+mod point;
+mod spot;
+mod directional;
+
+use super::{Float, Vec3};
+
+pub use point::PointLight;
+pub use spot::SpotLight;
+pub use directional::DirectionalLight;
+
+/// A light with no area that can only be reached by explicitly sampling it.
+pub trait Light: Send + Sync {
+    /// Light arriving at `point`, or `None` if the light can't reach it.
+    fn sample(&self, point: &Vec3) -> Option<LightSample>;
+}
+
+pub struct LightSample {
+    /// unit direction from the shaded point towards the light
+    pub dir: Vec3,
+    /// distance to the light, infinite for directional lights
+    pub dist: Float,
+    pub radiance: Vec3,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn point_light_falls_off_with_the_square_of_distance() {
+        let light = PointLight::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(8.0, 8.0, 8.0));
+        let s = light.sample(&Vec3::origin()).unwrap();
+        assert_eq!(s.dir, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(s.dist, 2.0);
+        assert_eq!(s.radiance, Vec3::new(2.0, 2.0, 2.0));
+        assert!(light.sample(&Vec3::new(0.0, 2.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn spot_light_fades_between_its_cones() {
+        let light = SpotLight::new(Vec3::new(0.0, 1.0, 0.0), Vec3::origin(), Vec3::new(1.0, 1.0, 1.0), 30.0, 60.0);
+        let at_angle = |degrees: Float| {
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            let point = Vec3::new(sin / cos, 0.0, 0.0);
+            light.sample(&point).map(|s| s.radiance.x() * (point - Vec3::new(0.0, 1.0, 0.0)).sq_len())
+        };
+        assert!(close(at_angle(0.0).unwrap(), 1.0));
+        assert!(close(at_angle(29.0).unwrap(), 1.0));
+        let halfway = at_angle(45.0).unwrap();
+        assert!(halfway > 0.0 && halfway < 1.0);
+        assert!(at_angle(61.0).is_none());
+    }
+
+    #[test]
+    fn directional_light_is_the_same_everywhere() {
+        let light = DirectionalLight::new(Vec3::new(0.0, -2.0, 0.0), Vec3::new(3.0, 3.0, 3.0));
+        for p in &[Vec3::origin(), Vec3::new(100.0, -5.0, 7.0)] {
+            let s = light.sample(p).unwrap();
+            assert_eq!(s.dir, Vec3::new(0.0, 1.0, 0.0));
+            assert_eq!(s.dist, Float::INFINITY);
+            assert_eq!(s.radiance, Vec3::new(3.0, 3.0, 3.0));
+        }
+    }
+}
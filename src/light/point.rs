@@ -0,0 +1,34 @@
+use crate::Vec3;
+use super::{Light, LightSample};
+
+/// Emits `intensity` equally in all directions from a single point.
+pub struct PointLight {
+    pos: Vec3,
+    intensity: Vec3,
+}
+
+impl PointLight {
+    pub fn new(pos: Vec3, intensity: Vec3) -> Self {
+        Self {
+            pos,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let to_light = self.pos - *point;
+        let sq_dist = to_light.sq_len();
+        if sq_dist == 0.0 {
+            return None;
+        }
+
+        let dist = sq_dist.sqrt();
+        Some(LightSample {
+            dir: to_light / dist,
+            dist,
+            radiance: self.intensity / sq_dist,
+        })
+    }
+}
@@ -0,0 +1,68 @@
+use crate::Vec3;
+use super::{Float, Light, LightSample};
+
+/// A point light restricted to a cone. Intensity is full inside
+/// `inner_angle` and fades smoothly to nothing at `outer_angle`.
+pub struct SpotLight {
+    pos: Vec3,
+    dir: Vec3,
+    intensity: Vec3,
+    cos_inner: Float,
+    cos_outer: Float,
+}
+
+impl SpotLight {
+    /// Angles are in degrees measured from the axis of the cone.
+    pub fn new(
+        pos: Vec3,
+        look_at: Vec3,
+        intensity: Vec3,
+        inner_angle: Float,
+        outer_angle: Float,
+    ) -> Self {
+        let outer_angle = outer_angle.max(inner_angle);
+        let dir = Vec3::unit_vector(look_at - pos);
+        Self {
+            pos,
+            dir,
+            intensity,
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: Float) -> Float {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let to_light = self.pos - *point;
+        let sq_dist = to_light.sq_len();
+        if sq_dist == 0.0 {
+            return None;
+        }
+
+        let dist = sq_dist.sqrt();
+        let dir = to_light / dist;
+        let falloff = self.falloff(-Vec3::dot(dir, self.dir));
+        if falloff == 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            dir,
+            dist,
+            radiance: falloff / sq_dist * self.intensity,
+        })
+    }
+}
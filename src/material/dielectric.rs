@@ -1,60 +1,138 @@
-
 use rand::Rng;
 
-use crate::{Vec3, Ray};
+use crate::{Color, Float, Ray, Vec3};
 use crate::hit::HitRecord;
-use super::{Material, reflect};
+use crate::spectrum::LAMBDA_D;
+use super::{Material, Medium, reflect};
+
+/// How a refractive index varies with wavelength.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ior {
+    Constant(Float),
+    /// Cauchy's equation `a + b / λ²`, with λ in micrometres.
+    Cauchy { a: Float, b: Float },
+    /// The Sellmeier equation `n² = 1 + Σ bᵢλ² / (λ² - cᵢ)`, with λ in
+    /// micrometres, as given in glass catalogues.
+    Sellmeier { b: [Float; 3], c: [Float; 3] },
+}
+
+// catalogue coefficients are kept as published, even where f32 can't hold them
+#[allow(clippy::excessive_precision)]
+impl Ior {
+    /// Schott N-BK7, a common crown glass.
+    pub const BK7: Ior = Ior::Sellmeier {
+        b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+        c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+    };
+
+    /// Schott N-SF11, a dense flint glass with strong dispersion.
+    pub const SF11: Ior = Ior::Sellmeier {
+        b: [1.737_596_95, 0.313_747_346, 1.898_781_01],
+        c: [0.013_188_707, 0.062_306_814_2, 155.236_29],
+    };
+
+    /// The index at `wavelength` nm, or at the sodium D line for `None`.
+    pub fn at(&self, wavelength: Option<Float>) -> Float {
+        let um = wavelength.unwrap_or(LAMBDA_D) / 1000.0;
+        let l2 = um * um;
+        match *self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                let sum: Float = b.iter().zip(c.iter()).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+}
+
+impl From<Float> for Ior {
+    fn from(n: Float) -> Self {
+        Ior::Constant(n)
+    }
+}
 
 pub struct Dielectric {
-    ref_index: f64
+    ior: Ior,
+    priority: u32,
 }
 
 impl Dielectric {
-    pub fn new(refraction_index: f64) -> Self {
-        Dielectric {
-            ref_index: refraction_index,
-        }
+    pub fn new(refraction_index: Float) -> Self {
+        Self::with_priority(refraction_index, 0)
+    }
+
+    /// A dielectric that takes precedence over overlapping ones with a lower
+    /// `priority`, whose surfaces are then ignored inside it.
+    pub fn with_priority(refraction_index: Float, priority: u32) -> Self {
+        Self::with_ior(Ior::Constant(refraction_index), priority)
+    }
+
+    /// A dielectric whose index may vary with wavelength, which splits light
+    /// into colors when rendering spectrally.
+    pub fn with_ior(ior: Ior, priority: u32) -> Self {
+        Dielectric { ior, priority }
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vec3)> {
-        let reflected = reflect(&ray_in.direction(), &rec.normal);
-        let attenuation = Vec3::new(1.0, 1.0, 1.0);
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let reflected = reflect(ray_in.direction(), *rec.normal);
+        let attenuation = Color::splat(1.0);
 
-        let (outward_normal, ni_over_nt, cosine) = if Vec3::dot(&ray_in.direction(), &rec.normal) > 0.0 {
-            let cosine = self.ref_index * Vec3::dot(&ray_in.direction(), &rec.normal) / ray_in.direction().len();
-            (-&rec.normal, self.ref_index, cosine)
+        let ref_index = self.ior.at(rec.wavelength);
+        let cosine = -Vec3::dot(ray_in.direction(), *rec.normal) / ray_in.direction().len();
+        let ni_over_nt = if rec.front_face {
+            rec.outside_ior / ref_index
+        } else {
+            ref_index / rec.outside_ior
+        };
+        let cosine = if ni_over_nt > 1.0 {
+            ni_over_nt * cosine
         } else {
-            let cosine = -Vec3::dot(&ray_in.direction(), &rec.normal) / ray_in.direction().len();
-            (rec.normal.clone(), 1.0/self.ref_index, cosine)
+            cosine
         };
 
-        match refract(&ray_in.direction(), &outward_normal, ni_over_nt) {
+        match refract(ray_in.direction(), *rec.normal, ni_over_nt) {
             Some(refracted) => {
-                let reflect_threshold = schlick_approx(cosine, self.ref_index);
-                let mut rng = rand::thread_rng();
-                let reflect_prob: f64 = rng.gen();
+                let reflect_threshold = schlick_approx(cosine, ni_over_nt);
+                let mut rng = crate::random::rng();
+                let reflect_prob: Float = rng.gen();
 
                 if reflect_prob < reflect_threshold {
-                    let s = Ray::with_values(rec.point.clone(), reflected);
+                    let s = rec.spawn_ray(reflected);
                     Some((s, attenuation))
                 } else {
-                    let s = Ray::with_values(rec.point.clone(), refracted);
+                    let s = rec.spawn_ray(refracted);
                     Some((s, attenuation))
                 }
             },
             None => {
-                let s = Ray::with_values(rec.point.clone(), reflected);
+                let s = rec.spawn_ray(reflected);
                 Some((s, attenuation))
             },
         }
     }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        Some(Medium {
+            ior: self.ior,
+            priority: self.priority,
+        })
+    }
 }
 
-fn refract(v: &Vec3, n: &Vec3, ni_over_nt: f64) -> Option<Vec3> {
+fn refract(v: Vec3, n: Vec3, ni_over_nt: Float) -> Option<Vec3> {
     let uv = Vec3::unit_vector(v);
-    let dt = Vec3::dot(&uv, &n);
+    let dt = Vec3::dot(uv, n);
     let discriminant = 1.0 - ni_over_nt * ni_over_nt * (1.0-dt*dt);
     if discriminant > 0.0 {
         let refracted = ni_over_nt * (uv - n*dt) - n*discriminant.sqrt();
@@ -64,8 +142,48 @@ fn refract(v: &Vec3, n: &Vec3, ni_over_nt: f64) -> Option<Vec3> {
     }
 }
 
-fn schlick_approx(cosine: f64, ref_idx: f64) -> f64 {
+fn schlick_approx(cosine: Float, ref_idx: Float) -> Float {
     let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
     r0 = r0 * r0;
     r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_materials;
+    use crate::Point3;
+
+    fn record(r: &Ray, outward: Vec3) -> HitRecord {
+        let (_, mat) = test_materials();
+        HitRecord::new(r, 1.0, Point3::origin(), Vec3::origin(), outward, (0.0, 0.0), mat)
+    }
+
+    #[test]
+    fn refracts_straight_through_at_normal_incidence() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let t = refract(Vec3::new(0.0, -1.0, 0.0), n, 1.0 / 1.5).unwrap();
+        assert!((t - Vec3::new(0.0, -1.0, 0.0)).len() < 1e-6);
+    }
+
+    #[test]
+    fn grazing_rays_leaving_glass_are_totally_reflected() {
+        // leaving glass that fills y < 0, so the hit is on its back face
+        let dir = Vec3::unit_vector(Vec3::new(1.0, 0.2, 0.0));
+        let r = Ray::with_values(Point3::new(-1.0, -0.2, 0.0), dir);
+        let rec = record(&r, Vec3::new(0.0, 1.0, 0.0));
+        assert!(!rec.front_face);
+        for _ in 0..20 {
+            let (out, _) = Dielectric::new(1.5).scatter(&r, &rec).unwrap();
+            assert!(out.direction().y() < 0.0, "escaped the glass");
+        }
+    }
+
+    #[test]
+    fn glass_indices() {
+        assert_eq!(Ior::from(1.5).at(Some(400.0)), 1.5);
+        assert!(!Ior::Constant(1.5).is_dispersive());
+        assert!((Ior::BK7.at(None) - 1.5168).abs() < 1e-3);
+        assert!(Ior::SF11.at(Some(450.0)) > Ior::SF11.at(Some(650.0)));
+    }
+}
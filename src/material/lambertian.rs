@@ -1,16 +1,17 @@
 
+use crate::consts::PI;
 use crate::hit::HitRecord;
 use crate::Ray;
-use crate::Vec3;
+use crate::{Color, Vec3};
 use super::Material;
 use super::random_in_unit_sphere;
 
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo: Color,
 }
 
 impl Lambertian {
-    pub fn new(a: Vec3) -> Self {
+    pub fn new(a: Color) -> Self {
         Self {
             albedo: a
         }
@@ -18,11 +19,19 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vec3)> {
-        let target = &rec.point + &rec.normal + random_in_unit_sphere();
-        let scattered = Ray::with_values(rec.point.clone(), target-rec.point.clone());
+    fn scatter(&self, _ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let target = rec.point + *rec.normal + random_in_unit_sphere();
+        let scattered = rec.spawn_ray(target-rec.point);
 
-        Some((scattered, self.albedo.clone()))
+        Some((scattered, self.albedo))
+    }
+
+    fn eval(&self, _ray_in: &Ray, rec: &HitRecord, dir: &Vec3) -> Option<Color> {
+        let cosine = Vec3::dot(*rec.normal, Vec3::unit_vector(*dir)).max(0.0);
+        Some(cosine / PI * self.albedo)
     }
-}
 
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo
+    }
+}
@@ -1,16 +1,16 @@
 
 use crate::hit::HitRecord;
 use crate::Ray;
-use crate::Vec3;
+use crate::{Color, Float, Vec3};
 use super::{Material, random_in_unit_sphere, reflect};
 
 pub struct Metal {
-    albedo: Vec3,
-    fuzz: f64,
+    albedo: Color,
+    fuzz: Float,
 }
 
 impl Metal {
-    pub fn new(a: Vec3, f: f64) -> Self {
+    pub fn new(a: Color, f: Float) -> Self {
         let f = if f > 1.0 {
             1.0
         } else {
@@ -25,14 +25,22 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vec3)> {
-        let reflected = reflect(&Vec3::unit_vector(ray_in.direction()), &rec.normal);
-        let scattered = Ray::with_values(rec.point.clone(), reflected + self.fuzz * random_in_unit_sphere());
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let reflected = reflect(Vec3::unit_vector(ray_in.direction()), *rec.normal);
+        let scattered = rec.spawn_ray(reflected + self.fuzz * random_in_unit_sphere());
 
-        if Vec3::dot(scattered.direction(), &rec.normal) > 0.0 {
-            Some((scattered, self.albedo.clone()))
+        if Vec3::dot(scattered.direction(), *rec.normal) > 0.0 {
+            Some((scattered, self.albedo))
         } else {
             None
         }
     }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
 }
@@ -2,31 +2,83 @@
 mod lambertian;
 mod metal;
 mod dielectric;
+mod registry;
 
 use rand::Rng;
 
-use super::Ray;
-use super::Vec3;
+use super::{Color, Ray, Vec3};
 use super::hit::HitRecord;
 
 pub use lambertian::Lambertian;
 pub use metal::Metal;
-pub use dielectric::Dielectric;
+pub use dielectric::{Dielectric, Ior};
+pub use registry::{MaterialId, Materials};
+#[cfg(test)]
+pub(crate) use registry::test_materials;
 
 pub trait Material: Send + Sync {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vec3)>;
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)>;
+
+    /// Reflectance times the cosine term for light arriving from `dir`, used
+    /// to gather direct lighting. Materials that only scatter specularly
+    /// return `None` and rely on `scatter` to find light.
+    fn eval(&self, _ray_in: &Ray, _rec: &HitRecord, _dir: &Vec3) -> Option<Color> {
+        None
+    }
+
+    /// The color of the surface under white light, for albedo passes and
+    /// denoising. Surfaces that don't absorb anything are white.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::splat(1.0)
+    }
+
+    /// Whether the material scatters in a sharp lobe, like a mirror, rather
+    /// than diffusely. The renderer limits the two kinds of bounce
+    /// separately.
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    /// The medium filling the inside of objects made of this material, for
+    /// materials that let light through.
+    fn medium(&self) -> Option<Medium> {
+        None
+    }
+}
+
+/// What fills the inside of a transmissive object. Where objects overlap the
+/// medium with the highest priority wins, so the glass of a full drink can
+/// outrank the liquid without their surfaces having to match exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Medium {
+    pub ior: Ior,
+    pub priority: u32,
 }
 
 fn random_in_unit_sphere() -> Vec3 {
-    let mut rng = rand::thread_rng();
+    let mut rng = crate::random::rng();
     loop {
         let v = 2.0 * Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::new(1.0, 1.0, 1.0);
-        if v.sq_len() >= 1.0 {
+        if v.sq_len() < 1.0 {
             return v;
         }
     }
 }
 
-fn reflect(v: &Vec3, normal: &Vec3) -> Vec3 {
+fn reflect(v: Vec3, normal: Vec3) -> Vec3 {
     v - 2.0 * Vec3::dot(v, normal) * normal
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_fall_inside_the_unit_ball() {
+        crate::random::seed_sample(0, 0, 0);
+        let samples: Vec<Vec3> = (0..1000).map(|_| random_in_unit_sphere()).collect();
+        assert!(samples.iter().all(|v| v.sq_len() < 1.0));
+        let mean = samples.iter().fold(Vec3::origin(), |a, v| a + *v) / 1000.0;
+        assert!(mean.len() < 0.1, "{:?}", mean);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::ops::Index;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::Material;
+
+/// Numbers each `Materials` made, so IDs from one aren't taken for another's.
+static REGISTRIES: AtomicUsize = AtomicUsize::new(0);
+
+/// Refers to a material in a `Materials`. Objects hold these rather than the
+/// materials themselves, so hit records are small and copied freely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialId {
+    registry: usize,
+    index: usize,
+}
+
+impl MaterialId {
+    /// Position in the `Materials` it came from, counting from zero in the
+    /// order materials were added.
+    pub fn index(self) -> usize {
+        self.index
+    }
+}
+
+/// Every material in a scene, looked up by `MaterialId` or by name.
+/// Replacing a material changes it for every object that refers to it.
+/// Clones take the same IDs, but any other `Materials` has none of them.
+#[derive(Clone)]
+pub struct Materials {
+    registry: usize,
+    materials: Vec<Arc<dyn Material>>,
+    names: HashMap<String, MaterialId>,
+}
+
+impl Default for Materials {
+    fn default() -> Self {
+        Materials {
+            registry: REGISTRIES.fetch_add(1, Ordering::Relaxed),
+            materials: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+}
+
+impl Materials {
+    pub fn new() -> Self {
+        Materials::default()
+    }
+
+    pub fn add(&mut self, material: Arc<dyn Material>) -> MaterialId {
+        self.materials.push(material);
+        MaterialId {
+            registry: self.registry,
+            index: self.materials.len() - 1,
+        }
+    }
+
+    /// Adds a material called `name`, or replaces the one already called
+    /// that and returns its ID.
+    pub fn insert(&mut self, name: &str, material: Arc<dyn Material>) -> MaterialId {
+        match self.names.get(name) {
+            Some(&id) => {
+                self.set(id, material);
+                id
+            }
+            None => {
+                let id = self.add(material);
+                self.names.insert(String::from(name), id);
+                id
+            }
+        }
+    }
+
+    /// Replaces a material. Panics if `id` isn't from this `Materials`.
+    pub fn set(&mut self, id: MaterialId, material: Arc<dyn Material>) {
+        assert!(self.contains(id), "material {:?} isn't from this registry", id);
+        self.materials[id.index] = material;
+    }
+
+    /// Whether `id` refers to one of this registry's materials.
+    pub fn contains(&self, id: MaterialId) -> bool {
+        id.registry == self.registry && id.index < self.materials.len()
+    }
+
+    pub fn id(&self, name: &str) -> Option<MaterialId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn get(&self, id: MaterialId) -> Option<&Arc<dyn Material>> {
+        self.materials.get(id.index).filter(|_| id.registry == self.registry)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+}
+
+impl Index<MaterialId> for Materials {
+    type Output = dyn Material;
+
+    /// Panics if `id` isn't from this registry, which `Renderer::new`
+    /// checks for up front.
+    fn index(&self, id: MaterialId) -> &(dyn Material + 'static) {
+        match self.get(id) {
+            Some(material) => material.as_ref(),
+            None => panic!("material {:?} isn't from this registry", id),
+        }
+    }
+}
+
+/// A registry holding one gray diffuse material, and its id, for tests that
+/// need a material but not any particular one.
+#[cfg(test)]
+pub(crate) fn test_materials() -> (Materials, MaterialId) {
+    let mut materials = Materials::new();
+    let gray = materials.add(Arc::new(super::Lambertian::new(crate::Color::new(0.5, 0.5, 0.5))));
+    (materials, gray)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::Color;
+
+    fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn ids_are_only_found_in_their_own_registry() {
+        let (a, in_a) = test_materials();
+        let (b, in_b) = test_materials();
+        assert_eq!(in_a.index(), in_b.index());
+        assert!(a.contains(in_a) && b.contains(in_b));
+        assert!(!a.contains(in_b) && !b.contains(in_a));
+        assert!(a.get(in_b).is_none());
+        assert!(b.get(in_a).is_none());
+    }
+
+    #[test]
+    fn clones_share_ids() {
+        let (a, id) = test_materials();
+        let b = a.clone();
+        assert!(b.contains(id));
+        assert!(b.get(id).is_some());
+    }
+
+    #[test]
+    fn insert_replaces_materials_by_name() {
+        let mut materials = Materials::new();
+        let first = materials.insert("floor", gray());
+        let second = materials.insert("floor", gray());
+        assert_eq!(first, second);
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials.id("floor"), Some(first));
+        assert_eq!(materials.id("wall"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't from this registry")]
+    fn indexing_with_a_foreign_id_panics() {
+        let (_, id) = test_materials();
+        let _ = &Materials::new()[id];
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't from this registry")]
+    fn setting_a_foreign_id_panics() {
+        let (_, id) = test_materials();
+        let (mut materials, _) = test_materials();
+        materials.set(id, gray());
+    }
+}
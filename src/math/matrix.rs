@@ -0,0 +1,432 @@
+use std::ops::Mul;
+
+use crate::Float;
+use super::Vec3;
+
+/// A row-major 3x3 matrix, mostly used to transform normals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub m: [[Float; 3]; 3],
+}
+
+impl Mat3 {
+    pub const fn new(m: [[Float; 3]; 3]) -> Self {
+        Mat3 { m }
+    }
+
+    pub const fn identity() -> Self {
+        Mat3::new([
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn from_rows(r0: Vec3, r1: Vec3, r2: Vec3) -> Self {
+        Mat3::new([
+            [r0.x(), r0.y(), r0.z()],
+            [r1.x(), r1.y(), r1.z()],
+            [r2.x(), r2.y(), r2.z()],
+        ])
+    }
+
+    pub fn from_cols(c0: Vec3, c1: Vec3, c2: Vec3) -> Self {
+        Mat3::from_rows(c0, c1, c2).transpose()
+    }
+
+    /// Rotation of `degrees` counter-clockwise around `axis`.
+    pub fn rotation(axis: Vec3, degrees: Float) -> Self {
+        let a = Vec3::unit_vector(axis);
+        let (s, c) = degrees.to_radians().sin_cos();
+        let t = 1.0 - c;
+        let (x, y, z) = (a.x(), a.y(), a.z());
+
+        Mat3::new([
+            [t * x * x + c, t * x * y - s * z, t * x * z + s * y],
+            [t * x * y + s * z, t * y * y + c, t * y * z - s * x],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c],
+        ])
+    }
+
+    pub fn row(&self, i: usize) -> Vec3 {
+        Vec3::new(self.m[i][0], self.m[i][1], self.m[i][2])
+    }
+
+    pub fn col(&self, j: usize) -> Vec3 {
+        Vec3::new(self.m[0][j], self.m[1][j], self.m[2][j])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Mat3::identity();
+        for (i, row) in self.m.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                t.m[j][i] = *v;
+            }
+        }
+        t
+    }
+
+    pub fn determinant(&self) -> Float {
+        Vec3::dot(self.row(0), Vec3::cross(self.row(1), self.row(2)))
+    }
+
+    /// The inverse, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 {
+            return None;
+        }
+
+        // the columns of the inverse are the cross products of pairs of rows
+        let (r0, r1, r2) = (self.row(0), self.row(1), self.row(2));
+        let inv = Mat3::from_cols(Vec3::cross(r1, r2), Vec3::cross(r2, r0), Vec3::cross(r0, r1));
+        Some(inv * (1.0 / det))
+    }
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Mat3::identity()
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, o: Mat3) -> Mat3 {
+        let mut r = Mat3::new([[0.0; 3]; 3]);
+        for i in 0..3 {
+            for j in 0..3 {
+                r.m[i][j] = Vec3::dot(self.row(i), o.col(j));
+            }
+        }
+        r
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(self.row(0), v), Vec3::dot(self.row(1), v), Vec3::dot(self.row(2), v))
+    }
+}
+
+impl Mul<Float> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, t: Float) -> Mat3 {
+        let mut r = self;
+        for v in r.m.iter_mut().flatten() {
+            *v *= t;
+        }
+        r
+    }
+}
+
+/// A row-major 4x4 affine or projective transform acting on column vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[Float; 4]; 4],
+}
+
+impl Mat4 {
+    pub const fn new(m: [[Float; 4]; 4]) -> Self {
+        Mat4 { m }
+    }
+
+    pub const fn identity() -> Self {
+        Mat4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// An affine transform with `linear` as its upper 3x3 and a translation.
+    pub fn from_parts(linear: &Mat3, translation: Vec3) -> Self {
+        let mut r = Mat4::identity();
+        for i in 0..3 {
+            r.m[i][..3].copy_from_slice(&linear.m[i]);
+            r.m[i][3] = translation[i];
+        }
+        r
+    }
+
+    pub fn translation(t: Vec3) -> Self {
+        Mat4::from_parts(&Mat3::identity(), t)
+    }
+
+    pub fn scale(s: Vec3) -> Self {
+        let mut r = Mat4::identity();
+        for i in 0..3 {
+            r.m[i][i] = s[i];
+        }
+        r
+    }
+
+    /// Rotation of `degrees` counter-clockwise around `axis`.
+    pub fn rotation(axis: Vec3, degrees: Float) -> Self {
+        Mat4::from_parts(&Mat3::rotation(axis, degrees), Vec3::origin())
+    }
+
+    /// World to camera transform for a camera at `eye` looking at `target`.
+    /// The camera looks down its -z axis with +y up, as in OpenGL.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let w = Vec3::unit_vector(eye - target);
+        let u = Vec3::unit_vector(Vec3::cross(up, w));
+        let v = Vec3::cross(w, u);
+        let rot = Mat3::from_rows(u, v, w);
+        Mat4::from_parts(&rot, -(rot * eye))
+    }
+
+    /// Projection from camera space (looking down -z) to normalized device
+    /// coordinates, where the visible frustum between `near` and `far` maps
+    /// to [-1, 1] on every axis after `transform_point`'s divide.
+    pub fn perspective(v_fov: Float, aspect: Float, near: Float, far: Float) -> Self {
+        let f = 1.0 / (v_fov.to_radians() / 2.0).tan();
+        let depth = near - far;
+        Mat4::new([
+            [f / aspect, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [0.0, 0.0, (far + near) / depth, 2.0 * far * near / depth],
+            [0.0, 0.0, -1.0, 0.0],
+        ])
+    }
+
+    /// The upper left 3x3 block, i.e. the transform without translation.
+    pub fn linear(&self) -> Mat3 {
+        let mut r = Mat3::identity();
+        for i in 0..3 {
+            r.m[i].copy_from_slice(&self.m[i][..3]);
+        }
+        r
+    }
+
+    /// The matrix that transforms normals: the inverse transpose of the
+    /// linear part. Falls back to the identity for singular transforms.
+    pub fn normal_matrix(&self) -> Mat3 {
+        self.linear().inverse().map(|m| m.transpose()).unwrap_or_default()
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Mat4::identity();
+        for (i, row) in self.m.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                t.m[j][i] = *v;
+            }
+        }
+        t
+    }
+
+    /// The inverse, or `None` if the matrix is singular. Uses Gauss-Jordan
+    /// elimination with partial pivoting.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap_or(col);
+            if a[pivot][col] == 0.0 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let p = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= p;
+                inv[col][j] *= p;
+            }
+
+            for row in 0..4 {
+                let f = a[row][col];
+                if row == col || f == 0.0 {
+                    continue;
+                }
+                for j in 0..4 {
+                    a[row][j] -= f * a[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+
+        Some(Mat4::new(inv))
+    }
+
+    /// Transforms a point, dividing through by w for projective transforms.
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let row = |i: usize| {
+            let r = &self.m[i];
+            r[0] * p.x() + r[1] * p.y() + r[2] * p.z() + r[3]
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        if w == 1.0 {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x, y, z) / w
+        }
+    }
+
+    /// Transforms a direction, ignoring translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.linear() * v
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+
+/// An invertible transform with its inverse and normal matrix worked out
+/// once, for applying it to many points, vectors and normals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    m: Mat4,
+    inv: Mat4,
+    normal: Mat3,
+}
+
+impl Transform {
+    /// Returns `None` if `m` can't be inverted.
+    pub fn new(m: Mat4) -> Option<Self> {
+        Some(Transform {
+            m,
+            inv: m.inverse()?,
+            normal: m.normal_matrix(),
+        })
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.m
+    }
+
+    /// The transform undoing this one.
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            m: self.inv,
+            inv: self.m,
+            normal: self.inv.normal_matrix(),
+        }
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        self.m.transform_point(p)
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.m.transform_vector(v)
+    }
+
+    /// Transforms a normal by the inverse transpose. The result isn't
+    /// renormalized.
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        self.normal * n
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, o: Mat4) -> Mat4 {
+        let mut r = Mat4::new([[0.0; 4]; 4]);
+        for i in 0..4 {
+            for j in 0..4 {
+                r.m[i][j] = (0..4).map(|k| self.m[i][k] * o.m[k][j]).sum();
+            }
+        }
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: Float, b: Float) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    fn assert_identity4(m: &Mat4) {
+        for (i, row) in m.m.iter().enumerate() {
+            for (j, &v) in row.iter().enumerate() {
+                assert!(near(v, if i == j { 1.0 } else { 0.0 }), "{:?}", m);
+            }
+        }
+    }
+
+    fn affine() -> Mat4 {
+        Mat4::translation(Vec3::new(1.0, -2.0, 3.0))
+            * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0)
+            * Mat4::scale(Vec3::new(2.0, 0.5, 3.0))
+    }
+
+    #[test]
+    fn mat3_inverse_undoes_the_matrix() {
+        let skew = Mat3::new([[2.0, 0.0, 0.0], [0.0, 3.0, 1.0], [0.0, 0.0, 4.0]]);
+        let m = Mat3::rotation(Vec3::new(0.0, 1.0, 1.0), 40.0) * skew;
+        let inv = m.inverse().unwrap();
+        for p in [inv * m, m * inv].iter() {
+            for i in 0..3 {
+                for j in 0..3 {
+                    assert!(near(p.m[i][j], if i == j { 1.0 } else { 0.0 }), "{:?}", p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mat4_inverse_undoes_the_matrix() {
+        let m = affine();
+        let inv = m.inverse().unwrap();
+        assert_identity4(&(inv * m));
+        assert_identity4(&(m * inv));
+
+        // projections are inverted too, which needs the pivoting
+        let p = Mat4::perspective(60.0, 1.5, 0.1, 100.0);
+        assert_identity4(&(p.inverse().unwrap() * p));
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert_eq!(Mat3::new([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 0.0, 1.0]]).inverse(), None);
+        assert_eq!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        assert!(Transform::new(Mat4::scale(Vec3::new(1.0, 1.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn perspective_maps_the_near_and_far_planes() {
+        let p = Mat4::perspective(90.0, 1.0, 1.0, 10.0);
+        assert!(near(p.transform_point(Vec3::new(0.0, 0.0, -1.0)).z(), -1.0));
+        assert!(near(p.transform_point(Vec3::new(0.0, 0.0, -10.0)).z(), 1.0));
+        assert!(near(p.transform_point(Vec3::new(1.0, 1.0, -1.0)).x(), 1.0));
+    }
+
+    #[test]
+    fn transformed_normals_stay_perpendicular() {
+        let t = Transform::new(affine()).unwrap();
+        let (a, b) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 1.0));
+        let n = Vec3::cross(a, b);
+        let (ta, tb) = (t.transform_vector(a), t.transform_vector(b));
+        let tn = t.transform_normal(n);
+        assert!(near(Vec3::dot(tn, ta), 0.0));
+        assert!(near(Vec3::dot(tn, tb), 0.0));
+        assert_eq!(tn, affine().normal_matrix() * n);
+    }
+
+    #[test]
+    fn transform_inverse_round_trips() {
+        let t = Transform::new(affine()).unwrap();
+        let p = Vec3::new(0.3, -1.2, 4.0);
+        let back = t.inverse().transform_point(t.transform_point(p));
+        assert!(near(back.x(), p.x()) && near(back.y(), p.y()) && near(back.z(), p.z()));
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let round = t.inverse().transform_normal(t.transform_normal(n));
+        assert!(near(round.x(), 0.0) && near(round.y(), 0.0) && near(round.z(), 1.0));
+    }
+}
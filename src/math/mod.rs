@@ -0,0 +1,9 @@
+mod vec3;
+mod matrix;
+mod quat;
+mod onb;
+
+pub use vec3::{Vec3, Point3, Normal, Color};
+pub use matrix::{Mat3, Mat4, Transform};
+pub use quat::Quat;
+pub use onb::Onb;
@@ -0,0 +1,81 @@
+use super::Vec3;
+
+/// An orthonormal basis, used as a local frame around a normal or for a
+/// camera's axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// Some frame with `w` along `n`, which doesn't have to be unit length.
+    pub fn from_w(n: Vec3) -> Self {
+        // Duff et al., "Building an Orthonormal Basis, Revisited"
+        let w = Vec3::unit_vector(n);
+        let sign = if w.z() >= 0.0 { 1.0 } else { -1.0 };
+        let a = -1.0 / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        let u = Vec3::new(1.0 + sign * w.x() * w.x() * a, sign * b, -sign * w.x());
+        let v = Vec3::new(b, sign + w.y() * w.y() * a, -w.y());
+        Onb { u, v, w }
+    }
+
+    /// The frame with `w` along `n` and `v` as close to `up` as possible.
+    pub fn from_w_up(n: Vec3, up: Vec3) -> Self {
+        let w = Vec3::unit_vector(n);
+        let u = Vec3::unit_vector(Vec3::cross(up, w));
+        let v = Vec3::cross(w, u);
+        Onb { u, v, w }
+    }
+
+    /// Converts coordinates in this frame to world space.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    /// Converts a world space vector to coordinates in this frame.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(a, self.u), Vec3::dot(a, self.v), Vec3::dot(a, self.w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_orthonormal(b: &Onb) {
+        for v in [b.u, b.v, b.w].iter() {
+            assert!((v.len() - 1.0).abs() < 1e-5);
+        }
+        assert!(Vec3::dot(b.u, b.v).abs() < 1e-5);
+        assert!(Vec3::dot(b.v, b.w).abs() < 1e-5);
+        assert!(Vec3::dot(b.w, b.u).abs() < 1e-5);
+        // right handed
+        assert!((Vec3::cross(b.u, b.v) - b.w).len() < 1e-5);
+    }
+
+    #[test]
+    fn frames_are_orthonormal() {
+        let normals = [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(-0.2, 0.9, -0.1),
+        ];
+        for n in normals.iter() {
+            let b = Onb::from_w(*n);
+            assert_orthonormal(&b);
+            assert!((b.w - Vec3::unit_vector(*n)).len() < 1e-5);
+        }
+        assert_orthonormal(&Onb::from_w_up(Vec3::new(0.0, -1.0, 1.0), Vec3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn local_and_to_local_are_inverses() {
+        let b = Onb::from_w(Vec3::new(1.0, -1.0, 0.5));
+        let a = Vec3::new(0.3, 0.4, 0.5);
+        assert!((b.to_local(b.local(a)) - a).len() < 1e-5);
+    }
+}
@@ -0,0 +1,172 @@
+use std::ops::{Mul, Neg};
+
+use crate::Float;
+use super::{Mat3, Mat4, Vec3};
+
+/// A quaternion `w + xi + yj + zk`. Unit quaternions represent rotations and
+/// can be smoothly interpolated with `slerp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: Float,
+    pub v: Vec3,
+}
+
+impl Quat {
+    pub const fn new(w: Float, x: Float, y: Float, z: Float) -> Self {
+        Quat {
+            w,
+            v: Vec3::new(x, y, z),
+        }
+    }
+
+    pub const fn identity() -> Self {
+        Quat::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Rotation of `degrees` counter-clockwise around `axis`.
+    pub fn from_axis_angle(axis: Vec3, degrees: Float) -> Self {
+        let (s, c) = (degrees.to_radians() / 2.0).sin_cos();
+        Quat {
+            w: c,
+            v: Vec3::unit_vector(axis) * s,
+        }
+    }
+
+    pub fn dot(l: Self, r: Self) -> Float {
+        l.w * r.w + Vec3::dot(l.v, r.v)
+    }
+
+    pub fn len(&self) -> Float {
+        Quat::dot(*self, *self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let inv = 1.0 / self.len();
+        Quat {
+            w: self.w * inv,
+            v: self.v * inv,
+        }
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Quat {
+            w: self.w,
+            v: -self.v,
+        }
+    }
+
+    /// Rotates `v` by this unit quaternion.
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        let t = 2.0 * Vec3::cross(self.v, v);
+        v + self.w * t + Vec3::cross(self.v, t)
+    }
+
+    /// Spherical linear interpolation between unit quaternions, taking the
+    /// shorter way around.
+    pub fn slerp(a: Self, b: Self, t: Float) -> Self {
+        let mut cos_theta = Quat::dot(a, b);
+        let b = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            -b
+        } else {
+            b
+        };
+
+        // nearly parallel, where the sine below would blow up
+        if cos_theta > 0.9995 {
+            return Quat {
+                w: a.w + t * (b.w - a.w),
+                v: a.v + t * (b.v - a.v),
+            }.normalize();
+        }
+
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        let wa = ((1.0 - t) * theta).sin() / sin_theta;
+        let wb = (t * theta).sin() / sin_theta;
+        Quat {
+            w: wa * a.w + wb * b.w,
+            v: wa * a.v + wb * b.v,
+        }
+    }
+
+    pub fn to_mat3(&self) -> Mat3 {
+        let (w, x, y, z) = (self.w, self.v.x(), self.v.y(), self.v.z());
+        Mat3::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)],
+        ])
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_parts(&self.to_mat3(), Vec3::origin())
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::identity()
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    /// The rotation `o` followed by `self`.
+    fn mul(self, o: Quat) -> Quat {
+        Quat {
+            w: self.w * o.w - Vec3::dot(self.v, o.v),
+            v: self.w * o.v + o.w * self.v + Vec3::cross(self.v, o.v),
+        }
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+
+    fn neg(self) -> Quat {
+        Quat {
+            w: -self.w,
+            v: -self.v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).len() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn rotates_like_the_matrix() {
+        let axis = Vec3::new(1.0, 2.0, -1.0);
+        let q = Quat::from_axis_angle(axis, 70.0);
+        let v = Vec3::new(0.5, -1.0, 2.0);
+        assert_near(q.rotate(v), Mat3::rotation(axis, 70.0) * v);
+        assert_near(q.to_mat3() * v, q.rotate(v));
+        assert_near(q.conjugate().rotate(q.rotate(v)), v);
+    }
+
+    #[test]
+    fn products_compose_rotations() {
+        let a = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 90.0);
+        let b = Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), 90.0);
+        let v = Vec3::new(0.0, 0.0, 1.0);
+        assert_near((a * b).rotate(v), a.rotate(b.rotate(v)));
+    }
+
+    #[test]
+    fn slerp_goes_halfway_the_short_way() {
+        let a = Quat::identity();
+        let b = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let half = Quat::slerp(a, b, 0.5);
+        assert_near(half.rotate(Vec3::new(1.0, 0.0, 0.0)), Vec3::unit_vector(Vec3::new(1.0, 1.0, 0.0)));
+        // -b is the same rotation, so it must give the same answer
+        assert_near(Quat::slerp(a, -b, 0.5).rotate(Vec3::new(1.0, 0.0, 0.0)), half.rotate(Vec3::new(1.0, 0.0, 0.0)));
+        assert!((Quat::slerp(a, b, 0.3).len() - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,488 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, Sub, Mul, Div, Index, Neg, Deref,
+    AddAssign, SubAssign, MulAssign, DivAssign};
+
+use crate::Float;
+
+/// A three component vector.
+///
+/// The components are stored in four lanes with the last one always zero and
+/// the struct aligned to the size of all four, so vectors load and store as
+/// whole SIMD registers. Component-wise arithmetic only works on the three
+/// real lanes, as computing the padding lane too measured slower.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "f32", repr(C, align(16)))]
+#[cfg_attr(not(feature = "f32"), repr(C, align(32)))]
+pub struct Vec3 {
+    e: [Float; 4]
+}
+
+impl Vec3 {
+    pub const fn origin() -> Self {
+        Vec3{
+            e: [0.0, 0.0, 0.0, 0.0]
+        }
+    }
+
+    pub const fn new(e0: Float, e1: Float, e2: Float) -> Self {
+        Vec3{
+            e: [e0, e1, e2, 0.0],
+        }
+    }
+
+    pub const fn splat(v: Float) -> Self {
+        Vec3::new(v, v, v)
+    }
+
+    pub fn x(&self) -> Float {
+        self.e[0]
+    }
+    pub fn y(&self) -> Float {
+        self.e[1]
+    }
+    pub fn z(&self) -> Float {
+        self.e[2]
+    }
+
+    pub fn r(&self) -> Float {
+        self.e[0]
+    }
+    pub fn g(&self) -> Float {
+        self.e[1]
+    }
+
+    pub fn b(&self) -> Float {
+        self.e[2]
+    }
+
+    #[inline]
+    pub fn len(&self) -> Float {
+        self.sq_len().sqrt()
+    }
+
+    #[inline]
+    pub fn sq_len(&self) -> Float {
+        Vec3::dot(*self, *self)
+    }
+
+    pub fn make_unit_vector(&mut self) {
+        *self = Vec3::unit_vector(*self);
+    }
+
+    #[inline]
+    pub fn dot(l: Self, r: Self) -> Float {
+        let p = l * r;
+        p.e[0] + p.e[1] + p.e[2]
+    }
+
+    #[inline]
+    pub fn cross(l: Self, r: Self) -> Self {
+        Vec3::new(
+            l.e[1] * r.e[2] - l.e[2] * r.e[1],
+            -(l.e[0] * r.e[2] - l.e[2] * r.e[0]),
+            l.e[0] * r.e[1] - l.e[1] * r.e[0]
+        )
+    }
+
+    #[inline]
+    pub fn unit_vector(v: Self) -> Self {
+        v / v.len()
+    }
+
+    /// Component-wise absolute value.
+    pub fn abs(&self) -> Self {
+        Vec3::new(self.e[0].abs(), self.e[1].abs(), self.e[2].abs())
+    }
+
+    /// Component-wise minimum.
+    pub fn min(l: Self, r: Self) -> Self {
+        Vec3::new(l.e[0].min(r.e[0]), l.e[1].min(r.e[1]), l.e[2].min(r.e[2]))
+    }
+
+    /// Component-wise maximum.
+    pub fn max(l: Self, r: Self) -> Self {
+        Vec3::new(l.e[0].max(r.e[0]), l.e[1].max(r.e[1]), l.e[2].max(r.e[2]))
+    }
+
+    #[inline]
+    fn lanes(self, o: Self, f: impl Fn(Float, Float) -> Float) -> Self {
+        Vec3::new(f(self.e[0], o.e[0]), f(self.e[1], o.e[1]), f(self.e[2], o.e[2]))
+    }
+}
+
+macro_rules! lane_ops {
+    ($Op:ident, $op:ident, $OpAssign:ident, $op_assign:ident, $sym:tt) => {
+        impl $Op for Vec3 {
+            type Output = Vec3;
+
+            #[inline]
+            fn $op(self, o: Vec3) -> Vec3 {
+                self.lanes(o, |l, r| l $sym r)
+            }
+        }
+
+        impl $Op<Float> for Vec3 {
+            type Output = Vec3;
+
+            #[inline]
+            fn $op(self, t: Float) -> Vec3 {
+                self.lanes(Vec3 { e: [t; 4] }, |l, r| l $sym r)
+            }
+        }
+
+        impl $OpAssign for Vec3 {
+            #[inline]
+            fn $op_assign(&mut self, o: Vec3) {
+                *self = *self $sym o;
+            }
+        }
+
+        impl $OpAssign<Float> for Vec3 {
+            #[inline]
+            fn $op_assign(&mut self, t: Float) {
+                *self = *self $sym t;
+            }
+        }
+    };
+}
+
+lane_ops!(Add, add, AddAssign, add_assign, +);
+lane_ops!(Sub, sub, SubAssign, sub_assign, -);
+lane_ops!(Mul, mul, MulAssign, mul_assign, *);
+
+impl Div for Vec3 {
+    type Output = Vec3;
+
+    #[inline]
+    fn div(self, o: Vec3) -> Vec3 {
+        self.lanes(o, |l, r| l / r)
+    }
+}
+
+impl Div<Float> for Vec3 {
+    type Output = Vec3;
+
+    #[inline]
+    fn div(self, t: Float) -> Vec3 {
+        self * (1.0 / t)
+    }
+}
+
+impl DivAssign for Vec3 {
+    #[inline]
+    fn div_assign(&mut self, o: Vec3) {
+        *self = *self / o;
+    }
+}
+
+impl DivAssign<Float> for Vec3 {
+    #[inline]
+    fn div_assign(&mut self, t: Float) {
+        *self = *self / t;
+    }
+}
+
+impl Mul<Vec3> for Float {
+    type Output = Vec3;
+
+    #[inline]
+    fn mul(self, o: Vec3) -> Vec3 {
+        o * self
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    #[inline]
+    fn neg(self) -> Vec3 {
+        self * -1.0
+    }
+}
+
+impl Index<usize> for Vec3 {
+    type Output = Float;
+
+    fn index(&self, i: usize) -> &Float {
+        // the fourth lane is padding, not a component
+        assert!(i < 3, "Vec3 index {} out of bounds", i);
+        &self.e[i]
+    }
+}
+
+impl Sum for Vec3 {
+    fn sum<I: Iterator<Item = Vec3>>(iter: I) -> Vec3 {
+        iter.fold(Vec3::origin(), |acc, v| acc + v)
+    }
+}
+
+impl fmt::Display for Vec3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.e[0], self.e[1], self.e[2])
+    }
+}
+
+/// A position in space. Subtracting two points gives the `Vec3` between them
+/// and points can be moved by a `Vec3`, but not added together.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point3(pub Vec3);
+
+impl Point3 {
+    pub const fn new(x: Float, y: Float, z: Float) -> Self {
+        Point3(Vec3::new(x, y, z))
+    }
+
+    pub const fn origin() -> Self {
+        Point3(Vec3::origin())
+    }
+}
+
+impl Deref for Point3 {
+    type Target = Vec3;
+
+    fn deref(&self) -> &Vec3 {
+        &self.0
+    }
+}
+
+impl From<Vec3> for Point3 {
+    fn from(v: Vec3) -> Self {
+        Point3(v)
+    }
+}
+
+impl From<Point3> for Vec3 {
+    fn from(p: Point3) -> Self {
+        p.0
+    }
+}
+
+impl Add<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn add(self, o: Vec3) -> Point3 {
+        Point3(self.0 + o)
+    }
+}
+
+impl Sub<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn sub(self, o: Vec3) -> Point3 {
+        Point3(self.0 - o)
+    }
+}
+
+impl Sub for Point3 {
+    type Output = Vec3;
+
+    fn sub(self, o: Point3) -> Vec3 {
+        self.0 - o.0
+    }
+}
+
+/// A unit length surface normal.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Normal(Vec3);
+
+impl Normal {
+    /// Normalizes `v`, which must not be zero.
+    pub fn new(v: Vec3) -> Self {
+        Normal(Vec3::unit_vector(v))
+    }
+
+    /// Takes `v` as it is, for vectors already of unit length, so hits
+    /// don't pay for a second square root.
+    pub const fn from_unit(v: Vec3) -> Self {
+        Normal(v)
+    }
+}
+
+impl Deref for Normal {
+    type Target = Vec3;
+
+    fn deref(&self) -> &Vec3 {
+        &self.0
+    }
+}
+
+impl From<Normal> for Vec3 {
+    fn from(n: Normal) -> Self {
+        n.0
+    }
+}
+
+impl Neg for Normal {
+    type Output = Normal;
+
+    fn neg(self) -> Normal {
+        Normal(-self.0)
+    }
+}
+
+impl Mul<Float> for Normal {
+    type Output = Vec3;
+
+    #[inline]
+    fn mul(self, t: Float) -> Vec3 {
+        self.0 * t
+    }
+}
+
+impl Mul<Normal> for Float {
+    type Output = Vec3;
+
+    #[inline]
+    fn mul(self, n: Normal) -> Vec3 {
+        n.0 * self
+    }
+}
+
+/// Linear RGB radiance or reflectance.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Color(pub Vec3);
+
+impl Color {
+    pub const fn new(r: Float, g: Float, b: Float) -> Self {
+        Color(Vec3::new(r, g, b))
+    }
+
+    pub const fn black() -> Self {
+        Color(Vec3::origin())
+    }
+
+    pub const fn splat(v: Float) -> Self {
+        Color(Vec3::splat(v))
+    }
+
+    /// Relative luminance with Rec. 709 primaries.
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+}
+
+impl Deref for Color {
+    type Target = Vec3;
+
+    fn deref(&self) -> &Vec3 {
+        &self.0
+    }
+}
+
+impl From<Vec3> for Color {
+    fn from(v: Vec3) -> Self {
+        Color(v)
+    }
+}
+
+impl From<Color> for Vec3 {
+    fn from(c: Color) -> Self {
+        c.0
+    }
+}
+
+macro_rules! color_ops {
+    ($Op:ident, $op:ident, $OpAssign:ident, $op_assign:ident) => {
+        impl $Op for Color {
+            type Output = Color;
+
+            #[inline]
+            fn $op(self, o: Color) -> Color {
+                Color($Op::$op(self.0, o.0))
+            }
+        }
+
+        impl $Op<Float> for Color {
+            type Output = Color;
+
+            #[inline]
+            fn $op(self, t: Float) -> Color {
+                Color($Op::$op(self.0, t))
+            }
+        }
+
+        impl $OpAssign for Color {
+            #[inline]
+            fn $op_assign(&mut self, o: Color) {
+                $OpAssign::$op_assign(&mut self.0, o.0);
+            }
+        }
+
+        impl $OpAssign<Float> for Color {
+            #[inline]
+            fn $op_assign(&mut self, t: Float) {
+                $OpAssign::$op_assign(&mut self.0, t);
+            }
+        }
+    };
+}
+
+color_ops!(Add, add, AddAssign, add_assign);
+color_ops!(Sub, sub, SubAssign, sub_assign);
+color_ops!(Mul, mul, MulAssign, mul_assign);
+color_ops!(Div, div, DivAssign, div_assign);
+
+impl Mul<Color> for Float {
+    type Output = Color;
+
+    #[inline]
+    fn mul(self, c: Color) -> Color {
+        c * self
+    }
+}
+
+impl Sum for Color {
+    fn sum<I: Iterator<Item = Color>>(iter: I) -> Color {
+        iter.fold(Color::black(), |acc, c| acc + c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_is_per_component() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(4.0, -5.0, 6.0);
+        assert_eq!(a + b, Vec3::new(5.0, -3.0, 9.0));
+        assert_eq!(a - b, Vec3::new(-3.0, 7.0, -3.0));
+        assert_eq!(a * b, Vec3::new(4.0, -10.0, 18.0));
+        assert_eq!(b / a, Vec3::new(4.0, -2.5, 2.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(-a, Vec3::new(-1.0, -2.0, -3.0));
+        assert_eq!(vec![a, b].into_iter().sum::<Vec3>(), a + b);
+    }
+
+    #[test]
+    fn division_keeps_the_padding_lane_zero() {
+        // 0/0 in the unused lane would make equal vectors compare unequal
+        let v = Vec3::new(1.0, 2.0, 3.0) / Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(v, Vec3::splat(1.0));
+    }
+
+    #[test]
+    fn dot_and_cross() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        assert_eq!(Vec3::dot(x, y), 0.0);
+        assert_eq!(Vec3::cross(x, y), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(Vec3::cross(y, x), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(Vec3::new(3.0, 4.0, 0.0).len(), 5.0);
+        assert!((Vec3::unit_vector(Vec3::new(3.0, 4.0, 12.0)).len() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn indexes_components() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!((v[0], v[1], v[2]), (1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_past_the_components_panics() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        let _ = v[3];
+    }
+}
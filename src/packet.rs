@@ -0,0 +1,305 @@
+//! Tracing rays in packets, for coherent rays like those from the camera.
+//!
+//! A `RayPacket` stores up to `WIDTH` rays a component at a time. The box,
+//! sphere and triangle tests work on whole `Lanes` at once: every step is a
+//! loop over a fixed-size array with no branches, which compiles to packed
+//! SIMD instructions, and lanes that miss are masked out at the end. Only
+//! the hit records are made a ray at a time. The BVH saves the rest: rays
+//! that start close together and point the same way tend to pass through
+//! the same nodes, which the packet then only visits once.
+
+use std::ops::{Mul, Sub};
+
+use super::hit::HitRecord;
+use super::{Float, Ray, Vec3};
+
+/// Rays in a packet.
+pub const WIDTH: usize = 8;
+
+/// A value for each ray of a packet.
+pub type Lanes = [Float; WIDTH];
+
+/// `f` of each lane, as one loop the compiler vectorizes.
+#[inline(always)]
+pub fn lanes<F: Fn(usize) -> Float>(f: F) -> Lanes {
+    let mut out = [0.0; WIDTH];
+    for (i, o) in out.iter_mut().enumerate() {
+        *o = f(i);
+    }
+    out
+}
+
+/// A vector for each ray of a packet, a component at a time. The sums are
+/// done in the same order as `Vec3`'s, so each lane gets exactly the result
+/// a single ray would.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vec3Lanes {
+    pub x: Lanes,
+    pub y: Lanes,
+    pub z: Lanes,
+}
+
+impl Vec3Lanes {
+    /// `v` in every lane.
+    pub fn splat(v: Vec3) -> Self {
+        Vec3Lanes {
+            x: [v.x(); WIDTH],
+            y: [v.y(); WIDTH],
+            z: [v.z(); WIDTH],
+        }
+    }
+
+    #[inline(always)]
+    pub fn dot(l: &Self, r: &Self) -> Lanes {
+        lanes(|i| l.x[i] * r.x[i] + l.y[i] * r.y[i] + l.z[i] * r.z[i])
+    }
+
+    #[inline(always)]
+    pub fn cross(l: &Self, r: &Self) -> Self {
+        Vec3Lanes {
+            x: lanes(|i| l.y[i] * r.z[i] - l.z[i] * r.y[i]),
+            y: lanes(|i| -(l.x[i] * r.z[i] - l.z[i] * r.x[i])),
+            z: lanes(|i| l.x[i] * r.y[i] - l.y[i] * r.x[i]),
+        }
+    }
+}
+
+impl Sub for Vec3Lanes {
+    type Output = Vec3Lanes;
+
+    #[inline(always)]
+    fn sub(self, o: Vec3Lanes) -> Vec3Lanes {
+        Vec3Lanes {
+            x: lanes(|i| self.x[i] - o.x[i]),
+            y: lanes(|i| self.y[i] - o.y[i]),
+            z: lanes(|i| self.z[i] - o.z[i]),
+        }
+    }
+}
+
+/// Each lane's vector scaled by that lane's value.
+impl Mul<Lanes> for Vec3Lanes {
+    type Output = Vec3Lanes;
+
+    #[inline(always)]
+    fn mul(self, k: Lanes) -> Vec3Lanes {
+        Vec3Lanes {
+            x: lanes(|i| self.x[i] * k[i]),
+            y: lanes(|i| self.y[i] * k[i]),
+            z: lanes(|i| self.z[i] * k[i]),
+        }
+    }
+}
+
+pub struct RayPacket {
+    rays: [Ray; WIDTH],
+    len: usize,
+    origin: [Lanes; 3],
+    direction: [Lanes; 3],
+    inv_direction: [Lanes; 3],
+}
+
+impl RayPacket {
+    /// Packs up to `WIDTH` rays. Panics if there are more.
+    pub fn new(rays: &[Ray]) -> Self {
+        assert!(rays.len() <= WIDTH, "a packet holds at most {} rays", WIDTH);
+        let mut packet = RayPacket {
+            rays: [Ray::new(); WIDTH],
+            len: rays.len(),
+            origin: [[0.0; WIDTH]; 3],
+            direction: [[0.0; WIDTH]; 3],
+            inv_direction: [[0.0; WIDTH]; 3],
+        };
+        for (i, r) in rays.iter().enumerate() {
+            packet.rays[i] = *r;
+            for axis in 0..3 {
+                packet.origin[axis][i] = r.origin()[axis];
+                packet.direction[axis][i] = r.direction()[axis];
+                packet.inv_direction[axis][i] = 1.0 / r.direction()[axis];
+            }
+        }
+        packet
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn ray(&self, lane: usize) -> &Ray {
+        &self.rays[lane]
+    }
+
+    pub fn origin(&self, axis: usize) -> &Lanes {
+        &self.origin[axis]
+    }
+
+    pub fn direction(&self, axis: usize) -> &Lanes {
+        &self.direction[axis]
+    }
+
+    pub fn inv_direction(&self, axis: usize) -> &Lanes {
+        &self.inv_direction[axis]
+    }
+
+    /// Every ray's origin.
+    pub fn origins(&self) -> Vec3Lanes {
+        let [x, y, z] = self.origin;
+        Vec3Lanes { x, y, z }
+    }
+
+    /// Every ray's direction.
+    pub fn directions(&self) -> Vec3Lanes {
+        let [x, y, z] = self.direction;
+        Vec3Lanes { x, y, z }
+    }
+}
+
+/// The closest hit found so far for each ray of a packet. Lanes past the
+/// end of the packet have a `t_max` of minus infinity, so nothing can hit
+/// them.
+pub struct PacketHits {
+    t_max: Lanes,
+    records: [Option<HitRecord>; WIDTH],
+}
+
+impl PacketHits {
+    /// No hits yet for the rays of `packet`, which are looked for up to
+    /// `t_max`.
+    pub fn new(packet: &RayPacket, t_max: Float) -> Self {
+        let mut lanes = [Float::NEG_INFINITY; WIDTH];
+        lanes[..packet.len()].fill(t_max);
+        PacketHits {
+            t_max: lanes,
+            records: [None; WIDTH],
+        }
+    }
+
+    /// How far along each ray a hit has to be closer than.
+    pub fn t_max(&self) -> &Lanes {
+        &self.t_max
+    }
+
+    /// Whether the ray in `lane` can still be hit beyond `t_min`.
+    pub fn is_active(&self, lane: usize, t_min: Float) -> bool {
+        self.t_max[lane] > t_min
+    }
+
+    /// Keeps a hit for the ray in `lane`, which has to be closer than any
+    /// found so far.
+    pub fn add(&mut self, lane: usize, record: HitRecord) {
+        self.t_max[lane] = record.t;
+        self.records[lane] = Some(record);
+    }
+
+    pub fn get_mut(&mut self, lane: usize) -> Option<&mut HitRecord> {
+        self.records[lane].as_mut()
+    }
+
+    pub fn into_records(self) -> [Option<HitRecord>; WIDTH] {
+        self.records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::hit::Hit;
+    use crate::material::test_materials;
+    use crate::shape::Triangle;
+    use crate::sphere::Sphere;
+    use crate::Vec3;
+    use std::sync::Arc;
+    use crate::Point3;
+
+    #[test]
+    fn stores_rays_a_component_at_a_time() {
+        let rays = [
+            Ray::with_values(Point3::new(1.0, 2.0, 3.0), Vec3::new(0.5, -4.0, 2.0)),
+            Ray::with_values(Point3::new(4.0, 5.0, 6.0), Vec3::new(1.0, 1.0, -1.0)),
+        ];
+        let packet = RayPacket::new(&rays);
+        assert_eq!(packet.len(), 2);
+        assert_eq!(packet.origin(1)[..2], [2.0, 5.0]);
+        assert_eq!(packet.direction(2)[..2], [2.0, -1.0]);
+        assert_eq!(packet.inv_direction(1)[..2], [-0.25, 1.0]);
+        assert_eq!(packet.ray(1).origin(), rays[1].origin());
+
+        let hits = PacketHits::new(&packet, 10.0);
+        assert!(hits.is_active(1, 0.0) && !hits.is_active(2, 0.0));
+    }
+
+    #[test]
+    fn lane_math_matches_vec3() {
+        let rays: Vec<Ray> = (0..WIDTH)
+            .map(|i| {
+                let a = i as Float * 0.7;
+                Ray::with_values(Point3::new(a, -a, 1.0 + a), Vec3::new(a.sin(), a.cos(), 0.3 - a))
+            })
+            .collect();
+        let packet = RayPacket::new(&rays);
+        let (o, d) = (packet.origins(), packet.directions());
+        let k = lanes(|i| i as Float - 2.5);
+        let (dot, cross, diff) = (Vec3Lanes::dot(&o, &d), Vec3Lanes::cross(&o, &d), o - d * k);
+        for (i, r) in rays.iter().enumerate() {
+            assert_eq!(dot[i], Vec3::dot(*r.origin(), r.direction()));
+            let c = Vec3::cross(*r.origin(), r.direction());
+            assert_eq!((cross.x[i], cross.y[i], cross.z[i]), (c.x(), c.y(), c.z()));
+            let v = r.origin() - k[i] * r.direction();
+            assert_eq!((diff.x[i], diff.y[i], diff.z[i]), (v.x(), v.y(), v.z()));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at most")]
+    fn holds_at_most_width_rays() {
+        RayPacket::new(&[Ray::new(); WIDTH + 1]);
+    }
+
+    #[test]
+    fn finds_the_same_hits_as_single_rays() {
+        let (_, mat) = test_materials();
+        let mut objects: Vec<Arc<dyn Hit>> = Vec::new();
+        for i in 0..30 {
+            let c = Vec3::new((i % 6) as Float - 2.5, (i / 6) as Float - 2.0, -4.0 - (i % 4) as Float);
+            if i % 2 == 0 {
+                objects.push(Arc::new(Sphere::new(c, 0.4, mat)));
+            } else {
+                let (dx, dy) = (Vec3::new(0.8, 0.0, 0.1), Vec3::new(0.0, 0.8, 0.2));
+                objects.push(Arc::new(Triangle::new(c, c + dx, c + dy, mat)));
+            }
+        }
+        let bvh = Bvh::new(&objects);
+
+        // a coherent fan from a camera, and rays off every which way
+        let fan = (0..64).map(|i| {
+            let (u, v) = ((i % 8) as Float / 7.0, (i / 8) as Float / 7.0);
+            Ray::with_values(Point3::origin(), Vec3::new(6.0 * u - 3.0, 5.0 * v - 2.5, -4.0))
+        });
+        let scattered = (0..61).map(|i| {
+            let a = i as Float * 2.4;
+            let dir = Vec3::new(a.cos(), (1.7 * a).sin(), (0.3 * a).cos() - 0.8);
+            Ray::with_values(Point3::new(0.0, 0.0, -5.0), dir)
+        });
+        let rays: Vec<Ray> = fan.chain(scattered).collect();
+
+        let mut hit = 0;
+        for chunk in rays.chunks(WIDTH) {
+            let packet = RayPacket::new(chunk);
+            let mut hits = PacketHits::new(&packet, Float::MAX);
+            bvh.hit_packet(&packet, 0.0, &mut hits);
+            let records = hits.into_records();
+            for (lane, r) in chunk.iter().enumerate() {
+                let scalar = bvh.hit(r, 0.0, Float::MAX).map(|h| (h.t, h.object, h.point));
+                assert_eq!(records[lane].map(|h| (h.t, h.object, h.point)), scalar);
+                hit += scalar.is_some() as usize;
+            }
+            assert!(records[chunk.len()..].iter().all(Option::is_none));
+        }
+        assert!(hit > 15, "only {} rays hit", hit);
+    }
+}
@@ -0,0 +1,396 @@
+//! A small HTTP server for watching a render progress and steering its
+//! camera from a browser or any HTTP client, without a window.
+//!
+//! * `GET /` is a page showing the image as it refines.
+//! * `GET /image.png` is the latest image.
+//! * `GET /status` reports progress and the view as JSON.
+//! * `POST /camera?position=x,y,z&look_at=x,y,z&fov=degrees` moves the
+//!   camera, keeping whatever isn't given, and starts over.
+//! * `POST /restart` throws the samples away and starts over.
+//!
+//! The renderer publishes images with `Preview::publish` and picks up what
+//! clients asked for with `Preview::command`.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::color::Pipeline;
+use super::image::{self, HdrImage};
+use super::{Float, Vec3};
+
+/// Where the camera is and what it looks at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    pub position: Vec3,
+    pub look_at: Vec3,
+    /// Vertical field of view in degrees.
+    pub fov: Float,
+}
+
+impl View {
+    /// Which way is up for the camera, which can't look straight along it.
+    pub const UP: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+}
+
+/// What a client asked the renderer to do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    /// Render from this view instead, starting over.
+    Move(View),
+    Restart,
+}
+
+/// How long a client may take to send each part of its request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest request line or header read.
+const MAX_LINE: u64 = 8 * 1024;
+/// Largest request body read.
+const MAX_BODY: u64 = 64 * 1024;
+
+struct State {
+    png: Vec<u8>,
+    samples: u64,
+    target: u64,
+    view: View,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    commands: Mutex<Sender<Command>>,
+}
+
+/// The renderer's side of a running preview server.
+pub struct Preview {
+    shared: Arc<Shared>,
+    commands: Receiver<Command>,
+    addr: SocketAddr,
+}
+
+impl Preview {
+    /// Starts serving on `addr` in the background, showing `view`.
+    pub fn start<A: ToSocketAddrs>(addr: A, view: View) -> io::Result<Preview> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let (tx, commands) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                png: Vec::new(),
+                samples: 0,
+                target: 0,
+                view,
+            }),
+            commands: Mutex::new(tx),
+        });
+
+        let server = shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = server.clone();
+                thread::spawn(move || {
+                    // a client hanging up early isn't the renderer's problem
+                    let _ = respond(stream, &shared);
+                });
+            }
+        });
+
+        Ok(Preview {
+            shared,
+            commands,
+            addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Makes `image` the one clients see, `samples` of `target` samples per
+    /// pixel in.
+    pub fn publish(&self, image: &HdrImage, display: &Pipeline, samples: u64, target: u64) {
+        let mut png = Vec::new();
+        image::write_png(&mut png, image, display).expect("writing to memory can't fail");
+        let mut state = self.shared.state.lock().unwrap();
+        state.png = png;
+        state.samples = samples;
+        state.target = target;
+    }
+
+    /// The next thing a client asked for, waiting for one if `wait` is set.
+    /// Only the last of several camera moves matters, so they're merged.
+    pub fn command(&self, wait: bool) -> Option<Command> {
+        let mut command = if wait {
+            self.commands.recv().ok()
+        } else {
+            self.commands.try_recv().ok()
+        };
+        while let Ok(next) = self.commands.try_recv() {
+            command = match (command, next) {
+                (_, Command::Move(view)) | (Some(Command::Move(view)), Command::Restart) => {
+                    Some(Command::Move(view))
+                }
+                (_, Command::Restart) => Some(Command::Restart),
+            };
+        }
+        command
+    }
+}
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>riow preview</title></head>
+<body style="background: #222; color: #ccc; font-family: sans-serif">
+<img id="image" src="/image.png">
+<p id="status"></p>
+<script>
+setInterval(async () => {
+    const image = new Image();
+    image.onload = () => document.getElementById("image").src = image.src;
+    image.src = "/image.png?" + Date.now();
+    const status = await (await fetch("/status")).json();
+    document.getElementById("status").textContent =
+        status.samples + " / " + status.target + " samples";
+}, 500);
+</script>
+</body>
+</html>
+"#;
+
+fn respond(stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    // clients that stop sending mustn't hold on to a thread for ever
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let reader = BufReader::new(stream.try_clone()?);
+    handle(reader, stream, shared)
+}
+
+fn handle<R: BufRead, W: Write>(mut reader: R, mut w: W, shared: &Shared) -> io::Result<()> {
+    let line = read_line(&mut reader)?;
+    let mut words = line.split_whitespace();
+    let (method, target) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+
+    // skip the headers and any body, which nothing here needs
+    let mut length = 0;
+    loop {
+        let header = read_line(&mut reader)?;
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return reply(&mut w, "413 Payload Too Large", "text/plain", b"request too large\n");
+    }
+    io::copy(&mut reader.by_ref().take(length), &mut io::sink())?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match (method, path) {
+        ("GET", "/") => reply(&mut w, "200 OK", "text/html", PAGE.as_bytes()),
+        ("GET", "/image.png") => {
+            let png = shared.state.lock().unwrap().png.clone();
+            if png.is_empty() {
+                reply(&mut w, "503 Service Unavailable", "text/plain", b"no image yet\n")
+            } else {
+                reply(&mut w, "200 OK", "image/png", &png)
+            }
+        }
+        ("GET", "/status") => {
+            let status = status(&shared.state.lock().unwrap());
+            reply(&mut w, "200 OK", "application/json", status.as_bytes())
+        }
+        ("POST", "/camera") => {
+            let view = {
+                let mut state = shared.state.lock().unwrap();
+                match parse_view(query, state.view) {
+                    Ok(view) => {
+                        state.view = view;
+                        view
+                    }
+                    Err(e) => return reply(&mut w, "400 Bad Request", "text/plain", e.as_bytes()),
+                }
+            };
+            let _ = shared.commands.lock().unwrap().send(Command::Move(view));
+            reply(&mut w, "200 OK", "application/json", status(&shared.state.lock().unwrap()).as_bytes())
+        }
+        ("POST", "/restart") => {
+            let _ = shared.commands.lock().unwrap().send(Command::Restart);
+            reply(&mut w, "200 OK", "text/plain", b"restarted\n")
+        }
+        (_, "/") | (_, "/image.png") | (_, "/status") | (_, "/camera") | (_, "/restart") => {
+            reply(&mut w, "405 Method Not Allowed", "text/plain", b"method not allowed\n")
+        }
+        _ => reply(&mut w, "404 Not Found", "text/plain", b"not found\n"),
+    }
+}
+
+/// The next line of a request, which is empty at its end.
+fn read_line<R: BufRead>(r: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    r.take(MAX_LINE).read_line(&mut line)?;
+    if line.len() as u64 == MAX_LINE && !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+    }
+    Ok(line)
+}
+
+fn reply<W: Write>(w: &mut W, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        w,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    w.write_all(body)?;
+    w.flush()
+}
+
+fn status(state: &State) -> String {
+    let vec = |v: Vec3| format!("[{}, {}, {}]", v.x(), v.y(), v.z());
+    format!(
+        "{{\"samples\": {}, \"target\": {}, \"position\": {}, \"look_at\": {}, \"fov\": {}}}\n",
+        state.samples,
+        state.target,
+        vec(state.view.position),
+        vec(state.view.look_at),
+        state.view.fov
+    )
+}
+
+/// Applies `position`, `look_at` and `fov` from a query string to `view`.
+fn parse_view(query: &str, mut view: View) -> Result<View, String> {
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = value.replace("%2C", ",").replace("%2c", ",");
+        let numbers: Vec<Float> = value
+            .split(',')
+            .map(|v| match v.trim().parse() {
+                Ok(n) if Float::is_finite(n) => Ok(n),
+                _ => Err(format!("invalid number {}\n", v)),
+            })
+            .collect::<Result<_, _>>()?;
+        match (name, numbers.as_slice()) {
+            ("position", [x, y, z]) => view.position = Vec3::new(*x, *y, *z),
+            ("look_at", [x, y, z]) => view.look_at = Vec3::new(*x, *y, *z),
+            ("fov", [fov]) if *fov > 0.0 && *fov < 180.0 => view.fov = *fov,
+            _ => return Err(format!("invalid {}\n", name)),
+        }
+    }
+    if view.position == view.look_at {
+        return Err(String::from("the camera can't look at where it is\n"));
+    }
+    let dir = view.look_at - view.position;
+    if Vec3::cross(dir, View::UP).len() <= 1e-6 * dir.len() {
+        return Err(String::from("the camera can't look straight up or down\n"));
+    }
+    Ok(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEW: View = View {
+        position: Vec3::new(0.0, 0.0, 1.0),
+        look_at: Vec3::new(0.0, 0.0, 0.0),
+        fov: 40.0,
+    };
+
+    fn shared() -> (Shared, Receiver<Command>) {
+        let (tx, rx) = mpsc::channel();
+        let state = State {
+            png: Vec::new(),
+            samples: 3,
+            target: 8,
+            view: VIEW,
+        };
+        let shared = Shared {
+            state: Mutex::new(state),
+            commands: Mutex::new(tx),
+        };
+        (shared, rx)
+    }
+
+    /// The status line of the reply to `request`.
+    fn status_of(request: &str, shared: &Shared) -> String {
+        let mut reply = Vec::new();
+        handle(request.as_bytes(), &mut reply, shared).unwrap();
+        let reply = String::from_utf8_lossy(&reply);
+        String::from(reply.lines().next().unwrap_or(""))
+    }
+
+    #[test]
+    fn parses_views() {
+        let view = parse_view("position=1,2,3&fov=60", VIEW).unwrap();
+        assert_eq!(view.position, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!((view.look_at, view.fov), (VIEW.look_at, 60.0));
+        let view = parse_view("look_at=1%2C0%2c0", VIEW).unwrap();
+        assert_eq!(view.look_at, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(parse_view("", VIEW), Ok(VIEW));
+    }
+
+    #[test]
+    fn rejects_bad_views() {
+        for query in &[
+            "position=1,2",
+            "position=nan,0,0",
+            "look_at=inf,0,0",
+            "fov=NaN",
+            "fov=0",
+            "fov=180",
+            "fov=x",
+            "zoom=2",
+            "look_at=0,0,1",
+            "position=0,5,0",
+            "look_at=0,-3,1",
+        ] {
+            assert!(parse_view(query, VIEW).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn answers_requests() {
+        let (shared, _commands) = shared();
+        assert_eq!(status_of("GET / HTTP/1.1\r\n\r\n", &shared), "HTTP/1.1 200 OK");
+        assert_eq!(status_of("GET /image.png HTTP/1.1\r\n\r\n", &shared), "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(status_of("GET /nothing HTTP/1.1\r\n\r\n", &shared), "HTTP/1.1 404 Not Found");
+        assert_eq!(status_of("DELETE /status HTTP/1.1\r\n\r\n", &shared), "HTTP/1.1 405 Method Not Allowed");
+
+        let mut reply = Vec::new();
+        handle(&b"GET /status HTTP/1.1\r\nHost: x\r\n\r\n"[..], &mut reply, &shared).unwrap();
+        let reply = String::from_utf8(reply).unwrap();
+        let status = r#"{"samples": 3, "target": 8, "position": [0, 0, 1], "look_at": [0, 0, 0], "fov": 40}"#;
+        assert!(reply.ends_with(&format!("{}\n", status)), "{}", reply);
+    }
+
+    #[test]
+    fn camera_moves_reach_the_renderer() {
+        let (shared, commands) = shared();
+        let request = "POST /camera?fov=20 HTTP/1.1\r\nContent-Length: 4\r\n\r\nbody";
+        assert_eq!(status_of(request, &shared), "HTTP/1.1 200 OK");
+        let moved = View { fov: 20.0, ..VIEW };
+        assert_eq!(commands.try_recv(), Ok(Command::Move(moved)));
+        assert_eq!(shared.state.lock().unwrap().view, moved);
+
+        assert_eq!(status_of("POST /camera?fov=nan HTTP/1.1\r\n\r\n", &shared), "HTTP/1.1 400 Bad Request");
+        assert!(commands.try_recv().is_err());
+        assert_eq!(status_of("POST /restart HTTP/1.1\r\n\r\n", &shared), "HTTP/1.1 200 OK");
+        assert_eq!(commands.try_recv(), Ok(Command::Restart));
+    }
+
+    #[test]
+    fn rejects_oversized_requests() {
+        let (shared, _commands) = shared();
+        let mut reply = Vec::new();
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "x".repeat(MAX_LINE as usize));
+        assert!(handle(long.as_bytes(), &mut reply, &shared).is_err());
+        let big = "POST /restart HTTP/1.1\r\nContent-Length: 1000000000\r\n\r\n";
+        assert_eq!(status_of(big, &shared), "HTTP/1.1 413 Payload Too Large");
+    }
+}
@@ -0,0 +1,112 @@
+//! The random numbers used while rendering. Each thread has its own
+//! generator, which can be reseeded before every sample so a render comes
+//! out the same no matter how it's split across threads or runs.
+
+use std::cell::RefCell;
+
+use rand::{Error, RngCore};
+
+/// O'Neill's PCG32 (XSH RR), small and fast to seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+impl Pcg32 {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+thread_local! {
+    static RNG: RefCell<Pcg32> = RefCell::new(Pcg32::new(rand::random(), rand::random()));
+}
+
+/// A handle to this thread's generator, used like `rand::thread_rng()`.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalRng;
+
+pub fn rng() -> LocalRng {
+    LocalRng
+}
+
+impl RngCore for LocalRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|r| r.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|r| r.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|r| r.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        RNG.with(|r| r.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+/// Reseeds this thread's generator for sample `sample` of pixel `pixel`, so
+/// the sample only depends on the render's `seed` and where it is.
+pub fn seed_sample(seed: u64, pixel: u64, sample: u64) {
+    let s = splitmix64(seed ^ splitmix64(pixel ^ splitmix64(sample)));
+    RNG.with(|r| *r.borrow_mut() = Pcg32::new(s, pixel));
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// This thread's generator as it stands, so a sample can be put aside and
+/// carried on with later using `restore`.
+pub fn save() -> Pcg32 {
+    RNG.with(|r| *r.borrow())
+}
+
+pub fn restore(rng: Pcg32) {
+    RNG.with(|r| *r.borrow_mut() = rng);
+}
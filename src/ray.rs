@@ -1,35 +1,127 @@
 
-use super::Vec3;
+use super::{Float, Normal, Point3, Vec3};
 
+#[derive(Debug, Clone, Copy)]
 pub struct Ray {
-    origin: Vec3,
+    origin: Point3,
     dir: Vec3,
 }
 
+impl Default for Ray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Ray {
     pub fn new() -> Self {
         Ray{
-            origin: Vec3::origin(),
+            origin: Point3::origin(),
             dir: Vec3::origin(),
         }
     }
 
-    pub fn with_values(origin: Vec3, dir: Vec3) -> Self {
+    pub fn with_values(origin: Point3, dir: Vec3) -> Self {
         Ray{
-            origin: origin,
-            dir: dir,
+            origin,
+            dir,
         }
     }
 
-    pub fn origin(&self) -> &Vec3 {
-        &self.origin
+    /// A ray leaving a surface point that is only known to within `error`
+    /// in each axis. The origin is pushed along the surface normal `n` to
+    /// the side `dir` heads towards, far enough that the ray can't hit the
+    /// surface it started on again, so no `t_min` fudge is needed.
+    pub fn spawn(point: Point3, error: Vec3, n: Normal, dir: Vec3) -> Self {
+        Ray::with_values(offset_origin(point, error, n, dir), dir)
+    }
+
+    pub fn origin(&self) -> Point3 {
+        self.origin
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        self.dir
+    }
+
+    pub fn point_at_param(&self, t: Float) -> Point3 {
+        self.origin + t * self.dir
+    }
+
+    /// Bound on the rounding error of `point_at_param(t)` when `t` came
+    /// from solving for an intersection, for shapes that can't cheaply
+    /// project the point back onto their surface.
+    pub fn point_error(&self, t: Float) -> Vec3 {
+        gamma(7) * (self.origin.abs() + (t * self.dir).abs())
     }
+}
+
+/// Bound on the relative error after `n` rounded floating point operations,
+/// as defined in PBRT.
+pub(crate) fn gamma(n: i32) -> Float {
+    let e = n as Float * Float::EPSILON * 0.5;
+    e / (1.0 - e)
+}
+
+/// The smallest float greater than `v`.
+fn next_up(v: Float) -> Float {
+    if v.is_nan() || v == Float::INFINITY {
+        v
+    } else if v == 0.0 {
+        Float::from_bits(1)
+    } else if v > 0.0 {
+        Float::from_bits(v.to_bits() + 1)
+    } else {
+        Float::from_bits(v.to_bits() - 1)
+    }
+}
+
+fn offset_origin(p: Point3, error: Vec3, n: Normal, dir: Vec3) -> Point3 {
+    let d = Vec3::dot(n.abs(), error);
+    let offset = if Vec3::dot(dir, *n) < 0.0 { -d * n } else { d * n };
+    let po = p + offset;
+
+    // round away from the surface so the offset itself can't be lost
+    let round = |v: Float, o: Float| {
+        if o > 0.0 {
+            next_up(v)
+        } else if o < 0.0 {
+            -next_up(-v)
+        } else {
+            v
+        }
+    };
+    Point3::new(
+        round(po.x(), offset.x()),
+        round(po.y(), offset.y()),
+        round(po.z(), offset.z()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn direction(&self) -> &Vec3 {
-        &self.dir
+    #[test]
+    fn next_up_steps_one_float() {
+        assert_eq!(next_up(1.0), 1.0 + Float::EPSILON);
+        assert!(next_up(-1.0) > -1.0 && next_up(-1.0) < -1.0 + Float::EPSILON);
+        assert_eq!(next_up(0.0), Float::from_bits(1));
+        assert_eq!(next_up(-0.0), Float::from_bits(1));
+        assert_eq!(next_up(-Float::from_bits(1)), -0.0);
+        assert_eq!(next_up(Float::INFINITY), Float::INFINITY);
+        assert_eq!(next_up(Float::NEG_INFINITY), -Float::MAX);
     }
 
-    pub fn point_at_param(&self, t: f64) -> Vec3 {
-        &self.origin + t * &self.dir
+    #[test]
+    fn spawned_rays_start_on_the_side_they_head_to() {
+        let n = Normal::from_unit(Vec3::new(0.0, 1.0, 0.0));
+        let p = Point3::new(0.5, 0.0, 0.5);
+        let error = Vec3::splat(1e-6);
+        let up = Ray::spawn(p, error, n, Vec3::new(0.0, 1.0, 0.0));
+        assert!(up.origin().y() >= 1e-6);
+        let down = Ray::spawn(p, error, n, Vec3::new(0.3, -1.0, 0.0));
+        assert!(down.origin().y() <= -1e-6);
+        assert_eq!(down.direction(), Vec3::new(0.3, -1.0, 0.0));
     }
 }
@@ -0,0 +1,870 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aov::Aovs;
+use super::background::Environment;
+use super::bvh::Bvh;
+use super::hit::{HitRecord, HitableList};
+use super::light::Light;
+use super::material::{Ior, Material, MaterialId, Materials, Medium};
+use super::packet::{PacketHits, RayPacket, WIDTH};
+use super::scene::{RayKind, Visibility};
+use super::spectrum::{self, SampledSpectrum, SampledWavelengths};
+use super::stats;
+use super::{Float, Ray, Vec3};
+
+/// The single limit on bounces from before `MaxDepth`, which is still the
+/// default for diffuse ones.
+#[deprecated(note = "use `MaxDepth`")]
+pub const MAX_BOUNCE: u8 = 10;
+
+/// Limits on how many times a path can bounce off each kind of surface
+/// before it's cut off. Russian roulette usually ends paths well before
+/// these, which are there for paths that lose no energy, like light
+/// trapped inside glass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxDepth {
+    pub diffuse: u32,
+    /// Mirror-like reflections, including off glass.
+    pub specular: u32,
+    /// Refractions into or out of glass.
+    pub transmission: u32,
+}
+
+impl Default for MaxDepth {
+    fn default() -> Self {
+        MaxDepth {
+            diffuse: 10,
+            specular: 32,
+            transmission: 32,
+        }
+    }
+}
+
+/// Bounces a path has made so far.
+#[derive(Debug, Clone, Copy, Default)]
+struct Depth {
+    total: u32,
+    diffuse: u32,
+    specular: u32,
+    transmission: u32,
+}
+
+impl Depth {
+    fn exceeds(&self, max: &MaxDepth) -> bool {
+        self.diffuse > max.diffuse || self.specular > max.specular || self.transmission > max.transmission
+    }
+}
+
+/// How much shorter than the distance to a light shadow rays are, relative
+/// to it, so they can't hit an object the light sits on.
+const SHADOW_MARGIN: Float = 1e-4;
+
+/// Path traces a world lit by an environment and any analytic lights.
+pub struct Renderer {
+    world: HitableList,
+    /// The world's objects, which is what rays are actually traced against.
+    bvh: Bvh,
+    materials: Materials,
+    /// Which rays see each object of the world, by index, or empty if they
+    /// all see everything.
+    visibility: Vec<Visibility>,
+    environment: Environment,
+    lights: Vec<Arc<dyn Light>>,
+    spectral: bool,
+    max_depth: MaxDepth,
+    roulette_depth: Option<u32>,
+}
+
+impl Renderer {
+    /// Renders `world`, whose objects' materials are in `materials`. Fails if
+    /// an object refers to a material from another registry.
+    pub fn new(world: HitableList, materials: Materials, environment: Environment) -> Result<Self, String> {
+        for object in world.objects() {
+            if let Some(id) = object.material_ids().into_iter().find(|&id| !materials.contains(id)) {
+                return Err(format!("material {:?} isn't in the renderer's registry", id));
+            }
+        }
+        Ok(Self {
+            bvh: Bvh::new(world.objects()),
+            world,
+            materials,
+            visibility: Vec::new(),
+            environment,
+            lights: Vec::new(),
+            spectral: false,
+            max_depth: MaxDepth::default(),
+            roulette_depth: Some(3),
+        })
+    }
+
+    pub fn add_light(&mut self, light: Arc<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    pub fn world(&self) -> &HitableList {
+        &self.world
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn materials(&self) -> &Materials {
+        &self.materials
+    }
+
+    /// Swaps the material `id` refers to between renders. Fails if `id` is
+    /// from another registry.
+    pub fn set_material(&mut self, id: MaterialId, material: Arc<dyn Material>) -> Result<(), String> {
+        if !self.materials.contains(id) {
+            return Err(format!("material {:?} isn't in the renderer's registry", id));
+        }
+        self.materials.set(id, material);
+        Ok(())
+    }
+
+    /// Which rays see each object of the world, by its index in the world.
+    /// Fails unless there's one for every object.
+    pub fn set_visibility(&mut self, visibility: Vec<Visibility>) -> Result<(), String> {
+        if visibility.len() != self.world.objects().len() {
+            return Err(format!(
+                "{} visibilities for {} objects",
+                visibility.len(),
+                self.world.objects().len()
+            ));
+        }
+        self.visibility = visibility;
+        Ok(())
+    }
+
+    /// Traces with a few wavelengths per path instead of RGB, converting
+    /// the result through CIE XYZ. Needed for dispersion to show up.
+    pub fn set_spectral(&mut self, spectral: bool) {
+        self.spectral = spectral;
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: MaxDepth) {
+        self.max_depth = max_depth;
+    }
+
+    /// The number of bounces after which paths are ended at random, with a
+    /// chance that grows as their throughput drops. Survivors are weighted
+    /// up to make up for the ones ended, so this doesn't darken the image.
+    /// `None` turns it off, leaving only the depth limits.
+    pub fn set_roulette_depth(&mut self, depth: Option<u32>) {
+        self.roulette_depth = depth;
+    }
+
+    /// Linear sRGB radiance arriving along `r`.
+    pub fn color(&self, r: &Ray) -> Vec3 {
+        self.color_from(r, self.first_hit(r))
+    }
+
+    /// Where a camera ray first hits something it sees.
+    pub fn first_hit(&self, r: &Ray) -> Option<HitRecord> {
+        self.hit(r, Float::MAX, RayKind::Camera)
+    }
+
+    /// Where each ray of a packet first hits the world, found for all of
+    /// them at once. Faster than one at a time for rays that head the same
+    /// way from nearby, like those from the camera.
+    pub fn hit_packet(&self, packet: &RayPacket) -> [Option<HitRecord>; WIDTH] {
+        let mut hits = PacketHits::new(packet, Float::MAX);
+        self.bvh.hit_packet_visible(packet, 0.0, &mut hits, |i| self.sees(i, RayKind::Camera));
+        hits.into_records()
+    }
+
+    /// Like `color`, for a ray whose first hit `hit_packet` already found.
+    pub fn color_from(&self, r: &Ray, hit: Option<HitRecord>) -> Vec3 {
+        self.shade(r, hit, None)
+    }
+
+    /// Like `color_from`, also returning the passes at the first surface
+    /// the path scattered off, with the direct light it took from there.
+    pub fn color_and_aovs(&self, r: &Ray, hit: Option<HitRecord>) -> (Vec3, Aovs) {
+        let mut aovs = Aovs {
+            lights: vec![Vec3::origin(); self.lights.len()],
+            ..Aovs::default()
+        };
+        let color = self.shade(r, hit, Some(&mut aovs));
+        (color, aovs)
+    }
+
+    fn shade(&self, r: &Ray, hit: Option<HitRecord>, aovs: Option<&mut Aovs>) -> Vec3 {
+        if self.spectral {
+            let lambda = SampledWavelengths::sample(crate::random::rng().gen());
+            self.trace(*r, hit, Some(lambda), aovs).to_rgb(&lambda)
+        } else {
+            let s = self.trace(*r, hit, None, aovs);
+            Vec3::new(s.0[0], s.0[1], s.0[2])
+        }
+    }
+
+    // the closest hit along `r` on an object rays of `kind` see
+    fn hit(&self, r: &Ray, t_max: Float, kind: RayKind) -> Option<HitRecord> {
+        self.bvh.hit_visible(r, 0.0, t_max, |i| self.sees(i, kind))
+    }
+
+    fn sees(&self, object: usize, kind: RayKind) -> bool {
+        // every object is seen until visibilities are set
+        match self.visibility.get(object) {
+            Some(v) => v.sees(kind),
+            None => true,
+        }
+    }
+
+    // follows a path from `ray`, which hits `first`, bounce by bounce,
+    // adding up the light that reaches its start and filling in `aovs` at
+    // the first bounce
+    fn trace(
+        &self,
+        mut ray: Ray,
+        first: Option<HitRecord>,
+        mut lambda: Option<SampledWavelengths>,
+        mut aovs: Option<&mut Aovs>,
+    ) -> SampledSpectrum {
+        let start = ray.origin();
+        let mut radiance = SampledSpectrum::zero();
+        // how much of the light arriving along `ray` makes it to the start,
+        // and the product of the attenuations alone that roulette goes by
+        let mut weight = SampledSpectrum::one();
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+        let mut depth = Depth::default();
+        let mut media = MediumStack::default();
+        // false after a diffuse bounce off a surface that already sampled the
+        // environment directly, so that light isn't counted twice
+        let mut count_env = true;
+        let mut first = Some(first);
+
+        loop {
+            stats::count(|s| s.ray(depth.total as usize));
+            let kind = if depth.total == 0 { RayKind::Camera } else { RayKind::Reflection };
+            let hit = first.take().unwrap_or_else(|| self.hit(&ray, Float::MAX, kind));
+            let mut h = match hit {
+                Some(h) => h,
+                None => {
+                    stats::count(|s| s.escaped += 1);
+                    if count_env {
+                        radiance += weight * spectrum(self.environment.radiance(&ray.direction()), lambda);
+                    }
+                    return radiance;
+                }
+            };
+            h.wavelength = lambda.map(|l| l.hero());
+
+            // work out which media meet at a transmissive surface, or skip the
+            // surface entirely if it's inside a medium with higher priority
+            let mat = &self.materials[h.material];
+            let medium = mat.medium().map(|m| (h.material, m));
+            let mut terminate = false;
+            if let Some((id, m)) = medium {
+                match media.interface(id, m, h.front_face) {
+                    Some(outside) => {
+                        h.outside_ior = outside.at(h.wavelength);
+                        let dispersive = m.ior.is_dispersive() || outside.is_dispersive();
+                        terminate = dispersive && lambda.is_some_and(|l| !l.is_secondary_terminated());
+                    }
+                    None => {
+                        // these don't scatter, but still count so a path
+                        // can't cross hidden surfaces forever
+                        depth.transmission += 1;
+                        if depth.exceeds(&self.max_depth) {
+                            stats::count(|s| s.max_depth += 1);
+                            return radiance;
+                        }
+                        ray = h.spawn_ray(ray.direction());
+                        media.cross(id, m, h.front_face);
+                        continue;
+                    }
+                }
+            }
+
+            // the path only goes on the way the hero wavelength refracted
+            if terminate {
+                lambda = lambda.map(|l| l.terminate_secondary());
+            }
+
+            let first_bounce = aovs.as_deref_mut().filter(|_| depth.total == 0);
+            if let Some(a) = first_bounce {
+                a.depth = (h.point - start).len();
+                a.normal = *h.normal;
+                a.albedo = *mat.albedo(&h);
+                a.position = *h.point;
+                a.object = Some(h.object);
+                a.material = Some(h.material.index());
+            }
+
+            let (scattered, attenuation) = match mat.scatter(&ray, &h) {
+                Some(s) => s,
+                None => {
+                    stats::count(|s| s.absorbed += 1);
+                    return radiance;
+                }
+            };
+            let (env, sampled) = self.sample_environment(&ray, &h);
+            let lights = match aovs.as_deref_mut().filter(|_| depth.total == 0) {
+                Some(a) => {
+                    for (light, l) in self.lights.iter().zip(a.lights.iter_mut()) {
+                        *l = self.sample_light(&ray, &h, light.as_ref());
+                    }
+                    a.lights.iter().copied().sum()
+                }
+                None => self.sample_lights(&ray, &h),
+            };
+            let direct = env + lights;
+            radiance += weight * spectrum(direct, lambda);
+
+            let transmitted = Vec3::dot(scattered.direction(), *h.normal) < 0.0;
+            depth.total += 1;
+            if transmitted {
+                depth.transmission += 1;
+            } else if mat.is_specular() {
+                depth.specular += 1;
+            } else {
+                depth.diffuse += 1;
+            }
+
+            throughput *= *attenuation;
+            let survival = self.survival(depth.total, throughput);
+            if depth.exceeds(&self.max_depth) {
+                stats::count(|s| s.max_depth += 1);
+                return radiance;
+            }
+            if survival < 1.0 && crate::random::rng().gen::<Float>() >= survival {
+                stats::count(|s| s.roulette += 1);
+                return radiance;
+            }
+
+            weight = weight * spectrum(*attenuation, lambda) * (1.0 / survival);
+            if terminate {
+                weight = weight.terminate_secondary();
+            }
+            if let Some((id, m)) = medium.filter(|_| transmitted) {
+                media.cross(id, m, h.front_face);
+            }
+            ray = scattered;
+            count_env = !sampled;
+        }
+    }
+
+    /// The chance of a path with `throughput` going on after `depth` bounces,
+    /// which is one until Russian roulette starts.
+    fn survival(&self, depth: u32, throughput: Vec3) -> Float {
+        match self.roulette_depth {
+            Some(start) if depth > start => {
+                throughput.x().max(throughput.y()).max(throughput.z()).min(1.0)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Next event estimation towards an importance sampled environment.
+    /// Returns the direct light and whether the environment was sampled.
+    fn sample_environment(&self, r: &Ray, h: &HitRecord) -> (Vec3, bool) {
+        let none = (Vec3::origin(), false);
+        if !self.environment.is_sampled() {
+            return none;
+        }
+
+        let mut rng = crate::random::rng();
+        let sample = match self.environment.sample(rng.gen(), rng.gen()) {
+            Some(s) => s,
+            None => return none,
+        };
+        let f = match self.materials[h.material].eval(r, h, &sample.dir) {
+            Some(f) => f,
+            None => return none,
+        };
+
+        if self.occluded(h, sample.dir, Float::MAX) {
+            return (Vec3::origin(), true);
+        }
+
+        (*f * sample.radiance / sample.pdf, true)
+    }
+
+    /// Direct light from every analytic light, with a shadow ray each.
+    fn sample_lights(&self, r: &Ray, h: &HitRecord) -> Vec3 {
+        let mut direct = Vec3::origin();
+        for light in &self.lights {
+            direct += self.sample_light(r, h, light.as_ref());
+        }
+        direct
+    }
+
+    fn sample_light(&self, r: &Ray, h: &HitRecord, light: &dyn Light) -> Vec3 {
+        let sample = match light.sample(&h.point) {
+            Some(s) => s,
+            None => return Vec3::origin(),
+        };
+        let f = match self.materials[h.material].eval(r, h, &sample.dir) {
+            Some(f) => f,
+            None => return Vec3::origin(),
+        };
+
+        if self.occluded(h, sample.dir, sample.dist * (1.0 - SHADOW_MARGIN)) {
+            return Vec3::origin();
+        }
+        *f * sample.radiance
+    }
+
+    /// Whether something blocks the way from `h` along `dir` within `t_max`.
+    /// Shadow rays go straight through transmissive surfaces, so glass
+    /// doesn't cast a solid shadow.
+    fn occluded(&self, h: &HitRecord, dir: Vec3, mut t_max: Float) -> bool {
+        stats::count(|s| s.shadow_rays += 1);
+        let mut shadow = h.spawn_ray(dir);
+
+        // most shadow rays are settled by the first opaque surface found,
+        // and only those crossing media need walking from surface to surface
+        let mut crosses_media = false;
+        let opaque = |b: &HitRecord| {
+            let medium = self.materials[b.material].medium().is_some();
+            crosses_media |= medium;
+            !medium
+        };
+        if self.bvh.any_hit_visible(&shadow, 0.0, t_max, |i| self.sees(i, RayKind::Shadow), opaque) {
+            return true;
+        }
+        if !crosses_media {
+            return false;
+        }
+        for _ in 0..=self.max_depth.transmission {
+            match self.hit(&shadow, t_max, RayKind::Shadow) {
+                None => return false,
+                Some(b) if self.materials[b.material].medium().is_some() => {
+                    t_max -= b.t;
+                    shadow = b.spawn_ray(dir);
+                }
+                Some(_) => return true,
+            }
+        }
+        true
+    }
+}
+
+/// A color as seen along a path: upsampled to the path's wavelengths when
+/// rendering spectrally, or kept as RGB in the first three lanes otherwise.
+fn spectrum(rgb: Vec3, lambda: Option<SampledWavelengths>) -> SampledSpectrum {
+    match lambda {
+        Some(l) => spectrum::sample_rgb(rgb, &l),
+        None => SampledSpectrum([rgb.r(), rgb.g(), rgb.b(), 0.0]),
+    }
+}
+
+/// The media a path is currently inside, in the order it entered them.
+#[derive(Clone, Default)]
+struct MediumStack {
+    entered: Vec<(MaterialId, Medium)>,
+}
+
+impl MediumStack {
+    /// The medium filling the current point: the one with the highest
+    /// priority, or the most recently entered of equal ones.
+    fn current(&self) -> Option<&(MaterialId, Medium)> {
+        self.current_index().map(|i| &self.entered[i])
+    }
+
+    fn current_index(&self) -> Option<usize> {
+        self.entered.iter().enumerate().max_by_key(|(_, e)| e.1.priority).map(|(i, _)| i)
+    }
+
+    /// The refractive index on the far side of the surface of medium `m`
+    /// when entering or leaving it, or `None` if the surface is hidden by a
+    /// medium with higher priority and should be passed straight through.
+    fn interface(&self, id: MaterialId, m: Medium, entering: bool) -> Option<Ior> {
+        let ior = |e: Option<&(MaterialId, Medium)>| e.map_or(Ior::Constant(1.0), |e| e.1.ior);
+        let current = self.current();
+        if entering {
+            return match current {
+                Some(c) if c.1.priority > m.priority => None,
+                _ => Some(ior(current)),
+            };
+        }
+
+        // leaving something the path was never seen to enter, like an
+        // inside-out sphere, which it must have been in all along in place of
+        // the medium it thought it was in
+        if !self.entered.iter().any(|e| e.0 == id) {
+            return Some(ior(self.crossed(id, m, false).current()));
+        }
+        match current {
+            Some(c) if c.0 != id => None,
+            _ => Some(ior(self.crossed(id, m, false).current())),
+        }
+    }
+
+    /// Enters or leaves the medium `m`.
+    fn cross(&mut self, id: MaterialId, m: Medium, entering: bool) {
+        if entering {
+            self.entered.push((id, m));
+        } else if let Some(i) = self.entered.iter().rposition(|e| e.0 == id).or_else(|| self.current_index()) {
+            self.entered.remove(i);
+        }
+    }
+
+    /// The stack after entering or leaving the medium `m`.
+    fn crossed(&self, id: MaterialId, m: Medium, entering: bool) -> MediumStack {
+        let mut next = self.clone();
+        next.cross(id, m, entering);
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::Hit;
+    use crate::light::{PointLight, SpotLight};
+    use crate::background::Constant;
+    use crate::material::{test_materials, Dielectric, Lambertian, Metal};
+    use crate::shape::Plane;
+    use crate::sphere::Sphere;
+    use crate::{Color, Point3};
+
+    /// A gray unit sphere at the origin, and where a ray straight down hits
+    /// its top.
+    fn sphere_top() -> (Renderer, Ray, HitRecord) {
+        let (materials, gray) = test_materials();
+        let world = HitableList::with_vals(vec![Arc::new(Sphere::new(Vec3::origin(), 1.0, gray))]);
+        let renderer = Renderer::new(world, materials, Environment::default()).unwrap();
+        let r = Ray::with_values(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let h = renderer.hit(&r, Float::MAX, RayKind::Camera).unwrap();
+        (renderer, r, h)
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn max_bounce_is_the_default_diffuse_depth() {
+        assert_eq!(MaxDepth::default().diffuse, u32::from(MAX_BOUNCE));
+    }
+
+    #[test]
+    fn depths_exceed_any_of_their_limits() {
+        let max = MaxDepth {
+            diffuse: 2,
+            specular: 3,
+            transmission: 4,
+        };
+        let depth = Depth {
+            total: 9,
+            diffuse: 2,
+            specular: 3,
+            transmission: 4,
+        };
+        assert!(!depth.exceeds(&max));
+        assert!(Depth { diffuse: 3, ..depth }.exceeds(&max));
+        assert!(Depth { specular: 4, ..depth }.exceeds(&max));
+        assert!(Depth { transmission: 5, ..depth }.exceeds(&max));
+    }
+
+    #[test]
+    fn diffuse_spheres_reflect_their_albedo_of_a_uniform_sky() {
+        let (materials, gray) = test_materials();
+        let world = HitableList::with_vals(vec![Arc::new(Sphere::new(Vec3::origin(), 1.0, gray))]);
+        let sky = Environment::new(Arc::new(Constant::new(Vec3::new(1.0, 1.0, 1.0))), 1.0);
+        let renderer = Renderer::new(world, materials, sky).unwrap();
+        let r = Ray::with_values(Point3::new(0.3, 3.0, 0.2), Vec3::new(0.0, -1.0, 0.0));
+
+        let n = 4000;
+        let mut sum = Vec3::origin();
+        for s in 0..n {
+            crate::random::seed_sample(1, 0, s);
+            sum += renderer.color(&r);
+        }
+        let mean = sum / n as Float;
+        assert!((mean.x() - 0.5).abs() < 0.02, "{:?}", mean);
+    }
+
+    #[test]
+    fn paths_between_mirrors_end_at_the_depth_limit() {
+        let mut materials = Materials::new();
+        let mirror = materials.add(Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0)));
+        let world = HitableList::with_vals(vec![
+            Arc::new(Plane::new(Vec3::origin(), Vec3::new(0.0, 1.0, 0.0), mirror)) as Arc<dyn Hit>,
+            Arc::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), mirror)),
+        ]);
+        let mut renderer = Renderer::new(world, materials, Environment::default()).unwrap();
+        // far deeper than the stack would allow if paths recursed
+        renderer.set_max_depth(MaxDepth {
+            specular: 200_000,
+            ..MaxDepth::default()
+        });
+        renderer.set_roulette_depth(None);
+        let r = Ray::with_values(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(renderer.color(&r), Vec3::origin());
+    }
+
+    #[test]
+    fn lights_after_one_that_cant_reach_still_count() {
+        let (mut renderer, r, h) = sphere_top();
+        let (pos, white) = (Vec3::new(0.0, 2.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+        // pointing away from the sphere
+        renderer.add_light(Arc::new(SpotLight::new(pos, Vec3::new(0.0, 10.0, 0.0), white, 10.0, 20.0)));
+        renderer.add_light(Arc::new(PointLight::new(pos, white)));
+        let direct = renderer.sample_lights(&r, &h);
+        assert!(direct.x() > 0.0, "the point light was skipped");
+    }
+
+    fn glass(ior: Float, priority: u32) -> Medium {
+        Medium {
+            ior: Ior::Constant(ior),
+            priority,
+        }
+    }
+
+    fn ids(n: usize) -> Vec<MaterialId> {
+        let mut materials = Materials::new();
+        (0..n).map(|_| materials.add(Arc::new(Lambertian::new(Color::black())))).collect()
+    }
+
+    #[test]
+    fn media_nest_by_priority() {
+        let (id, m) = (ids(2), [glass(1.5, 1), glass(1.33, 0)]);
+        let mut media = MediumStack::default();
+        assert_eq!(media.interface(id[0], m[0], true), Some(Ior::Constant(1.0)));
+        media.cross(id[0], m[0], true);
+        // water inside the glass's surface is hidden until the glass is left
+        assert_eq!(media.interface(id[1], m[1], true), None);
+        media.cross(id[1], m[1], true);
+        assert_eq!(media.interface(id[0], m[0], false), Some(Ior::Constant(1.33)));
+        media.cross(id[0], m[0], false);
+        assert_eq!(media.interface(id[1], m[1], false), Some(Ior::Constant(1.0)));
+    }
+
+    #[test]
+    fn inside_out_spheres_hollow_out_glass() {
+        // through a glass sphere with an inside-out one of the same glass
+        // inside it, which the path crosses from its back first
+        let (id, m) = (ids(2), [glass(1.5, 0), glass(1.5, 0)]);
+        let mut media = MediumStack::default();
+        let mut outside = Vec::new();
+        for &(i, entering) in [(0, true), (1, false), (1, true), (0, false)].iter() {
+            outside.push(media.interface(id[i], m[i], entering));
+            media.cross(id[i], m[i], entering);
+        }
+        assert_eq!(outside, vec![Some(Ior::Constant(1.0)); 4]);
+        assert!(media.current().is_none());
+    }
+
+    #[test]
+    fn shadow_rays_pass_through_glass() {
+        let (mut materials, gray) = test_materials();
+        let glass = materials.add(Arc::new(crate::material::Dielectric::new(1.5)));
+        let world = HitableList::with_vals(vec![
+            Arc::new(Sphere::new(Vec3::origin(), 1.0, gray)) as Arc<dyn Hit>,
+            Arc::new(Sphere::new(Vec3::new(0.0, 3.0, 0.0), 0.5, glass)),
+        ]);
+        let mut renderer = Renderer::new(world, materials, Environment::default()).unwrap();
+        renderer.add_light(Arc::new(PointLight::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(1.0, 1.0, 1.0))));
+        let r = Ray::with_values(Point3::new(0.0, 1.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let h = renderer.hit(&r, Float::MAX, RayKind::Camera).unwrap();
+        assert!(renderer.sample_lights(&r, &h).x() > 0.0);
+    }
+
+    #[test]
+    fn opaque_objects_beyond_glass_still_cast_shadows() {
+        let (mut materials, gray) = test_materials();
+        let glass = materials.add(Arc::new(crate::material::Dielectric::new(1.5)));
+        let world = HitableList::with_vals(vec![
+            Arc::new(Sphere::new(Vec3::origin(), 1.0, gray)) as Arc<dyn Hit>,
+            Arc::new(Sphere::new(Vec3::new(0.0, 3.0, 0.0), 0.5, glass)),
+            // inside the glass, so the shadow ray meets the glass first
+            Arc::new(Sphere::new(Vec3::new(0.0, 3.0, 0.0), 0.2, gray)),
+        ]);
+        let mut renderer = Renderer::new(world, materials, Environment::default()).unwrap();
+        renderer.add_light(Arc::new(PointLight::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(1.0, 1.0, 1.0))));
+        let r = Ray::with_values(Point3::new(0.0, 1.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let h = renderer.hit(&r, Float::MAX, RayKind::Camera).unwrap();
+        assert_eq!(renderer.sample_lights(&r, &h), Vec3::origin());
+    }
+
+    #[test]
+    fn passes_come_from_the_first_bounce() {
+        let (mut renderer, r, h) = sphere_top();
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        renderer.add_light(Arc::new(PointLight::new(Vec3::new(0.0, 2.0, 0.0), white)));
+        renderer.add_light(Arc::new(PointLight::new(Vec3::new(0.0, -2.0, 0.0), white)));
+        let (_, aovs) = renderer.color_and_aovs(&r, Some(h));
+        assert!((aovs.depth - 2.0).abs() < 1e-5);
+        assert!((aovs.normal - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-5);
+        assert_eq!(aovs.object, Some(0));
+        assert_eq!(aovs.material, Some(0));
+        assert_eq!(aovs.albedo, Vec3::new(0.5, 0.5, 0.5));
+        // the light above is what the path took, the one below is in shadow
+        assert_eq!(aovs.lights, vec![renderer.sample_lights(&r, &h), Vec3::origin()]);
+        assert!(aovs.lights[0].x() > 0.0);
+    }
+
+    #[test]
+    fn rays_that_miss_have_empty_passes() {
+        let (mut renderer, _, _) = sphere_top();
+        renderer.add_light(Arc::new(PointLight::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(1.0, 1.0, 1.0))));
+        let up = Ray::with_values(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let (_, aovs) = renderer.color_and_aovs(&up, renderer.first_hit(&up));
+        assert_eq!(aovs.object, None);
+        assert_eq!(aovs.lights, vec![Vec3::origin()]);
+    }
+
+    #[test]
+    fn objects_between_the_point_and_the_light_cast_shadows() {
+        let (mut renderer, r, h) = sphere_top();
+        renderer.add_light(Arc::new(PointLight::new(Vec3::new(0.0, -2.0, 0.0), Vec3::new(1.0, 1.0, 1.0))));
+        assert_eq!(renderer.sample_lights(&r, &h), Vec3::origin());
+    }
+
+    #[test]
+    fn materials_from_another_registry_are_rejected() {
+        let (_, foreign) = test_materials();
+        let world = HitableList::with_vals(vec![Arc::new(Sphere::new(Vec3::origin(), 1.0, foreign))]);
+        assert!(Renderer::new(world, Materials::new(), Environment::default()).is_err());
+
+        let (mut renderer, _, _) = sphere_top();
+        assert!(renderer.set_material(foreign, Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0))).is_err());
+        let own = renderer.world().objects()[0].material_ids()[0];
+        assert!(renderer.set_material(own, Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0))).is_ok());
+    }
+
+    #[test]
+    fn visibility_needs_one_entry_per_object() {
+        let (mut renderer, _, _) = sphere_top();
+        assert!(renderer.set_visibility(Vec::new()).is_err());
+        assert!(renderer.set_visibility(vec![Visibility::all(); 2]).is_err());
+        assert!(renderer.set_visibility(vec![Visibility::none()]).is_ok());
+        let down = Ray::with_values(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(renderer.first_hit(&down).is_none());
+    }
+
+    // how paths were traced before the loop in `trace`, kept to check the
+    // loop adds up the same light
+    fn recursive_color(renderer: &Renderer, r: &Ray) -> Vec3 {
+        let (media, white) = (MediumStack::default(), Vec3::new(1.0, 1.0, 1.0));
+        if renderer.spectral {
+            let lambda = SampledWavelengths::sample(crate::random::rng().gen());
+            recursive_trace(renderer, r, Depth::default(), white, true, &media, Some(lambda)).to_rgb(&lambda)
+        } else {
+            let s = recursive_trace(renderer, r, Depth::default(), white, true, &media, None);
+            Vec3::new(s.0[0], s.0[1], s.0[2])
+        }
+    }
+
+    fn recursive_trace(
+        renderer: &Renderer,
+        r: &Ray,
+        depth: Depth,
+        throughput: Vec3,
+        count_env: bool,
+        media: &MediumStack,
+        lambda: Option<SampledWavelengths>,
+    ) -> SampledSpectrum {
+        let kind = if depth.total == 0 { RayKind::Camera } else { RayKind::Reflection };
+        let mut h = match renderer.hit(r, Float::MAX, kind) {
+            Some(h) => h,
+            None if count_env => return spectrum(renderer.environment.radiance(&r.direction()), lambda),
+            None => return SampledSpectrum::zero(),
+        };
+        h.wavelength = lambda.map(|l| l.hero());
+
+        let mat = &renderer.materials[h.material];
+        let medium = mat.medium().map(|m| (h.material, m));
+        let mut terminate = false;
+        if let Some((id, m)) = medium {
+            match media.interface(id, m, h.front_face) {
+                Some(outside) => {
+                    h.outside_ior = outside.at(h.wavelength);
+                    let dispersive = m.ior.is_dispersive() || outside.is_dispersive();
+                    terminate = dispersive && lambda.is_some_and(|l| !l.is_secondary_terminated());
+                }
+                None => {
+                    let next = Depth {
+                        transmission: depth.transmission + 1,
+                        ..depth
+                    };
+                    if next.exceeds(&renderer.max_depth) {
+                        return SampledSpectrum::zero();
+                    }
+                    let through = h.spawn_ray(r.direction());
+                    let media = media.crossed(id, m, h.front_face);
+                    return recursive_trace(renderer, &through, next, throughput, count_env, &media, lambda);
+                }
+            }
+        }
+        let lambda = if terminate { lambda.map(|l| l.terminate_secondary()) } else { lambda };
+
+        let (scattered, attenuation) = match mat.scatter(r, &h) {
+            Some(s) => s,
+            None => return SampledSpectrum::zero(),
+        };
+        let (env, sampled) = renderer.sample_environment(r, &h);
+        let direct = env + renderer.sample_lights(r, &h);
+
+        let transmitted = Vec3::dot(scattered.direction(), *h.normal) < 0.0;
+        let mut next = Depth {
+            total: depth.total + 1,
+            ..depth
+        };
+        if transmitted {
+            next.transmission += 1;
+        } else if mat.is_specular() {
+            next.specular += 1;
+        } else {
+            next.diffuse += 1;
+        }
+
+        let throughput = throughput * *attenuation;
+        let survival = renderer.survival(next.total, throughput);
+        let mut indirect = if next.exceeds(&renderer.max_depth)
+            || (survival < 1.0 && crate::random::rng().gen::<Float>() >= survival)
+        {
+            SampledSpectrum::zero()
+        } else {
+            let media = match medium {
+                Some((id, m)) if transmitted => media.crossed(id, m, h.front_face),
+                _ => media.clone(),
+            };
+            recursive_trace(renderer, &scattered, next, throughput, !sampled, &media, lambda) * (1.0 / survival)
+        };
+        if terminate {
+            indirect = indirect.terminate_secondary();
+        }
+        spectrum(direct, lambda) + spectrum(*attenuation, lambda) * indirect
+    }
+
+    #[test]
+    fn the_path_loop_matches_the_old_recursion() {
+        let (mut materials, gray) = test_materials();
+        let metal = materials.add(Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)));
+        let glass = materials.add(Arc::new(Dielectric::with_ior(Ior::SF11, 1)));
+        let water = materials.add(Arc::new(Dielectric::new(1.33)));
+        let world = HitableList::with_vals(vec![
+            Arc::new(Sphere::new(Vec3::new(0.0, -100.5, -1.0), 100.0, gray)) as Arc<dyn Hit>,
+            Arc::new(Sphere::new(Vec3::new(-1.0, 0.0, -1.0), 0.5, metal)),
+            Arc::new(Sphere::new(Vec3::new(1.0, 0.0, -1.0), 0.5, glass)),
+            // partly inside the glass, where its surface is hidden
+            Arc::new(Sphere::new(Vec3::new(1.3, 0.0, -1.0), 0.4, water)),
+            Arc::new(Sphere::new(Vec3::new(0.0, 0.0, -1.0), 0.5, gray)),
+        ]);
+        let sky = Environment::new(Arc::new(Constant::new(Vec3::new(0.6, 0.7, 1.0))), 1.0);
+        let mut renderer = Renderer::new(world, materials, sky).unwrap();
+        renderer.add_light(Arc::new(PointLight::new(Vec3::new(0.0, 3.0, 0.0), Vec3::new(4.0, 4.0, 4.0))));
+        renderer.set_roulette_depth(Some(1));
+
+        for spectral in [false, true] {
+            renderer.set_spectral(spectral);
+            for x in -4..=4 {
+                let dir = Vec3::new(x as Float * 0.3, -0.1, -1.0);
+                let r = Ray::with_values(Point3::origin(), dir);
+                for s in 0..16 {
+                    crate::random::seed_sample(7, (x + 4) as u64, s);
+                    let looped = renderer.color(&r);
+                    crate::random::seed_sample(7, (x + 4) as u64, s);
+                    let recursed = recursive_color(&renderer, &r);
+                    let tolerance = 1e3 * Float::EPSILON * (1.0 + recursed.len());
+                    assert!((looped - recursed).len() <= tolerance, "{:?} != {:?}", looped, recursed);
+                }
+            }
+        }
+    }
+}
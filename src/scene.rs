@@ -0,0 +1,407 @@
+//! Scenes as a tree of named nodes.
+//!
+//! Each node has a transform relative to its parent, may hold an object, and
+//! says which kinds of ray see what's in it and below it. A `SceneGraph` also
+//! keeps the materials its objects refer to. Rendering doesn't walk the tree:
+//! `compile` flattens it into a plain list of objects, placed in the world,
+//! for the renderer's BVH.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::background::Environment;
+use super::hit::{Hit, HitableList};
+use super::instance::Instance;
+use super::material::Materials;
+use super::render::Renderer;
+use super::Mat4;
+
+/// What a ray is tracing, for deciding whether it sees an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayKind {
+    /// The first ray of a path, from the camera.
+    Camera,
+    /// Rays towards lights and the environment to see if they're blocked.
+    Shadow,
+    /// Rays scattered off or through a surface.
+    Reflection,
+}
+
+/// Which kinds of ray see an object. Everything is visible by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visibility {
+    pub camera: bool,
+    pub shadow: bool,
+    pub reflection: bool,
+}
+
+impl Visibility {
+    pub fn all() -> Self {
+        Visibility {
+            camera: true,
+            shadow: true,
+            reflection: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        Visibility {
+            camera: false,
+            shadow: false,
+            reflection: false,
+        }
+    }
+
+    pub fn sees(self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Shadow => self.shadow,
+            RayKind::Reflection => self.reflection,
+        }
+    }
+
+    /// Visible only to rays both see it from.
+    pub fn and(self, other: Visibility) -> Self {
+        Visibility {
+            camera: self.camera && other.camera,
+            shadow: self.shadow && other.shadow,
+            reflection: self.reflection && other.reflection,
+        }
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::all()
+    }
+}
+
+/// Numbers each `SceneGraph` made, so IDs from one aren't taken for another's.
+static GRAPHS: AtomicUsize = AtomicUsize::new(0);
+
+/// Refers to a node in a `SceneGraph`. Methods given one from another graph
+/// panic, except `add`, which returns an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    graph: usize,
+    index: usize,
+}
+
+struct Node {
+    name: String,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// From the node's space to its parent's.
+    transform: Mat4,
+    object: Option<Arc<dyn Hit>>,
+    visibility: Visibility,
+}
+
+/// A tree of named nodes under a root called "root", and the materials of
+/// the objects in it.
+pub struct SceneGraph {
+    graph: usize,
+    nodes: Vec<Node>,
+    names: HashMap<String, NodeId>,
+    materials: Materials,
+}
+
+/// A scene graph's objects, placed in the world, in the order `flatten`
+/// found them.
+pub struct Flattened {
+    pub objects: Vec<Arc<dyn Hit>>,
+    /// Which rays see each object, after its ancestors' flags.
+    pub visibility: Vec<Visibility>,
+    /// The node each object came from.
+    pub nodes: Vec<NodeId>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        let mut graph = SceneGraph {
+            graph: GRAPHS.fetch_add(1, Ordering::Relaxed),
+            nodes: Vec::new(),
+            names: HashMap::new(),
+            materials: Materials::new(),
+        };
+        graph.push(None, "root");
+        graph
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId {
+            graph: self.graph,
+            index: 0,
+        }
+    }
+
+    /// Whether `id` refers to one of this graph's nodes.
+    pub fn contains(&self, id: NodeId) -> bool {
+        id.graph == self.graph && id.index < self.nodes.len()
+    }
+
+    /// Adds an empty node called `name` under `parent`. Names are unique
+    /// within a graph, and `parent` must be from it.
+    pub fn add(&mut self, parent: NodeId, name: &str) -> Result<NodeId, String> {
+        if !self.contains(parent) {
+            return Err(format!("node {:?} isn't from this graph", parent));
+        }
+        if self.names.contains_key(name) {
+            return Err(format!("there's already a node called '{}'", name));
+        }
+        let id = self.push(Some(parent), name);
+        self.node_mut(parent).children.push(id);
+        Ok(id)
+    }
+
+    /// Adds a node called `name` holding `object`.
+    pub fn add_object(&mut self, parent: NodeId, name: &str, object: Arc<dyn Hit>) -> Result<NodeId, String> {
+        let id = self.add(parent, name)?;
+        self.set_object(id, Some(object));
+        Ok(id)
+    }
+
+    fn push(&mut self, parent: Option<NodeId>, name: &str) -> NodeId {
+        let id = NodeId {
+            graph: self.graph,
+            index: self.nodes.len(),
+        };
+        self.nodes.push(Node {
+            name: String::from(name),
+            parent,
+            children: Vec::new(),
+            transform: Mat4::identity(),
+            object: None,
+            visibility: Visibility::all(),
+        });
+        self.names.insert(String::from(name), id);
+        id
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        assert!(self.contains(id), "node {:?} isn't from this graph", id);
+        &self.nodes[id.index]
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        assert!(self.contains(id), "node {:?} isn't from this graph", id);
+        &mut self.nodes[id.index]
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.node(id).name
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    pub fn object(&self, id: NodeId) -> Option<&Arc<dyn Hit>> {
+        self.node(id).object.as_ref()
+    }
+
+    pub fn set_object(&mut self, id: NodeId, object: Option<Arc<dyn Hit>>) {
+        self.node_mut(id).object = object;
+    }
+
+    /// From the node's space to its parent's.
+    pub fn transform(&self, id: NodeId) -> &Mat4 {
+        &self.node(id).transform
+    }
+
+    /// Places a node and everything under it relative to its parent. Nodes
+    /// whose transform to the world can't be inverted, like those scaled to
+    /// nothing, are left out of renders.
+    pub fn set_transform(&mut self, id: NodeId, transform: Mat4) {
+        self.node_mut(id).transform = transform;
+    }
+
+    /// From the node's space to the world's.
+    pub fn world_transform(&self, id: NodeId) -> Mat4 {
+        let node = self.node(id);
+        match node.parent {
+            Some(parent) => self.world_transform(parent) * node.transform,
+            None => node.transform,
+        }
+    }
+
+    /// The node's own flags, which also apply to everything under it.
+    pub fn visibility(&self, id: NodeId) -> Visibility {
+        self.node(id).visibility
+    }
+
+    pub fn set_visibility(&mut self, id: NodeId, visibility: Visibility) {
+        self.node_mut(id).visibility = visibility;
+    }
+
+    pub fn materials(&self) -> &Materials {
+        &self.materials
+    }
+
+    pub fn materials_mut(&mut self) -> &mut Materials {
+        &mut self.materials
+    }
+
+    /// Every object in the graph, depth first in the order nodes were
+    /// added. Objects whose node isn't transformed relative to the world
+    /// are kept as they are; the rest are wrapped in an `Instance`.
+    pub fn flatten(&self) -> Flattened {
+        let mut flat = Flattened {
+            objects: Vec::new(),
+            visibility: Vec::new(),
+            nodes: Vec::new(),
+        };
+        let root = &self.nodes[0];
+        self.flatten_node(self.root(), root.transform, root.visibility, &mut flat);
+        flat
+    }
+
+    fn flatten_node(&self, id: NodeId, to_world: Mat4, visibility: Visibility, flat: &mut Flattened) {
+        let node = self.node(id);
+        if let Some(object) = &node.object {
+            let placed = if to_world == Mat4::identity() {
+                Some(object.clone())
+            } else {
+                Instance::new(object.clone(), to_world).map(|i| Arc::new(i) as Arc<dyn Hit>)
+            };
+            if let Some(object) = placed {
+                flat.objects.push(object);
+                flat.visibility.push(visibility);
+                flat.nodes.push(id);
+            }
+        }
+        for &child in &node.children {
+            let c = self.node(child);
+            self.flatten_node(child, to_world * c.transform, visibility.and(c.visibility), flat);
+        }
+    }
+
+    /// A renderer for the graph's objects and materials. Fails if an object
+    /// uses a material that isn't in the graph's `Materials`.
+    pub fn compile(&self, environment: Environment) -> Result<Renderer, String> {
+        let flat = self.flatten();
+        let mut renderer = Renderer::new(HitableList::with_vals(flat.objects), self.materials.clone(), environment)?;
+        renderer.set_visibility(flat.visibility)?;
+        Ok(renderer)
+    }
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        SceneGraph::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{MaterialId, test_materials};
+    use crate::ray::Ray;
+    use crate::sphere::Sphere;
+    use crate::Vec3;
+    use crate::Point3;
+
+    fn gray(graph: &mut SceneGraph) -> MaterialId {
+        let (materials, gray) = test_materials();
+        *graph.materials_mut() = materials;
+        gray
+    }
+
+    fn ball(mat: MaterialId) -> Arc<dyn Hit> {
+        Arc::new(Sphere::new(Vec3::origin(), 1.0, mat))
+    }
+
+    #[test]
+    fn nodes_are_found_by_name() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let a = graph.add(root, "a").unwrap();
+        let b = graph.add(a, "b").unwrap();
+        assert_eq!(graph.find("b"), Some(b));
+        assert_eq!(graph.find("c"), None);
+        assert_eq!(graph.name(b), "b");
+        assert_eq!(graph.parent(b), Some(a));
+        assert_eq!(graph.children(root), &[a]);
+        assert!(graph.add(root, "b").is_err());
+    }
+
+    #[test]
+    fn nodes_from_another_graph_are_rejected() {
+        let mut other = SceneGraph::new();
+        let foreign = other.add(other.root(), "a").unwrap();
+        let mut graph = SceneGraph::new();
+        graph.add(graph.root(), "a").unwrap();
+        assert!(!graph.contains(foreign));
+        assert!(!graph.contains(other.root()));
+        assert!(graph.add(foreign, "b").is_err());
+        assert!(graph.add(other.root(), "b").is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't from this graph")]
+    fn looking_up_a_foreign_node_panics() {
+        let mut other = SceneGraph::new();
+        let foreign = other.add(other.root(), "a").unwrap();
+        let mut graph = SceneGraph::new();
+        graph.add(graph.root(), "a").unwrap();
+        graph.name(foreign);
+    }
+
+    #[test]
+    fn flattening_places_objects_and_combines_visibility() {
+        let mut graph = SceneGraph::new();
+        let mat = gray(&mut graph);
+        let root = graph.root();
+        let group = graph.add(root, "group").unwrap();
+        graph.set_transform(group, Mat4::translation(Vec3::new(0.0, 2.0, 0.0)));
+        graph.set_visibility(group, Visibility { camera: false, ..Visibility::all() });
+        let moved = graph.add_object(group, "moved", ball(mat)).unwrap();
+        graph.set_visibility(moved, Visibility { shadow: false, ..Visibility::all() });
+        let still = graph.add_object(root, "still", ball(mat)).unwrap();
+        let squashed = graph.add_object(root, "squashed", ball(mat)).unwrap();
+        graph.set_transform(squashed, Mat4::scale(Vec3::new(1.0, 0.0, 1.0)));
+
+        let flat = graph.flatten();
+        // the node scaled to nothing is left out
+        assert_eq!(flat.nodes, vec![moved, still]);
+        let moved_visibility = Visibility { camera: false, shadow: false, reflection: true };
+        assert_eq!(flat.visibility, vec![moved_visibility, Visibility::all()]);
+        let bounds = flat.objects[0].bounding_box().unwrap();
+        assert!((bounds.min().y() - 1.0).abs() < 1e-5);
+        assert!(Arc::ptr_eq(&flat.objects[1], graph.object(still).unwrap()));
+        assert_eq!(graph.world_transform(moved), Mat4::translation(Vec3::new(0.0, 2.0, 0.0)));
+    }
+
+    #[test]
+    fn compiled_renderers_hide_what_the_camera_doesnt_see() {
+        let mut graph = SceneGraph::new();
+        let mat = gray(&mut graph);
+        let hidden = graph.add_object(graph.root(), "hidden", ball(mat)).unwrap();
+        graph.set_visibility(hidden, Visibility { camera: false, ..Visibility::all() });
+        let renderer = graph.compile(Environment::default()).unwrap();
+        let r = Ray::with_values(Point3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(renderer.first_hit(&r).is_none());
+
+        graph.set_visibility(hidden, Visibility::all());
+        let renderer = graph.compile(Environment::default()).unwrap();
+        assert!(renderer.first_hit(&r).is_some());
+    }
+
+    #[test]
+    fn compiling_rejects_foreign_materials() {
+        let mut other = SceneGraph::new();
+        let foreign = gray(&mut other);
+        let mut graph = SceneGraph::new();
+        graph.add_object(graph.root(), "ball", ball(foreign)).unwrap();
+        assert!(graph.compile(Environment::default()).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+mod primitives;
+mod ops;
+mod object;
+
+use super::{Float, Vec3};
+
+pub use primitives::{Capsule, RoundBox, Sphere, Torus};
+pub use ops::{Intersection, Repeat, SmoothUnion, Subtraction, Twist, Union};
+pub use object::SdfObject;
+
+/// A signed distance field: negative inside the shape, positive outside, and
+/// never more than the distance to the nearest surface.
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: &Vec3) -> Float;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::aabb::Aabb;
+    use crate::hit::Hit;
+    use crate::material::test_materials;
+    use crate::Ray;
+    use crate::Point3;
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn sphere(x: Float) -> Arc<dyn Sdf> {
+        Arc::new(Sphere::new(Vec3::new(x, 0.0, 0.0), 1.0))
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let origin = Vec3::origin();
+        assert_eq!(Sphere::new(origin, 1.0).distance(&Vec3::new(3.0, 0.0, 0.0)), 2.0);
+        assert_eq!(Sphere::new(origin, 1.0).distance(&origin), -1.0);
+        let cube = RoundBox::new(origin, Vec3::new(1.0, 1.0, 1.0), 0.0);
+        assert!(close(cube.distance(&Vec3::new(2.0, 0.0, 0.0)), 1.0));
+        assert!(close(cube.distance(&Vec3::new(0.5, 0.0, 0.0)), -0.5));
+        let torus = Torus::new(origin, 2.0, 0.5);
+        assert!(close(torus.distance(&Vec3::new(2.0, 0.0, 0.0)), -0.5));
+        assert!(close(torus.distance(&origin), 1.5));
+        let capsule = Capsule::new(origin, Vec3::new(0.0, 2.0, 0.0), 0.5);
+        assert!(close(capsule.distance(&Vec3::new(1.0, 1.0, 0.0)), 0.5));
+        assert!(close(capsule.distance(&Vec3::new(0.0, 3.0, 0.0)), 0.5));
+    }
+
+    #[test]
+    fn boolean_operations() {
+        let p = Vec3::new(0.5, 0.0, 0.0);
+        // p is inside the left sphere and just inside the right one
+        assert!(close(Union::new(sphere(0.0), sphere(1.4)).distance(&p), -0.5));
+        assert!(close(Intersection::new(sphere(0.0), sphere(1.4)).distance(&p), -0.1));
+        assert!(close(Subtraction::new(sphere(0.0), sphere(1.4)).distance(&p), 0.1));
+    }
+
+    #[test]
+    fn smooth_union_only_blends_within_k() {
+        let sharp = SmoothUnion::new(sphere(-2.0), sphere(2.0), 0.0);
+        let smooth = SmoothUnion::new(sphere(-2.0), sphere(2.0), 0.5);
+        let far = Vec3::new(-5.0, 0.0, 0.0);
+        assert!(close(smooth.distance(&far), sharp.distance(&far)));
+        // halfway between, both are 1 away, and blending pulls the surface in
+        let middle = Vec3::origin();
+        assert!(smooth.distance(&middle) < sharp.distance(&middle));
+    }
+
+    #[test]
+    fn repeat_wraps_into_one_cell() {
+        let grid = Repeat::new(sphere(0.0), Vec3::new(4.0, 0.0, 0.0));
+        assert!(close(grid.distance(&Vec3::new(8.0, 0.0, 0.0)), -1.0));
+        assert!(close(grid.distance(&Vec3::new(10.0, 0.0, 0.0)), 1.0));
+        // y isn't repeated
+        assert!(close(grid.distance(&Vec3::new(8.0, 5.0, 0.0)), 4.0));
+    }
+
+    #[test]
+    fn sphere_tracing_finds_the_surface() {
+        let (_, mat) = test_materials();
+        let bounds = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        let object = SdfObject::new(sphere(0.0), bounds, mat);
+
+        let r = Ray::with_values(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let h = object.hit(&r, 0.0, Float::MAX).unwrap();
+        assert!(close(h.t, 4.0));
+        assert!(close(h.normal.z(), -1.0));
+
+        // and the way out for rays starting inside
+        let r = Ray::with_values(Point3::origin(), Vec3::new(0.0, 2.0, 0.0));
+        let h = object.hit(&r, 0.0, Float::MAX).unwrap();
+        assert!(close(h.t, 0.5));
+        assert!(!h.front_face);
+
+        let miss = Ray::with_values(Point3::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(object.hit(&miss, 0.0, Float::MAX).is_none());
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::{Float, Point3, Ray, Vec3};
+use super::Sdf;
+
+const MAX_STEPS: usize = 512;
+const EPSILON: Float = 1e-5;
+
+/// Renders a signed distance field by sphere tracing it inside `bounds`.
+pub struct SdfObject {
+    sdf: Arc<dyn Sdf>,
+    bounds: Aabb,
+    step_scale: Float,
+    mat: MaterialId,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Arc<dyn Sdf>, bounds: Aabb, mat: MaterialId) -> Self {
+        Self {
+            sdf,
+            bounds,
+            step_scale: 1.0,
+            mat,
+        }
+    }
+
+    /// Shrinks every step along the ray, for fields like `Twist` that
+    /// overestimate the distance to the surface.
+    pub fn set_step_scale(&mut self, scale: Float) {
+        self.step_scale = scale;
+    }
+
+    /// Gradient of the field by central differences, which points along
+    /// the outward normal at the surface.
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        let h = EPSILON;
+        let d = |x: Float, y: Float, z: Float| self.sdf.distance(&(*p + Vec3::new(x, y, z)));
+        let grad = Vec3::new(
+            d(h, 0.0, 0.0) - d(-h, 0.0, 0.0),
+            d(0.0, h, 0.0) - d(0.0, -h, 0.0),
+            d(0.0, 0.0, h) - d(0.0, 0.0, -h),
+        );
+        Vec3::unit_vector(grad)
+    }
+}
+
+impl Hit for SdfObject {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (start, end) = self.bounds.clip(r, t_min, t_max)?;
+        let dir_len = r.direction().len();
+
+        // march on the absolute distance so rays starting inside find their
+        // way out through the surface as well
+        let mut t = start;
+        for _ in 0..MAX_STEPS {
+            let point = *r.point_at_param(t);
+            let dist = self.sdf.distance(&point).abs();
+            if dist < EPSILON && t > t_min {
+                // the surface is only found to within EPSILON, and rays
+                // leaving it must start clear of that band
+                let error = Vec3::splat(4.0 * EPSILON) + r.point_error(t);
+                let normal = self.normal(&point);
+                return Some(HitRecord::new(r, t, Point3(point), error, normal, (0.0, 0.0), self.mat));
+            }
+
+            if t >= end {
+                break;
+            }
+            // keep moving even when starting on the surface, but stop at the
+            // end of the bounds, where a tight box has the surface
+            t = (t + self.step_scale * dist.max(EPSILON) / dir_len).min(end);
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds.clone())
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use crate::{Float, Vec3};
+use super::Sdf;
+
+pub struct Union {
+    a: Arc<dyn Sdf>,
+    b: Arc<dyn Sdf>,
+}
+
+impl Union {
+    pub fn new(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>) -> Self {
+        Self {
+            a,
+            b,
+        }
+    }
+}
+
+impl Sdf for Union {
+    fn distance(&self, p: &Vec3) -> Float {
+        self.a.distance(p).min(self.b.distance(p))
+    }
+}
+
+pub struct Intersection {
+    a: Arc<dyn Sdf>,
+    b: Arc<dyn Sdf>,
+}
+
+impl Intersection {
+    pub fn new(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>) -> Self {
+        Self {
+            a,
+            b,
+        }
+    }
+}
+
+impl Sdf for Intersection {
+    fn distance(&self, p: &Vec3) -> Float {
+        self.a.distance(p).max(self.b.distance(p))
+    }
+}
+
+/// `a` with `b` carved out of it.
+pub struct Subtraction {
+    a: Arc<dyn Sdf>,
+    b: Arc<dyn Sdf>,
+}
+
+impl Subtraction {
+    pub fn new(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>) -> Self {
+        Self {
+            a,
+            b,
+        }
+    }
+}
+
+impl Sdf for Subtraction {
+    fn distance(&self, p: &Vec3) -> Float {
+        self.a.distance(p).max(-self.b.distance(p))
+    }
+}
+
+/// A union that blends the two shapes together where they come within
+/// `k` of each other.
+pub struct SmoothUnion {
+    a: Arc<dyn Sdf>,
+    b: Arc<dyn Sdf>,
+    k: Float,
+}
+
+impl SmoothUnion {
+    pub fn new(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>, k: Float) -> Self {
+        Self {
+            a,
+            b,
+            k,
+        }
+    }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: &Vec3) -> Float {
+        let (da, db) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0.0 {
+            return da.min(db);
+        }
+
+        let h = (0.5 + 0.5 * (db - da) / self.k).clamp(0.0, 1.0);
+        db + (da - db) * h - self.k * h * (1.0 - h)
+    }
+}
+
+/// Repeats a shape infinitely on a grid with the given cell size. An axis
+/// with a period of zero isn't repeated.
+pub struct Repeat {
+    sdf: Arc<dyn Sdf>,
+    period: Vec3,
+}
+
+impl Repeat {
+    pub fn new(sdf: Arc<dyn Sdf>, period: Vec3) -> Self {
+        Self {
+            sdf,
+            period,
+        }
+    }
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: &Vec3) -> Float {
+        let wrap = |i: usize| {
+            let c = self.period[i];
+            if c == 0.0 {
+                p[i]
+            } else {
+                (p[i] + 0.5 * c).rem_euclid(c) - 0.5 * c
+            }
+        };
+        self.sdf.distance(&Vec3::new(wrap(0), wrap(1), wrap(2)))
+    }
+}
+
+/// Twists a shape around the y axis by `rate` radians per unit of height.
+/// This stretches distances, so objects using it need a step scale below one.
+pub struct Twist {
+    sdf: Arc<dyn Sdf>,
+    rate: Float,
+}
+
+impl Twist {
+    pub fn new(sdf: Arc<dyn Sdf>, rate: Float) -> Self {
+        Self {
+            sdf,
+            rate,
+        }
+    }
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: &Vec3) -> Float {
+        let (s, c) = (self.rate * p.y()).sin_cos();
+        let q = Vec3::new(c * p.x() - s * p.z(), p.y(), s * p.x() + c * p.z());
+        self.sdf.distance(&q)
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{Float, Vec3};
+use super::Sdf;
+
+pub struct Sphere {
+    center: Vec3,
+    radius: Float,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3, radius: Float) -> Self {
+        Self {
+            center,
+            radius,
+        }
+    }
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, p: &Vec3) -> Float {
+        (*p - self.center).len() - self.radius
+    }
+}
+
+/// An axis aligned box with its edges rounded off by `rounding`.
+pub struct RoundBox {
+    center: Vec3,
+    half_size: Vec3,
+    rounding: Float,
+}
+
+impl RoundBox {
+    pub fn new(center: Vec3, half_size: Vec3, rounding: Float) -> Self {
+        Self {
+            center,
+            half_size,
+            rounding,
+        }
+    }
+}
+
+impl Sdf for RoundBox {
+    fn distance(&self, p: &Vec3) -> Float {
+        let local = *p - self.center;
+        let q = |i: usize| local[i].abs() - self.half_size[i] + self.rounding;
+        let (qx, qy, qz) = (q(0), q(1), q(2));
+
+        let outside = Vec3::new(qx.max(0.0), qy.max(0.0), qz.max(0.0)).len();
+        let inside = qx.max(qy).max(qz).min(0.0);
+        outside + inside - self.rounding
+    }
+}
+
+/// A torus lying flat in the xz plane around `center`.
+pub struct Torus {
+    center: Vec3,
+    major: Float,
+    minor: Float,
+}
+
+impl Torus {
+    pub fn new(center: Vec3, major: Float, minor: Float) -> Self {
+        Self {
+            center,
+            major,
+            minor,
+        }
+    }
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: &Vec3) -> Float {
+        let local = *p - self.center;
+        let ring = local.x().hypot(local.z()) - self.major;
+        ring.hypot(local.y()) - self.minor
+    }
+}
+
+/// A line segment from `a` to `b` swept by a sphere of `radius`.
+pub struct Capsule {
+    a: Vec3,
+    b: Vec3,
+    radius: Float,
+}
+
+impl Capsule {
+    pub fn new(a: Vec3, b: Vec3, radius: Float) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+        }
+    }
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, p: &Vec3) -> Float {
+        let pa = *p - self.a;
+        let ba = self.b - self.a;
+        let h = (Vec3::dot(pa, ba) / ba.sq_len()).clamp(0.0, 1.0);
+        (pa - h * ba).len() - self.radius
+    }
+}
@@ -0,0 +1,152 @@
+use crate::aabb::Aabb;
+use crate::csg::{Interval, Solid};
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::{Float, Point3, Ray, Vec3};
+use crate::ray::gamma;
+use super::{angle_around, disk_extent, onto_plane, perpendicular, solve_quadratic};
+
+/// A cone with a circular base of `radius` at `base` narrowing to `apex`.
+/// The base is closed.
+pub struct Cone {
+    base: Vec3,
+    apex: Vec3,
+    radius: Float,
+    height: Float,
+    axis: Vec3,
+    u_axis: Vec3,
+    mat: MaterialId,
+}
+
+impl Cone {
+    pub fn new(base: Vec3, apex: Vec3, radius: Float, mat: MaterialId) -> Self {
+        let height = (apex - base).len();
+        let axis = (apex - base) / height;
+        let u_axis = perpendicular(axis);
+        Self {
+            base,
+            apex,
+            radius,
+            height,
+            axis,
+            u_axis,
+            mat,
+        }
+    }
+
+    fn side_record(&self, r: &Ray, t: Float) -> HitRecord {
+        let point = *r.point_at_param(t);
+        let local = point - self.base;
+        let y = Vec3::dot(local, self.axis);
+        let radial = local - y * self.axis;
+
+        // the side slopes in by radius over height, so the normal tilts
+        // towards the apex by the same ratio
+        let out = if radial.sq_len() > 0.0 {
+            Vec3::unit_vector(radial)
+        } else {
+            self.u_axis
+        };
+        let normal = Vec3::unit_vector(self.height * out + self.radius * self.axis);
+
+        // every tangent plane passes through the apex, so moving back by the
+        // distance to it lands on the surface and bounds the point's error
+        let point = point - Vec3::dot(point - self.apex, normal) * normal;
+        let size = Vec3::splat(self.height + self.radius);
+        let error = gamma(7) * (point.abs() + self.base.abs() + size);
+
+        let uv = (angle_around(radial, self.u_axis, self.axis), y / self.height);
+        HitRecord::new(r, t, Point3(point), error, normal, uv, self.mat)
+    }
+
+    fn base_record(&self, r: &Ray, t: Float) -> HitRecord {
+        let (point, error) = onto_plane(*r.point_at_param(t), self.base, -self.axis);
+        let local = point - self.base;
+        let uv = (angle_around(local, self.u_axis, self.axis), local.len() / self.radius);
+        HitRecord::new(r, t, Point3(point), error, -self.axis, uv, self.mat)
+    }
+}
+
+impl Cone {
+    /// Every t where the ray's line crosses the surface, unsorted, along with
+    /// whether it was through the base.
+    fn crossings(&self, r: &Ray) -> Vec<(Float, bool)> {
+        let oc = *r.origin() - self.base;
+        let dy = Vec3::dot(r.direction(), self.axis);
+        let oy = Vec3::dot(oc, self.axis);
+        let d_perp = r.direction() - dy * self.axis;
+        let o_perp = oc - oy * self.axis;
+
+        // radius at height y is k * (height - y)
+        let mut crossings = Vec::with_capacity(3);
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let a = d_perp.sq_len() - k2 * dy * dy;
+        let half_b = Vec3::dot(d_perp, o_perp) + k2 * dy * (self.height - oy);
+        let c = o_perp.sq_len() - k2 * (self.height - oy) * (self.height - oy);
+        let side = match solve_quadratic(a, half_b, c) {
+            Some((t0, t1)) => vec![t0, t1],
+            // parallel to the slope, so the line only crosses the cone once
+            None if a == 0.0 && half_b != 0.0 => vec![-c / (2.0 * half_b)],
+            None => Vec::new(),
+        };
+        for t in side {
+            let y = oy + t * dy;
+            if y >= 0.0 && y <= self.height {
+                crossings.push((t, false));
+            }
+        }
+
+        if dy != 0.0 {
+            let t = -oy / dy;
+            if (o_perp + t * d_perp).sq_len() <= self.radius * self.radius {
+                crossings.push((t, true));
+            }
+        }
+
+        crossings
+    }
+
+    fn record(&self, r: &Ray, (t, is_base): (Float, bool)) -> HitRecord {
+        if is_base {
+            self.base_record(r, t)
+        } else {
+            self.side_record(r, t)
+        }
+    }
+}
+
+impl Hit for Cone {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        self.crossings(r)
+            .into_iter()
+            .filter(|c| c.0 > t_min && c.0 < t_max)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|c| self.record(r, c))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let base = Aabb::around(&self.base, &disk_extent(&self.axis, self.radius));
+        let apex = Aabb::new(self.apex, self.apex);
+        Some(Aabb::surrounding(&base, &apex))
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
+
+impl Solid for Cone {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        // convex, so the line is inside between the first and last crossing
+        let crossings = self.crossings(r);
+        let enter = crossings.iter().min_by(|a, b| a.0.total_cmp(&b.0));
+        let exit = crossings.iter().max_by(|a, b| a.0.total_cmp(&b.0));
+        match (enter, exit) {
+            (Some(&enter), Some(&exit)) if enter.0 < exit.0 => {
+                vec![Interval::new(self.record(r, enter), self.record(r, exit))]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::aabb::Aabb;
+use crate::csg::{Interval, Solid};
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::ray::gamma;
+use crate::{Float, Point3, Ray, Vec3};
+
+// where a ray crosses a face, as (t, axis, sign of the normal along the axis)
+type Crossing = (Float, usize, Float);
+
+/// An axis aligned box between two corners.
+pub struct Cuboid {
+    min: Vec3,
+    max: Vec3,
+    mat: MaterialId,
+}
+
+impl Cuboid {
+    pub fn new(min: Vec3, max: Vec3, mat: MaterialId) -> Self {
+        Self {
+            min,
+            max,
+            mat,
+        }
+    }
+
+    fn record(&self, r: &Ray, t: Float, axis: usize, sign: Float) -> HitRecord {
+        // snap onto the face so the error is only in the other two axes
+        let point = *r.point_at_param(t);
+        let mut e = [point.x(), point.y(), point.z()];
+        e[axis] = if sign > 0.0 { self.max[axis] } else { self.min[axis] };
+        let point = Vec3::new(e[0], e[1], e[2]);
+        let error = gamma(3) * point.abs();
+
+        let mut n = [0.0; 3];
+        n[axis] = sign;
+
+        // texture coordinates come from the two axes lying in the face
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let u = (point[a] - self.min[a]) / (self.max[a] - self.min[a]);
+        let v = (point[b] - self.min[b]) / (self.max[b] - self.min[b]);
+
+        HitRecord::new(r, t, Point3(point), error, Vec3::new(n[0], n[1], n[2]), (u, v), self.mat)
+    }
+
+    /// Where the ray's line enters and leaves the box.
+    fn slabs(&self, r: &Ray) -> Option<(Crossing, Crossing)> {
+        // slab test keeping track of which face the ray enters and leaves by
+        let mut near = (Float::NEG_INFINITY, 0, 0.0);
+        let mut far = (Float::INFINITY, 0, 0.0);
+        for axis in 0..3 {
+            // parallel to the slab, where the divisions below would give NaN
+            // for rays starting on one of its faces
+            if r.direction()[axis] == 0.0 {
+                if r.origin()[axis] < self.min[axis] || r.origin()[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inv_d = 1.0 / r.direction()[axis];
+            let t0 = (self.min[axis] - r.origin()[axis]) * inv_d;
+            let t1 = (self.max[axis] - r.origin()[axis]) * inv_d;
+            let (t_in, t_out, sign) = if inv_d < 0.0 {
+                (t1, t0, 1.0)
+            } else {
+                (t0, t1, -1.0)
+            };
+
+            if t_in > near.0 {
+                near = (t_in, axis, sign);
+            }
+            if t_out < far.0 {
+                far = (t_out, axis, -sign);
+            }
+        }
+
+        if near.0 > far.0 {
+            return None;
+        }
+        Some((near, far))
+    }
+}
+
+impl Hit for Cuboid {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (near, far) = self.slabs(r)?;
+        if near.0 > t_min && near.0 < t_max {
+            return Some(self.record(r, near.0, near.1, near.2));
+        }
+        if far.0 > t_min && far.0 < t_max {
+            return Some(self.record(r, far.0, far.1, far.2));
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
+
+impl Solid for Cuboid {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        match self.slabs(r) {
+            Some((near, far)) => vec![Interval::new(
+                self.record(r, near.0, near.1, near.2),
+                self.record(r, far.0, far.1, far.2),
+            )],
+            None => Vec::new(),
+        }
+    }
+}
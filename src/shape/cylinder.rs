@@ -0,0 +1,142 @@
+use crate::aabb::Aabb;
+use crate::csg::{Interval, Solid};
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::{Float, Point3, Ray, Vec3};
+use crate::ray::gamma;
+use super::{angle_around, disk_extent, onto_plane, perpendicular, solve_quadratic};
+
+/// A cylinder running from `base` to `top`, closed at both ends.
+pub struct Cylinder {
+    base: Vec3,
+    top: Vec3,
+    radius: Float,
+    height: Float,
+    axis: Vec3,
+    u_axis: Vec3,
+    mat: MaterialId,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, top: Vec3, radius: Float, mat: MaterialId) -> Self {
+        let height = (top - base).len();
+        let axis = (top - base) / height;
+        let u_axis = perpendicular(axis);
+        Self {
+            base,
+            top,
+            radius,
+            height,
+            axis,
+            u_axis,
+            mat,
+        }
+    }
+
+    fn side_record(&self, r: &Ray, t: Float) -> HitRecord {
+        let local = *r.point_at_param(t) - self.base;
+        let y = Vec3::dot(local, self.axis);
+        let normal = Vec3::unit_vector(local - y * self.axis);
+
+        // push the point out to exactly the radius to bound its error
+        let point = self.base + y * self.axis + self.radius * normal;
+        let size = Vec3::splat(self.height + self.radius);
+        let error = gamma(7) * (point.abs() + self.base.abs() + size);
+
+        let uv = (angle_around(normal, self.u_axis, self.axis), y / self.height);
+        HitRecord::new(r, t, Point3(point), error, normal, uv, self.mat)
+    }
+
+    fn cap_record(&self, r: &Ray, t: Float, top: bool) -> HitRecord {
+        let (center, normal) = if top {
+            (self.top, self.axis)
+        } else {
+            (self.base, -self.axis)
+        };
+        let (point, error) = onto_plane(*r.point_at_param(t), center, normal);
+
+        let local = point - center;
+        let uv = (angle_around(local, self.u_axis, self.axis), local.len() / self.radius);
+        HitRecord::new(r, t, Point3(point), error, normal, uv, self.mat)
+    }
+}
+
+impl Cylinder {
+    /// Every t where the ray's line crosses the surface, unsorted, along with
+    /// which cap was hit (`None` for the side).
+    fn crossings(&self, r: &Ray) -> Vec<(Float, Option<bool>)> {
+        let oc = *r.origin() - self.base;
+        let dy = Vec3::dot(r.direction(), self.axis);
+        let oy = Vec3::dot(oc, self.axis);
+        let d_perp = r.direction() - dy * self.axis;
+        let o_perp = oc - oy * self.axis;
+
+        let mut crossings = Vec::with_capacity(4);
+        let a = d_perp.sq_len();
+        let half_b = Vec3::dot(d_perp, o_perp);
+        let c = o_perp.sq_len() - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, half_b, c) {
+            for t in [t0, t1] {
+                let y = oy + t * dy;
+                if y >= 0.0 && y <= self.height {
+                    crossings.push((t, None));
+                }
+            }
+        }
+
+        if dy != 0.0 {
+            for (plane, top) in [(0.0, false), (self.height, true)] {
+                let t = (plane - oy) / dy;
+                if (o_perp + t * d_perp).sq_len() <= self.radius * self.radius {
+                    crossings.push((t, Some(top)));
+                }
+            }
+        }
+
+        crossings
+    }
+
+    fn record(&self, r: &Ray, (t, cap): (Float, Option<bool>)) -> HitRecord {
+        match cap {
+            None => self.side_record(r, t),
+            Some(top) => self.cap_record(r, t, top),
+        }
+    }
+}
+
+impl Hit for Cylinder {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        self.crossings(r)
+            .into_iter()
+            .filter(|c| c.0 > t_min && c.0 < t_max)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|c| self.record(r, c))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = disk_extent(&self.axis, self.radius);
+        Some(Aabb::surrounding(
+            &Aabb::around(&self.base, &extent),
+            &Aabb::around(&self.top, &extent),
+        ))
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
+
+impl Solid for Cylinder {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        // convex, so the line is inside between the first and last crossing
+        let crossings = self.crossings(r);
+        let enter = crossings.iter().min_by(|a, b| a.0.total_cmp(&b.0));
+        let exit = crossings.iter().max_by(|a, b| a.0.total_cmp(&b.0));
+        match (enter, exit) {
+            (Some(&enter), Some(&exit)) if enter.0 < exit.0 => {
+                vec![Interval::new(self.record(r, enter), self.record(r, exit))]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
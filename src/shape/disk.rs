@@ -0,0 +1,62 @@
+use crate::aabb::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::{Float, Point3, Ray, Vec3};
+use super::{angle_around, disk_extent, onto_plane, perpendicular};
+
+/// A flat disk facing `normal`. u runs around the rim and v outwards from
+/// the center.
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: Float,
+    u_axis: Vec3,
+    mat: MaterialId,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: Float, mat: MaterialId) -> Self {
+        let normal = Vec3::unit_vector(normal);
+        let u_axis = perpendicular(normal);
+        Self {
+            center,
+            normal,
+            radius,
+            u_axis,
+            mat,
+        }
+    }
+}
+
+impl Hit for Disk {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let denom = Vec3::dot(self.normal, r.direction());
+        if denom == 0.0 {
+            return None;
+        }
+
+        let t = Vec3::dot(self.normal, self.center - *r.origin()) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let (point, error) = onto_plane(*r.point_at_param(t), self.center, self.normal);
+
+        let local = point - self.center;
+        let dist = local.len();
+        if dist > self.radius {
+            return None;
+        }
+
+        let uv = (angle_around(local, self.u_axis, self.normal), dist / self.radius);
+        Some(HitRecord::new(r, t, Point3(point), error, self.normal, uv, self.mat))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::around(&self.center, &disk_extent(&self.normal, self.radius)))
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
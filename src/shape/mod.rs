@@ -0,0 +1,201 @@
+mod plane;
+mod disk;
+mod rect;
+mod cuboid;
+mod cylinder;
+mod cone;
+mod torus;
+mod triangle;
+
+use crate::consts::PI;
+use crate::ray::gamma;
+
+use super::{Float, Vec3};
+
+pub use plane::Plane;
+pub use disk::Disk;
+pub use rect::{XyRect, XzRect, YzRect};
+pub use cuboid::Cuboid;
+pub use cylinder::Cylinder;
+pub use cone::Cone;
+pub use torus::Torus;
+pub use triangle::Triangle;
+
+/// Some unit vector perpendicular to `w`, used as the reference direction
+/// for angular texture coordinates.
+fn perpendicular(w: Vec3) -> Vec3 {
+    let helper = if w.x().abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    Vec3::unit_vector(Vec3::cross(w, helper))
+}
+
+/// Angle of `p` around the axis `w` measured from `u`, mapped to [0, 1).
+fn angle_around(p: Vec3, u: Vec3, w: Vec3) -> Float {
+    let v = Vec3::cross(w, u);
+    let phi = Vec3::dot(p, v).atan2(Vec3::dot(p, u));
+    (phi + PI) / (2.0 * PI)
+}
+
+/// Projects a point found by intersecting a ray onto the plane through
+/// `origin` facing `n`, returning it with its error bound. Snapping back onto
+/// the surface keeps the error small however inaccurate the ray's t was.
+fn onto_plane(point: Vec3, origin: Vec3, n: Vec3) -> (Vec3, Vec3) {
+    let point = point - Vec3::dot(point - origin, n) * n;
+    (point, gamma(5) * (point.abs() + origin.abs()))
+}
+
+/// Padding so flat shapes don't get bounding boxes with zero thickness.
+const FLAT_PADDING: Float = 0.0001;
+
+/// Half the extent of a disk of `radius` facing `n` along each axis.
+fn disk_extent(n: &Vec3, radius: Float) -> Vec3 {
+    let extent = |c: Float| (radius * (1.0 - c * c).max(0.0).sqrt()).max(FLAT_PADDING);
+    Vec3::new(extent(n.x()), extent(n.y()), extent(n.z()))
+}
+
+/// Roots of `a*t^2 + 2*half_b*t + c` in ascending order.
+fn solve_quadratic(a: Float, half_b: Float, c: Float) -> Option<(Float, Float)> {
+    if a == 0.0 {
+        return None;
+    }
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // avoid cancellation between half_b and the root, which would lose all
+    // precision in the smaller solution
+    let sqrt_d = discriminant.sqrt();
+    let q = -half_b - sqrt_d.copysign(half_b);
+    let (t0, t1) = (q / a, if q != 0.0 { c / q } else { 0.0 });
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::{Hit, HitRecord};
+    use crate::material::{MaterialId, test_materials};
+    use crate::Ray;
+    use crate::Point3;
+
+    fn mat() -> MaterialId {
+        test_materials().1
+    }
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() <= 1e-5 * a.abs().max(1.0)
+    }
+
+    fn ray(origin: (Float, Float, Float), dir: (Float, Float, Float)) -> Ray {
+        Ray::with_values(Point3::new(origin.0, origin.1, origin.2), Vec3::new(dir.0, dir.1, dir.2))
+    }
+
+    #[test]
+    fn quadratic_roots_are_sorted() {
+        assert_eq!(solve_quadratic(1.0, -1.5, 2.0), Some((1.0, 2.0)));
+        assert_eq!(solve_quadratic(-1.0, 1.5, -2.0), Some((1.0, 2.0)));
+        assert_eq!(solve_quadratic(1.0, 0.0, 1.0), None);
+        assert_eq!(solve_quadratic(0.0, 1.0, 1.0), None);
+    }
+
+    #[test]
+    fn small_quadratic_root_keeps_its_precision() {
+        // t^2 - 2e4 t + 1, whose small root the textbook formula loses
+        let (t0, t1) = solve_quadratic(1.0, -1e4, 1.0).unwrap();
+        assert!(close(t0, 5e-5), "{}", t0);
+        assert!(close(t1, 2e4), "{}", t1);
+    }
+
+    #[test]
+    fn cuboid_is_hit_on_the_facing_side() {
+        let cube = Cuboid::new(Vec3::origin(), Vec3::new(1.0, 1.0, 1.0), mat());
+        let h = cube.hit(&ray((0.5, 0.5, -2.0), (0.0, 0.0, 1.0)), 0.0, Float::MAX).unwrap();
+        assert_eq!(h.t, 2.0);
+        assert_eq!(*h.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!(h.front_face);
+
+        let inside = cube.hit(&ray((0.5, 0.5, 0.5), (0.0, 0.0, 1.0)), 0.0, Float::MAX).unwrap();
+        assert_eq!(inside.t, 0.5);
+        assert!(!inside.front_face);
+    }
+
+    #[test]
+    fn cuboid_handles_rays_along_a_face() {
+        let cube = Cuboid::new(Vec3::origin(), Vec3::new(1.0, 1.0, 1.0), mat());
+        // starting in the plane of the x = 0 face, with no x component
+        let h = cube.hit(&ray((0.0, 0.5, -2.0), (0.0, 0.0, 1.0)), 0.0, Float::MAX).unwrap();
+        assert_eq!(h.t, 2.0);
+        assert_eq!(*h.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!(cube.hit(&ray((-0.1, 0.5, -2.0), (0.0, 0.0, 1.0)), 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn cylinder_caps_stop_rays_along_the_axis() {
+        let cylinder = Cylinder::new(Vec3::origin(), Vec3::new(0.0, 2.0, 0.0), 1.0, mat());
+        let h = cylinder.hit(&ray((0.5, 5.0, 0.0), (0.0, -1.0, 0.0)), 0.0, Float::MAX).unwrap();
+        assert_eq!(h.t, 3.0);
+        assert_eq!(*h.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!(cylinder.hit(&ray((1.5, 5.0, 0.0), (0.0, -1.0, 0.0)), 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn cylinder_side_is_hit_from_outside() {
+        let cylinder = Cylinder::new(Vec3::origin(), Vec3::new(0.0, 2.0, 0.0), 1.0, mat());
+        let h = cylinder.hit(&ray((-3.0, 1.0, 0.0), (1.0, 0.0, 0.0)), 0.0, Float::MAX).unwrap();
+        assert!(close(h.t, 2.0));
+        assert!(close(h.normal.x(), -1.0));
+    }
+
+    #[test]
+    fn cone_side_is_hit_by_rays_parallel_to_its_slope() {
+        let cone = Cone::new(Vec3::origin(), Vec3::new(0.0, 1.0, 0.0), 1.0, mat());
+        // parallel to the far side's slope, so the quadratic is linear
+        let h = cone.hit(&ray((-1.0, 1.5, 0.0), (1.0, -1.0, 0.0)), 0.0, Float::MAX).unwrap();
+        assert!(close(h.t, 0.75), "{}", h.t);
+        assert!(h.normal.x() < 0.0 && h.normal.y() > 0.0);
+    }
+
+    #[test]
+    fn cone_base_is_hit_along_the_axis() {
+        let cone = Cone::new(Vec3::origin(), Vec3::new(0.0, 1.0, 0.0), 1.0, mat());
+        let h = cone.hit(&ray((0.2, -1.0, 0.0), (0.0, 1.0, 0.0)), 0.0, Float::MAX).unwrap();
+        assert_eq!(h.t, 1.0);
+        assert_eq!(*h.normal, Vec3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn flat_shapes_are_bounded_only_where_they_exist() {
+        let m = mat();
+        let down = (0.0, 0.0, -1.0);
+        let disk = Disk::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0), 1.0, m);
+        assert!(disk.hit(&ray((0.5, 0.5, 1.0), down), 0.0, Float::MAX).is_some());
+        assert!(disk.hit(&ray((0.8, 0.8, 1.0), down), 0.0, Float::MAX).is_none());
+
+        let rect = XyRect::new(0.0, 1.0, 0.0, 2.0, 0.0, m);
+        let h = rect.hit(&ray((0.5, 1.5, 1.0), down), 0.0, Float::MAX).unwrap();
+        assert_eq!(h.t, 1.0);
+        assert_eq!((h.u, h.v), (0.5, 0.75));
+        assert!(rect.hit(&ray((0.5, 2.5, 1.0), down), 0.0, Float::MAX).is_none());
+
+        let plane = Plane::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0), m);
+        assert!(plane.hit(&ray((100.0, -50.0, 1.0), down), 0.0, Float::MAX).is_some());
+        assert!(plane.bounding_box().is_none());
+    }
+
+    struct Everywhere;
+
+    impl Hit for Everywhere {
+        fn hit(&self, _r: &Ray, _t_min: Float, _t_max: Float) -> Option<HitRecord> {
+            None
+        }
+    }
+
+    #[test]
+    fn objects_are_unbounded_unless_they_say_otherwise() {
+        assert!(Everywhere.bounding_box().is_none());
+    }
+}
@@ -0,0 +1,60 @@
+use crate::aabb::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::{Float, Point3, Ray, Vec3};
+use super::{onto_plane, perpendicular};
+
+/// An infinite plane through `point`. Texture coordinates repeat every
+/// world unit.
+pub struct Plane {
+    point: Vec3,
+    normal: Vec3,
+    u_axis: Vec3,
+    v_axis: Vec3,
+    mat: MaterialId,
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, mat: MaterialId) -> Self {
+        let normal = Vec3::unit_vector(normal);
+        let u_axis = perpendicular(normal);
+        let v_axis = Vec3::cross(normal, u_axis);
+        Self {
+            point,
+            normal,
+            u_axis,
+            v_axis,
+            mat,
+        }
+    }
+}
+
+impl Hit for Plane {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let denom = Vec3::dot(self.normal, r.direction());
+        if denom == 0.0 {
+            return None;
+        }
+
+        let t = Vec3::dot(self.normal, self.point - *r.origin()) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let (point, error) = onto_plane(*r.point_at_param(t), self.point, self.normal);
+
+        let local = point - self.point;
+        let u = Vec3::dot(local, self.u_axis);
+        let v = Vec3::dot(local, self.v_axis);
+        let uv = (u - u.floor(), v - v.floor());
+        Some(HitRecord::new(r, t, Point3(point), error, self.normal, uv, self.mat))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
@@ -0,0 +1,141 @@
+use crate::aabb::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::ray::gamma;
+use crate::{Float, Point3, Ray, Vec3};
+use super::FLAT_PADDING;
+
+// Rectangles lying in a plane where one axis is fixed at `k`. `a` and `b` are
+// the indices of the two in-plane axes and `c` the fixed one.
+struct AxisRect {
+    a: (usize, Float, Float),
+    b: (usize, Float, Float),
+    c: usize,
+    k: Float,
+}
+
+impl AxisRect {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<(Float, Vec3, Vec3, (Float, Float))> {
+        let t = (self.k - r.origin()[self.c]) / r.direction()[self.c];
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let point = *r.point_at_param(t);
+        let (a, a0, a1) = self.a;
+        let (b, b0, b1) = self.b;
+        if point[a] < a0 || point[a] > a1 || point[b] < b0 || point[b] > b1 {
+            return None;
+        }
+
+        // the fixed axis is known exactly, which leaves only rounding in
+        // the other two
+        let mut e = [point.x(), point.y(), point.z()];
+        e[self.c] = self.k;
+        let point = Vec3::new(e[0], e[1], e[2]);
+        let error = gamma(3) * point.abs();
+
+        let uv = ((point[a] - a0) / (a1 - a0), (point[b] - b0) / (b1 - b0));
+        Some((t, point, error, uv))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let mut min = [0.0; 3];
+        let mut max = [0.0; 3];
+        for &(axis, lo, hi) in &[self.a, self.b, (self.c, self.k - FLAT_PADDING, self.k + FLAT_PADDING)] {
+            min[axis] = lo;
+            max[axis] = hi;
+        }
+        Aabb::new(Vec3::new(min[0], min[1], min[2]), Vec3::new(max[0], max[1], max[2]))
+    }
+}
+
+/// Rectangle in the plane z = k, facing +z.
+pub struct XyRect {
+    rect: AxisRect,
+    mat: MaterialId,
+}
+
+impl XyRect {
+    pub fn new(x0: Float, x1: Float, y0: Float, y1: Float, k: Float, mat: MaterialId) -> Self {
+        Self {
+            rect: AxisRect { a: (0, x0, x1), b: (1, y0, y1), c: 2, k },
+            mat,
+        }
+    }
+}
+
+impl Hit for XyRect {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, point, error, uv) = self.rect.hit(r, t_min, t_max)?;
+        Some(HitRecord::new(r, t, Point3(point), error, Vec3::new(0.0, 0.0, 1.0), uv, self.mat))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.rect.bounding_box())
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
+
+/// Rectangle in the plane y = k, facing +y.
+pub struct XzRect {
+    rect: AxisRect,
+    mat: MaterialId,
+}
+
+impl XzRect {
+    pub fn new(x0: Float, x1: Float, z0: Float, z1: Float, k: Float, mat: MaterialId) -> Self {
+        Self {
+            rect: AxisRect { a: (0, x0, x1), b: (2, z0, z1), c: 1, k },
+            mat,
+        }
+    }
+}
+
+impl Hit for XzRect {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, point, error, uv) = self.rect.hit(r, t_min, t_max)?;
+        Some(HitRecord::new(r, t, Point3(point), error, Vec3::new(0.0, 1.0, 0.0), uv, self.mat))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.rect.bounding_box())
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
+
+/// Rectangle in the plane x = k, facing +x.
+pub struct YzRect {
+    rect: AxisRect,
+    mat: MaterialId,
+}
+
+impl YzRect {
+    pub fn new(y0: Float, y1: Float, z0: Float, z1: Float, k: Float, mat: MaterialId) -> Self {
+        Self {
+            rect: AxisRect { a: (1, y0, y1), b: (2, z0, z1), c: 0, k },
+            mat,
+        }
+    }
+}
+
+impl Hit for YzRect {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, point, error, uv) = self.rect.hit(r, t_min, t_max)?;
+        Some(HitRecord::new(r, t, Point3(point), error, Vec3::new(1.0, 0.0, 0.0), uv, self.mat))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.rect.bounding_box())
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
@@ -0,0 +1,318 @@
+use crate::consts::PI;
+
+use crate::aabb::Aabb;
+use crate::csg::{Interval, Solid};
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::ray::gamma;
+use crate::{Float, Point3, Ray, Vec3};
+
+/// A torus around `center` lying flat in the xz plane. `major` is the
+/// distance from the center to the middle of the tube and `minor` the radius
+/// of the tube.
+pub struct Torus {
+    center: Vec3,
+    major: Float,
+    minor: Float,
+    mat: MaterialId,
+}
+
+impl Torus {
+    pub fn new(center: Vec3, major: Float, minor: Float, mat: MaterialId) -> Self {
+        Self {
+            center,
+            major,
+            minor,
+            mat,
+        }
+    }
+}
+
+impl Torus {
+    /// Every t where the ray's line crosses the surface, in ascending order.
+    fn crossings(&self, r: &Ray) -> Vec<Float> {
+        let dir_len = r.direction().len();
+        let d = r.direction() / dir_len;
+
+        // start the quartic from close to the torus to keep its coefficients
+        // well conditioned for rays that come from far away
+        let bound = self.major + self.minor;
+        let oc = *r.origin() - self.center;
+        let to_center = -Vec3::dot(oc, d);
+        let closest = (oc + to_center * d).sq_len();
+        if closest > bound * bound {
+            return Vec::new();
+        }
+        let shift = to_center - bound;
+
+        // and solve in units of the torus' size, since the solvers' tolerances
+        // are absolute
+        let o = (oc + shift * d) / bound;
+        let (minor, major) = (self.minor / bound, self.major / bound);
+        let (r2, big_r2) = (minor * minor, major * major);
+        let m = Vec3::dot(o, d);
+        let k = o.sq_len() + big_r2 - r2;
+        let mut roots: Vec<Float> = solve_quartic(
+            4.0 * m,
+            4.0 * m * m + 2.0 * k - 4.0 * big_r2 * (d.x() * d.x() + d.z() * d.z()),
+            4.0 * m * k - 8.0 * big_r2 * (o.x() * d.x() + o.z() * d.z()),
+            k * k - 4.0 * big_r2 * (o.x() * o.x() + o.z() * o.z()),
+        )
+        .into_iter()
+        .map(|s| (s * bound + shift) / dir_len)
+        .collect();
+
+        roots.sort_by(Float::total_cmp);
+        roots
+    }
+
+    fn record(&self, r: &Ray, t: Float) -> HitRecord {
+        let local = *r.point_at_param(t) - self.center;
+        let ring_angle = local.z().atan2(local.x());
+        let ring = Vec3::new(ring_angle.cos(), 0.0, ring_angle.sin());
+        let tube = local - self.major * ring;
+        let normal = Vec3::unit_vector(tube);
+
+        // project out to exactly the tube radius to bound the point's error.
+        // The quartic's roots are only accurate to a small fraction of the
+        // torus' size though, so rays leaving need more room than that.
+        let point = self.center + self.major * ring + self.minor * normal;
+        let size = self.major + self.minor;
+        let error = gamma(9) * (point.abs() + self.center.abs()) + Vec3::splat(gamma(128) * size);
+
+        let tube_angle = tube.y().atan2(Vec3::dot(tube, ring));
+        let uv = ((ring_angle + PI) / (2.0 * PI), (tube_angle + PI) / (2.0 * PI));
+        HitRecord::new(r, t, Point3(point), error, normal, uv, self.mat)
+    }
+}
+
+impl Hit for Torus {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let t = self.crossings(r).into_iter().find(|t| *t > t_min && *t < t_max)?;
+        Some(self.record(r, t))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.major + self.minor;
+        Some(Aabb::around(&self.center, &Vec3::new(outer, self.minor, outer)))
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
+
+impl Solid for Torus {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        // which spans between crossings are inside is decided from their
+        // middles rather than by pairing crossings up, since a grazing hit
+        // may be found once, twice or not at all
+        let crossings = self.crossings(r);
+        let mut intervals = Vec::new();
+        let mut enter = None;
+        for span in crossings.windows(2) {
+            let inside = self.contains(*r.point_at_param(0.5 * (span[0] + span[1])));
+            match enter {
+                None if inside => enter = Some(span[0]),
+                Some(t) if !inside => {
+                    intervals.push(Interval::new(self.record(r, t), self.record(r, span[0])));
+                    enter = None;
+                }
+                _ => {}
+            }
+        }
+        if let (Some(t), Some(&last)) = (enter, crossings.last()) {
+            intervals.push(Interval::new(self.record(r, t), self.record(r, last)));
+        }
+        intervals
+    }
+}
+
+impl Torus {
+    fn contains(&self, p: Vec3) -> bool {
+        let local = p - self.center;
+        let ring = (local.x() * local.x() + local.z() * local.z()).sqrt() - self.major;
+        ring * ring + local.y() * local.y() < self.minor * self.minor
+    }
+}
+
+/// Real roots of the monic quartic `t^4 + a*t^3 + b*t^2 + c*t + d`, using
+/// Ferrari's method followed by a couple of Newton steps to clean up the
+/// precision lost along the way.
+fn solve_quartic(a: Float, b: Float, c: Float, d: Float) -> Vec<Float> {
+    // substitute t = y - a/4 to get the depressed quartic y^4 + p*y^2 + q*y + r
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = 1.0 / 8.0 * sq_a * a - 1.0 / 2.0 * a * b + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + 1.0 / 16.0 * sq_a * b - 1.0 / 4.0 * a * c + d;
+
+    let mut roots = Vec::with_capacity(4);
+    if r.abs() < 1e-12 {
+        // y * (y^3 + p*y + q) = 0
+        roots.push(0.0);
+        roots.extend(solve_cubic(0.0, p, q));
+    } else {
+        // resolvent cubic, any real root z gives a factorization into quadratics
+        let z = solve_cubic(-p / 2.0, -r, r * p / 2.0 - q * q / 8.0)[0];
+
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        if u < -1e-12 || v < -1e-12 {
+            return Vec::new();
+        }
+        let u = u.max(0.0).sqrt();
+        let v = v.max(0.0).sqrt();
+
+        let (s1, s2) = if q < 0.0 { (-v, v) } else { (v, -v) };
+        roots.extend(solve_monic_quadratic(s1, z - u));
+        roots.extend(solve_monic_quadratic(s2, z + u));
+    }
+
+    let f = |t: Float| (((t + a) * t + b) * t + c) * t + d;
+    let df = |t: Float| ((4.0 * t + 3.0 * a) * t + 2.0 * b) * t + c;
+    roots
+        .into_iter()
+        .map(|y| {
+            // the closed form can be far off, so polish until it stops
+            // improving; rays leaving the surface rely on the root at zero
+            // being accurate to avoid hitting it again
+            let mut t = y - a / 4.0;
+            for _ in 0..8 {
+                let slope = df(t);
+                if slope == 0.0 {
+                    break;
+                }
+                let step = f(t) / slope;
+                t -= step;
+                if step.abs() <= Float::EPSILON * t.abs() {
+                    break;
+                }
+            }
+            t
+        })
+        .collect()
+}
+
+/// Real roots of `t^2 + p*t + q`.
+fn solve_monic_quadratic(p: Float, q: Float) -> Vec<Float> {
+    let half_p = p / 2.0;
+    let discriminant = half_p * half_p - q;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+
+    let sqrt_d = discriminant.sqrt();
+    vec![-half_p - sqrt_d, -half_p + sqrt_d]
+}
+
+/// Real roots of `t^3 + a*t^2 + b*t + c`, always at least one.
+fn solve_cubic(a: Float, b: Float, c: Float) -> Vec<Float> {
+    // substitute t = y - a/3 to get y^3 + 3*p*y + 2*q
+    let sq_a = a * a;
+    let p = (-1.0 / 3.0 * sq_a + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - 1.0 / 3.0 * a * b + c) / 2.0;
+    let cb_p = p * p * p;
+    let discriminant = q * q + cb_p;
+    let shift = a / 3.0;
+
+    if discriminant.abs() < 1e-14 {
+        if q.abs() < 1e-14 {
+            return vec![-shift];
+        }
+        let u = (-q).cbrt();
+        return vec![2.0 * u - shift, -u - shift];
+    }
+
+    if discriminant < 0.0 {
+        // three real roots
+        let phi = 1.0 / 3.0 * (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos();
+        let t = 2.0 * (-p).sqrt();
+        return vec![
+            t * phi.cos() - shift,
+            -t * (phi + PI / 3.0).cos() - shift,
+            -t * (phi - PI / 3.0).cos() - shift,
+        ];
+    }
+
+    let sqrt_d = discriminant.sqrt();
+    vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt() - shift]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_materials;
+
+    fn sorted(mut roots: Vec<Float>) -> Vec<Float> {
+        roots.sort_by(Float::total_cmp);
+        roots
+    }
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn quartic_with_four_real_roots() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        let roots = sorted(solve_quartic(-10.0, 35.0, -50.0, 24.0));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip(&[1.0, 2.0, 3.0, 4.0]) {
+            assert!(close(*root, *expected), "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn quartic_with_a_root_at_zero() {
+        // t (t + 1)(t - 2)(t - 5)
+        let roots = sorted(solve_quartic(-6.0, 3.0, 10.0, 0.0));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip(&[-1.0, 0.0, 2.0, 5.0]) {
+            assert!(close(*root, *expected), "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn quartic_without_real_roots() {
+        // (t^2 + 1)(t^2 + 4)
+        assert!(solve_quartic(0.0, 5.0, 0.0, 4.0).is_empty());
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (t - 1)(t - 2)(t + 3)
+        let roots = sorted(solve_cubic(0.0, -7.0, 6.0));
+        assert_eq!(roots.len(), 3);
+        for (root, expected) in roots.iter().zip(&[-3.0, 1.0, 2.0]) {
+            assert!(close(*root, *expected), "{:?}", roots);
+        }
+        // t^3 + t + 1 crosses zero once
+        assert_eq!(solve_cubic(0.0, 1.0, 1.0).len(), 1);
+    }
+
+    #[test]
+    fn ray_through_the_middle_crosses_the_tube_four_times() {
+        let (_, mat) = test_materials();
+        let torus = Torus::new(Vec3::origin(), 2.0, 0.5, mat);
+        let r = Ray::with_values(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let crossings = torus.crossings(&r);
+        assert_eq!(crossings.len(), 4);
+        for (t, expected) in crossings.iter().zip(&[2.5, 3.5, 6.5, 7.5]) {
+            assert!(close(*t, *expected), "{:?}", crossings);
+        }
+
+        let h = torus.hit(&r, 0.0, Float::MAX).unwrap();
+        assert!(close(h.normal.x(), -1.0));
+        // leaving the tube, so the normal is turned back to face the ray
+        let h = torus.hit(&r, 3.0, Float::MAX).unwrap();
+        assert!(close(h.t, 3.5) && close(h.normal.x(), -1.0) && !h.front_face);
+    }
+
+    #[test]
+    fn ray_through_the_hole_misses() {
+        let (_, mat) = test_materials();
+        let torus = Torus::new(Vec3::origin(), 2.0, 0.5, mat);
+        let r = Ray::with_values(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(torus.hit(&r, 0.0, Float::MAX).is_none());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::aabb::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::MaterialId;
+use crate::packet::{lanes, PacketHits, RayPacket, Vec3Lanes};
+use crate::{Float, Point3, Ray, Vec3};
+use super::{onto_plane, FLAT_PADDING};
+
+/// A triangle, seen from both sides. u and v are the barycentric weights of
+/// the second and third corners.
+pub struct Triangle {
+    p0: Vec3,
+    e1: Vec3,
+    e2: Vec3,
+    normal: Vec3,
+    mat: MaterialId,
+}
+
+impl Triangle {
+    /// The normal faces the side the corners run counterclockwise around.
+    pub fn new(p0: Vec3, p1: Vec3, p2: Vec3, mat: MaterialId) -> Self {
+        let (e1, e2) = (p1 - p0, p2 - p0);
+        Self {
+            p0,
+            e1,
+            e2,
+            normal: Vec3::unit_vector(Vec3::cross(e1, e2)),
+            mat,
+        }
+    }
+
+    fn record(&self, r: &Ray, t: Float, uv: (Float, Float)) -> HitRecord {
+        let (point, error) = onto_plane(*r.point_at_param(t), self.p0, self.normal);
+        HitRecord::new(r, t, Point3(point), error, self.normal, uv, self.mat)
+    }
+}
+
+impl Hit for Triangle {
+    // Möller-Trumbore
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let p = Vec3::cross(r.direction(), self.e2);
+        let det = Vec3::dot(self.e1, p);
+        if det == 0.0 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = *r.origin() - self.p0;
+        let u = Vec3::dot(s, p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = Vec3::cross(s, self.e1);
+        let v = Vec3::dot(r.direction(), q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = Vec3::dot(self.e2, q) * inv_det;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        Some(self.record(r, t, (u, v)))
+    }
+
+    fn hit_packet(&self, packet: &RayPacket, t_min: Float, hits: &mut PacketHits) {
+        // the same sums as `hit` for every lane at once, so each ray finds
+        // exactly the same t
+        let d = packet.directions();
+        let (e1, e2) = (Vec3Lanes::splat(self.e1), Vec3Lanes::splat(self.e2));
+        let p = Vec3Lanes::cross(&d, &e2);
+        let det = Vec3Lanes::dot(&e1, &p);
+        let inv_det = lanes(|i| 1.0 / det[i]);
+
+        let s = packet.origins() - Vec3Lanes::splat(self.p0);
+        let s_p = Vec3Lanes::dot(&s, &p);
+        let u = lanes(|i| s_p[i] * inv_det[i]);
+        let q = Vec3Lanes::cross(&s, &e1);
+        let (d_q, e2_q) = (Vec3Lanes::dot(&d, &q), Vec3Lanes::dot(&e2, &q));
+        let v = lanes(|i| d_q[i] * inv_det[i]);
+        let t = lanes(|i| e2_q[i] * inv_det[i]);
+
+        // a zero determinant leaves u, v and t infinite or NaN, which fail
+        // these too
+        let t_max = hits.t_max();
+        let found = lanes(|i| {
+            let inside = (u[i] >= 0.0) & (u[i] <= 1.0) & (v[i] >= 0.0) & (u[i] + v[i] <= 1.0);
+            if inside & (t[i] > t_min) & (t[i] < t_max[i]) { t[i] } else { Float::NAN }
+        });
+
+        for (lane, t) in found.iter().enumerate() {
+            if !t.is_nan() {
+                hits.add(lane, self.record(packet.ray(lane), *t, (u[lane], v[lane])));
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (p1, p2) = (self.p0 + self.e1, self.p0 + self.e2);
+        let padding = Vec3::new(FLAT_PADDING, FLAT_PADDING, FLAT_PADDING);
+        Some(Aabb::new(
+            Vec3::min(self.p0, Vec3::min(p1, p2)) - padding,
+            Vec3::max(self.p0, Vec3::max(p1, p2)) + padding,
+        ))
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_materials;
+
+    /// The triangle (0,0,0), (1,0,0), (0,1,0), facing +z.
+    fn triangle() -> Triangle {
+        let (_, mat) = test_materials();
+        Triangle::new(Vec3::origin(), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), mat)
+    }
+
+    fn down_at(x: Float, y: Float) -> Ray {
+        Ray::with_values(Point3::new(x, y, 2.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn hits_inside_with_barycentric_coordinates() {
+        let h = triangle().hit(&down_at(0.25, 0.5), 0.0, Float::MAX).unwrap();
+        assert!((h.t - 2.0).abs() < 1e-6);
+        assert_eq!(*h.point, Vec3::new(0.25, 0.5, 0.0));
+        assert!((h.u - 0.25).abs() < 1e-6 && (h.v - 0.5).abs() < 1e-6);
+        assert_eq!(*h.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(h.front_face);
+    }
+
+    #[test]
+    fn is_seen_from_both_sides() {
+        let up = Ray::with_values(Point3::new(0.2, 0.2, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let h = triangle().hit(&up, 0.0, Float::MAX).unwrap();
+        assert!(!h.front_face);
+        assert_eq!(*h.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn misses_outside_parallel_and_out_of_range() {
+        let tri = triangle();
+        assert!(tri.hit(&down_at(0.6, 0.6), 0.0, Float::MAX).is_none());
+        assert!(tri.hit(&down_at(-0.1, 0.5), 0.0, Float::MAX).is_none());
+        let along = Ray::with_values(Point3::new(-1.0, 0.2, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(tri.hit(&along, 0.0, Float::MAX).is_none());
+        assert!(tri.hit(&down_at(0.2, 0.2), 0.0, 1.5).is_none());
+        assert!(tri.hit(&down_at(0.2, 0.2), 2.5, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn packets_find_the_same_hits_as_single_rays() {
+        let tri = triangle();
+        // inside, on the edges and corners, outside and along the plane
+        let rays = [
+            down_at(0.25, 0.5),
+            down_at(0.5, 0.5),
+            down_at(0.0, 0.0),
+            down_at(1.0, 0.0),
+            down_at(0.7, 0.7),
+            Ray::with_values(Point3::new(0.1, 0.1, -3.0), Vec3::new(0.01, 0.02, 1.0)),
+            Ray::with_values(Point3::new(-1.0, 0.2, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+        ];
+        let packet = RayPacket::new(&rays);
+        let mut hits = PacketHits::new(&packet, Float::MAX);
+        tri.hit_packet(&packet, 0.0, &mut hits);
+        let records = hits.into_records();
+        for (lane, r) in rays.iter().enumerate() {
+            let scalar = tri.hit(r, 0.0, Float::MAX);
+            assert_eq!(records[lane].map(|h| (h.t, h.u, h.v)), scalar.map(|h| (h.t, h.u, h.v)), "lane {}", lane);
+        }
+        assert!(records[..4].iter().all(Option::is_some));
+        assert!(records[7].is_none());
+    }
+}
@@ -0,0 +1,310 @@
+//! Spectral rendering support: wavelength sampling, upsampling RGB colors to
+//! spectra, and converting the sampled spectrum back through CIE XYZ.
+
+use std::ops::{Add, AddAssign, Index, Mul};
+
+use super::{Float, Mat3, Vec3};
+
+/// Wavelengths carried by each path.
+pub const N_WAVELENGTHS: usize = 4;
+
+/// The range paths sample, which is what the RGB to spectrum tables cover.
+/// The eye is barely sensitive past its red end.
+pub const LAMBDA_MIN: Float = 380.0;
+pub const LAMBDA_MAX: Float = 720.0;
+
+/// Sodium D line, the wavelength refractive indices are usually quoted at and
+/// the one used when rendering in RGB.
+pub const LAMBDA_D: Float = 589.3;
+
+/// A hero wavelength plus others spaced evenly through the visible range,
+/// so one random number covers the spectrum (Wilkie et al. 2014).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledWavelengths {
+    lambda: [Float; N_WAVELENGTHS],
+    terminated: bool,
+}
+
+impl SampledWavelengths {
+    pub fn sample(u: Float) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let mut lambda = [hero; N_WAVELENGTHS];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            *l = hero + i as Float * range / N_WAVELENGTHS as Float;
+            if *l > LAMBDA_MAX {
+                *l -= range;
+            }
+        }
+        SampledWavelengths {
+            lambda,
+            terminated: false,
+        }
+    }
+
+    /// The wavelength that decides anything only one of them can, like the
+    /// direction light takes through a prism.
+    pub fn hero(&self) -> Float {
+        self.lambda[0]
+    }
+
+    /// Marks the path as only carrying the hero wavelength from here on,
+    /// matching `SampledSpectrum::terminate_secondary` on the way back.
+    pub fn terminate_secondary(&self) -> Self {
+        SampledWavelengths {
+            terminated: true,
+            ..*self
+        }
+    }
+
+    pub fn is_secondary_terminated(&self) -> bool {
+        self.terminated
+    }
+
+    /// Density each wavelength was sampled with.
+    pub fn pdf(&self) -> Float {
+        1.0 / (LAMBDA_MAX - LAMBDA_MIN)
+    }
+}
+
+impl Index<usize> for SampledWavelengths {
+    type Output = Float;
+
+    fn index(&self, i: usize) -> &Float {
+        &self.lambda[i]
+    }
+}
+
+/// Values of a spectrum at the wavelengths of one path. When rendering in
+/// RGB the same type carries red, green and blue in the first three lanes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledSpectrum(pub [Float; N_WAVELENGTHS]);
+
+impl SampledSpectrum {
+    pub const fn zero() -> Self {
+        SampledSpectrum([0.0; N_WAVELENGTHS])
+    }
+
+    pub const fn one() -> Self {
+        SampledSpectrum([1.0; N_WAVELENGTHS])
+    }
+
+    /// Only keeps the hero wavelength, after something like refraction sent
+    /// the others where they wouldn't have gone. It's scaled up so the
+    /// estimate stays unbiased.
+    pub fn terminate_secondary(&self) -> Self {
+        let mut s = SampledSpectrum::zero();
+        s.0[0] = self.0[0] * N_WAVELENGTHS as Float;
+        s
+    }
+
+    /// Converts to CIE XYZ, normalized so a constant spectrum of one has a
+    /// luminance of one.
+    pub fn to_xyz(&self, lambda: &SampledWavelengths) -> Vec3 {
+        let mut xyz = Vec3::origin();
+        for (i, s) in self.0.iter().enumerate() {
+            xyz += *s * cie_xyz(lambda[i]);
+        }
+        let norm = N_WAVELENGTHS as Float * lambda.pdf();
+        xyz / norm / CIE_INTEGRAL
+    }
+
+    /// Converts to linear sRGB, with equal energy white mapping to white.
+    pub fn to_rgb(&self, lambda: &SampledWavelengths) -> Vec3 {
+        XYZ_E_TO_SRGB * self.to_xyz(lambda)
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn add(self, o: SampledSpectrum) -> SampledSpectrum {
+        let mut r = self;
+        r += o;
+        r
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, o: SampledSpectrum) {
+        for (a, b) in self.0.iter_mut().zip(o.0.iter()) {
+            *a += b;
+        }
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn mul(self, o: SampledSpectrum) -> SampledSpectrum {
+        let mut r = self;
+        for (a, b) in r.0.iter_mut().zip(o.0.iter()) {
+            *a *= b;
+        }
+        r
+    }
+}
+
+impl Mul<Float> for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn mul(self, t: Float) -> SampledSpectrum {
+        let mut r = self;
+        for a in r.0.iter_mut() {
+            *a *= t;
+        }
+        r
+    }
+}
+
+/// Integrals of the color matching functions below over the sampled range.
+const CIE_INTEGRAL: Vec3 = Vec3::new(106.761, 106.912, 106.825);
+
+/// The CIE 1931 2° color matching functions at `lambda` nm, using the
+/// multi-lobe Gaussian fit of Wyman, Sloan and Shirley (2013).
+pub fn cie_xyz(lambda: Float) -> Vec3 {
+    let g = |mu: Float, s1: Float, s2: Float| {
+        let s = if lambda < mu { s1 } else { s2 };
+        let t = (lambda - mu) / s;
+        (-0.5 * t * t).exp()
+    };
+
+    let x = 1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7)
+        - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+    Vec3::new(x, y, z)
+}
+
+/// XYZ to linear sRGB, Bradford adapted from illuminant E to D65 so flat
+/// spectra come out neutral.
+const XYZ_E_TO_SRGB: Mat3 = Mat3::new([
+    [3.146_251, -1.666_124, -0.480_127],
+    [-0.995_535, 1.955_763, 0.039_772],
+    [0.063_598, -0.214_597, 1.150_999],
+]);
+
+/// Smits' tables split the sampled range into bins of 34 nm.
+const SMITS_BINS: usize = 10;
+
+#[rustfmt::skip]
+mod smits {
+    use crate::Float;
+    use super::SMITS_BINS;
+
+    pub const WHITE: [Float; SMITS_BINS] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+    pub const CYAN: [Float; SMITS_BINS] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+    pub const MAGENTA: [Float; SMITS_BINS] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+    pub const YELLOW: [Float; SMITS_BINS] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+    pub const RED: [Float; SMITS_BINS] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+    pub const GREEN: [Float; SMITS_BINS] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+    pub const BLUE: [Float; SMITS_BINS] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+}
+
+/// Value at `lambda` nm of a spectrum that looks like the linear RGB color
+/// `rgb`, using Smits' "An RGB to Spectrum Conversion for Reflectances".
+/// Values above one, like light sources, scale the same spectra up.
+pub fn rgb_to_spectrum(rgb: Vec3, lambda: Float) -> Float {
+    let bin = ((lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * SMITS_BINS as Float)
+        .max(0.0) as usize;
+    let bin = bin.min(SMITS_BINS - 1);
+    let (r, g, b) = (rgb.r(), rgb.g(), rgb.b());
+
+    // the smallest channel is white, the middle one the complement of the
+    // largest and the rest the largest primary on its own
+    let (white, mid, primary, complement, primary_basis) = if r <= g && r <= b {
+        if g <= b {
+            (r, g - r, b - g, &smits::CYAN, &smits::BLUE)
+        } else {
+            (r, b - r, g - b, &smits::CYAN, &smits::GREEN)
+        }
+    } else if g <= r && g <= b {
+        if r <= b {
+            (g, r - g, b - r, &smits::MAGENTA, &smits::BLUE)
+        } else {
+            (g, b - g, r - b, &smits::MAGENTA, &smits::RED)
+        }
+    } else if r <= g {
+        (b, r - b, g - r, &smits::YELLOW, &smits::GREEN)
+    } else {
+        (b, g - b, r - g, &smits::YELLOW, &smits::RED)
+    };
+
+    white * smits::WHITE[bin] + mid * complement[bin] + primary * primary_basis[bin]
+}
+
+/// Upsamples `rgb` at every wavelength of a path.
+pub fn sample_rgb(rgb: Vec3, lambda: &SampledWavelengths) -> SampledSpectrum {
+    let mut s = SampledSpectrum::zero();
+    for (i, v) in s.0.iter_mut().enumerate() {
+        *v = rgb_to_spectrum(rgb, lambda[i]);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wavelengths_stay_in_range_and_evenly_spaced() {
+        for &u in [0.0, 0.3, 0.999].iter() {
+            let lambda = SampledWavelengths::sample(u);
+            let mut sorted: Vec<Float> = (0..N_WAVELENGTHS).map(|i| lambda[i]).collect();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            for l in &sorted {
+                assert!(*l >= LAMBDA_MIN && *l <= LAMBDA_MAX, "{} out of range", l);
+            }
+            let step = (LAMBDA_MAX - LAMBDA_MIN) / N_WAVELENGTHS as Float;
+            for pair in sorted.windows(2) {
+                assert!((pair[1] - pair[0] - step).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn the_tables_span_the_sampled_range() {
+        let blue = Vec3::new(0.0, 0.0, 1.0);
+        let width = (LAMBDA_MAX - LAMBDA_MIN) / SMITS_BINS as Float;
+        assert_eq!(rgb_to_spectrum(blue, LAMBDA_MIN), smits::BLUE[0]);
+        assert_eq!(rgb_to_spectrum(blue, LAMBDA_MIN + 3.5 * width), smits::BLUE[3]);
+        assert_eq!(rgb_to_spectrum(blue, LAMBDA_MAX - 1.0), smits::BLUE[SMITS_BINS - 1]);
+    }
+
+    #[test]
+    fn white_round_trips_to_white() {
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let mut sum = Vec3::origin();
+        let n = 1000;
+        for i in 0..n {
+            let lambda = SampledWavelengths::sample((i as Float + 0.5) / n as Float);
+            sum += sample_rgb(white, &lambda).to_rgb(&lambda);
+        }
+        let rgb = sum / n as Float;
+        for c in [rgb.r(), rgb.g(), rgb.b()].iter() {
+            assert!((c - 1.0).abs() < 0.02, "{}", rgb);
+        }
+    }
+
+    #[test]
+    fn primaries_upsample_to_their_part_of_the_spectrum() {
+        let red = Vec3::new(1.0, 0.0, 0.0);
+        assert!(rgb_to_spectrum(red, 700.0) > 0.9);
+        assert!(rgb_to_spectrum(red, 450.0) < 0.1);
+        let blue = Vec3::new(0.0, 0.0, 1.0);
+        assert!(rgb_to_spectrum(blue, 420.0) > 0.9 && rgb_to_spectrum(blue, 650.0) < 0.1);
+        // brighter colors scale the same spectra
+        assert!((rgb_to_spectrum(red * 4.0, 700.0) - 4.0 * rgb_to_spectrum(red, 700.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn color_matching_functions_peak_where_they_should() {
+        let peak = |f: &dyn Fn(Vec3) -> Float| {
+            let at = |l: i32| f(cie_xyz(l as Float));
+            (LAMBDA_MIN as i32..LAMBDA_MAX as i32).max_by(|&a, &b| at(a).total_cmp(&at(b))).unwrap()
+        };
+        assert!((peak(&|v| v.x()) - 600).abs() < 5);
+        assert!((peak(&|v| v.y()) - 555).abs() < 5);
+        assert!((peak(&|v| v.z()) - 445).abs() < 5);
+    }
+}
@@ -1,55 +1,216 @@
-
 //use std::rc::Rc;
-use std::sync::Arc;
+use crate::consts::PI;
 
+use super::aabb::Aabb;
+use super::csg::{Interval, Solid};
 use super::hit::{Hit, HitRecord};
-use super::{Ray, Vec3};
-use super::material::Material;
+use super::packet::{lanes, PacketHits, RayPacket, Vec3Lanes};
+use super::ray::gamma;
+use super::{Float, Point3, Ray, Vec3};
+use super::material::MaterialId;
 
 pub struct Sphere {
     center: Vec3,
-    radius: f64,
-    mat: Arc<dyn Material>,
+    radius: Float,
+    mat: MaterialId,
 }
 
 impl Sphere {
-    pub fn new(center: Vec3, radius: f64, mat: Arc<dyn Material + Sync + Send>) -> Self {
+    pub fn new(center: Vec3, radius: Float, mat: MaterialId) -> Self {
         Sphere {
-            center: center,
-            radius: radius,
-            mat: mat,
+            center,
+            radius,
+            mat,
         }
     }
+
+    /// Where a ray enters and leaves the ball, in order. Rays that only
+    /// graze it don't cross it.
+    fn crossings(&self, origin: Vec3, dir: Vec3) -> Option<(Float, Float)> {
+        let oc = origin - self.center;
+        let a = Vec3::dot(dir, dir);
+        let half_b = Vec3::dot(oc, dir);
+        let (oc_oc, rr) = (Vec3::dot(oc, oc), self.radius * self.radius);
+        let c = oc_oc - rr;
+        if a == 0.0 {
+            return None;
+        }
+        // most rays miss by far more than the textbook b^2 - ac can be off
+        // by, which settles them before the careful form below
+        if half_b * half_b - a * c < -gamma(16) * (half_b * half_b + a * (oc_oc + rr)) {
+            return None;
+        }
+
+        // b^2 - ac from how close the ray passes to the center, as the two
+        // terms cancel badly for small or distant spheres
+        let v = oc - (half_b / a) * dir;
+        let discriminant = a * (rr - Vec3::dot(v, v));
+        if discriminant <= 0.0 {
+            return None;
+        }
+        let q = -half_b - discriminant.sqrt().copysign(half_b);
+        let (t0, t1) = (q / a, c / q);
+        Some((t0.min(t1), t0.max(t1)))
+    }
+
+    fn record(&self, r: &Ray, t: Float) -> HitRecord {
+        // projecting the point back onto the surface bounds its error much
+        // more tightly than the error in t would
+        let offset = *r.point_at_param(t) - self.center;
+        let point = self.center + offset * (self.radius.abs() / offset.len());
+        let error = gamma(5) * ((point - self.center).abs() + self.center.abs());
+
+        let normal = (point - self.center) / self.radius;
+        let uv = sphere_uv(&normal);
+        HitRecord::new(r, t, Point3(point), error, normal, uv, self.mat)
+    }
 }
 
 impl Hit for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = r.origin() - &self.center;
-        let a = Vec3::dot(r.direction(), r.direction());
-        let b = Vec3::dot(&oc, r.direction());
-        let c = Vec3::dot(&oc, &oc) - self.radius * self.radius;
-
-        let discriminant = b * b - a * c;
-
-        if discriminant > 0.0 {
-            // try smaller t first
-            let t = (-b - discriminant.sqrt()) / a;
-            if t > t_min && t < t_max {
-                let point = r.point_at_param(t);
-                let normal = &(r.point_at_param(t) - &self.center) / self.radius;
-
-                return Some(HitRecord::new(t, point, normal, self.mat.clone()))
-            }
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        self.hit_t(r, t_min, t_max).map(|t| self.record(r, t))
+    }
+
+    fn hit_t(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let (near, far) = self.crossings(*r.origin(), r.direction())?;
+        [near, far].iter().copied().find(|&t| t > t_min && t < t_max)
+    }
 
-            // try larger t
-            let t = (-b + discriminant.sqrt()) / a;
-            if t > t_min && t < t_max {
-                let point = r.point_at_param(t);
-                let normal = (r.point_at_param(t) - &self.center) / self.radius;
-                return Some(HitRecord::new(t, point, normal, self.mat.clone()))
+    fn hit_packet(&self, packet: &RayPacket, t_min: Float, hits: &mut PacketHits) {
+        // `crossings` for every lane at once, step for step, so each ray
+        // finds exactly the t it would alone
+        let d = packet.directions();
+        let oc = packet.origins() - Vec3Lanes::splat(self.center);
+        let rr = self.radius * self.radius;
+        let a = Vec3Lanes::dot(&d, &d);
+        let half_b = Vec3Lanes::dot(&oc, &d);
+        let oc_oc = Vec3Lanes::dot(&oc, &oc);
+        let c = lanes(|i| oc_oc[i] - rr);
+        let k = lanes(|i| half_b[i] / a[i]);
+        let v = oc - d * k;
+        let vv = Vec3Lanes::dot(&v, &v);
+        let discriminant = lanes(|i| a[i] * (rr - vv[i]));
+        let q = lanes(|i| -half_b[i] - discriminant[i].sqrt().copysign(half_b[i]));
+        let (t0, t1) = (lanes(|i| q[i] / a[i]), lanes(|i| c[i] / q[i]));
+
+        // the nearer crossing in range, as masks rather than branches so the
+        // choice vectorizes too
+        let t_max = hits.t_max();
+        let in_range = |t: Float, i: usize| (t > t_min) & (t < t_max[i]);
+        let (near, far) = (lanes(|i| t0[i].min(t1[i])), lanes(|i| t0[i].max(t1[i])));
+        let t = lanes(|i| if in_range(near[i], i) { near[i] } else { far[i] });
+        let found = lanes(|i| {
+            let crosses = (a[i] != 0.0) & (discriminant[i] > 0.0);
+            if crosses & in_range(t[i], i) { t[i] } else { Float::NAN }
+        });
+
+        for (lane, t) in found.iter().enumerate() {
+            if !t.is_nan() {
+                hits.add(lane, self.record(packet.ray(lane), *t));
             }
         }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        Some(Aabb::around(&self.center, &Vec3::new(r, r, r)))
+    }
+
+    fn material_ids(&self) -> Vec<MaterialId> {
+        vec![self.mat]
+    }
+}
+
+impl Solid for Sphere {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        let crossings = self
+            .crossings(*r.origin(), r.direction())
+            .map(|(t0, t1)| (self.record(r, t0), self.record(r, t1)));
+
+        if self.radius >= 0.0 {
+            return crossings.map(|(enter, exit)| Interval::new(enter, exit)).into_iter().collect();
+        }
+        // a negative radius turns the sphere inside out, so the solid is
+        // everything outside the ball
+        let (before, after) = (self.at_infinity(r, Float::NEG_INFINITY), self.at_infinity(r, Float::INFINITY));
+        match crossings {
+            Some((exit, enter)) => vec![Interval::new(before, exit), Interval::new(enter, after)],
+            None => vec![Interval::new(before, after)],
+        }
+    }
+}
+
+impl Sphere {
+    /// Where an unbounded interval starts or ends. No ray reaches it, so
+    /// only its t means anything.
+    fn at_infinity(&self, r: &Ray, t: Float) -> HitRecord {
+        let normal = Vec3::unit_vector(r.direction());
+        HitRecord::new(r, t, Point3(Vec3::splat(t)), Vec3::origin(), normal, (0.0, 0.0), self.mat)
+    }
+}
+
+/// Longitude/latitude coordinates of a point on the unit sphere, with v
+/// running from the bottom pole to the top.
+pub(crate) fn sphere_uv(p: &Vec3) -> (Float, Float) {
+    let phi = (-p.z()).atan2(p.x()) + PI;
+    let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+    (phi / (2.0 * PI), theta / PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_materials;
+    use crate::packet::WIDTH;
+
+    fn sphere(center: Vec3, radius: Float) -> Sphere {
+        let (_, mat) = test_materials();
+        Sphere::new(center, radius, mat)
+    }
+
+    #[test]
+    fn hits_the_near_side_then_the_far_side() {
+        let s = sphere(Vec3::new(0.0, 0.0, -5.0), 1.0);
+        let r = Ray::with_values(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        let near = s.hit(&r, 0.0, Float::MAX).unwrap();
+        assert!((near.t - 4.0).abs() < 1e-5 && near.front_face);
+        let far = s.hit(&r, near.t, Float::MAX).unwrap();
+        assert!((far.t - 6.0).abs() < 1e-5 && !far.front_face);
+        assert!(s.hit(&r, 0.0, 3.9).is_none());
+    }
 
-        return None;
+    #[test]
+    fn grazing_and_missing_rays_cross_nothing() {
+        let s = sphere(Vec3::new(0.0, 0.0, -5.0), 1.0);
+        let graze = Ray::with_values(Point3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(s.hit(&graze, 0.0, Float::MAX).is_none());
+        let miss = Ray::with_values(Point3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(s.hit(&miss, 0.0, Float::MAX).is_none());
+        assert!(s.intervals(&miss).is_empty());
+    }
+
+    #[test]
+    fn small_distant_spheres_keep_their_near_root() {
+        // -b and the root nearly cancel here, which the naive formula loses
+        let s = sphere(Vec3::new(0.0, 0.0, -1e4), 0.01);
+        let r = Ray::with_values(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        let (near, far) = s.crossings(*r.origin(), r.direction()).unwrap();
+        assert!((near - (1e4 - 0.01)).abs() < 1e-6 * 1e4);
+        assert!((far - (1e4 + 0.01)).abs() < 1e-6 * 1e4);
+        assert!(near < far);
+    }
+
+    #[test]
+    fn packets_find_the_same_hits_as_single_rays() {
+        let s = sphere(Vec3::new(0.2, -0.1, -3.0), 0.8);
+        let rays: Vec<Ray> = (0..WIDTH)
+            .map(|i| Ray::with_values(Point3::new(0.0, 0.0, -2.8), Vec3::new(i as Float * 0.3 - 0.5, 0.1, -1.0)))
+            .collect();
+        let packet = RayPacket::new(&rays);
+        let mut hits = PacketHits::new(&packet, Float::MAX);
+        s.hit_packet(&packet, 0.0, &mut hits);
+        for (r, packed) in rays.iter().zip(hits.into_records().iter()) {
+            assert_eq!(s.hit(r, 0.0, Float::MAX).map(|h| h.t), packed.map(|h| h.t));
+        }
     }
 }
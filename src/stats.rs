@@ -0,0 +1,245 @@
+//! Counters for where rendering time goes.
+//!
+//! Counting is off until `enable`d. Each thread then counts into its own
+//! `Stats`, which the renderer collects with `take` after each tile and
+//! merges.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static STATS: RefCell<Stats> = RefCell::new(Stats::default());
+}
+
+/// Starts or stops counting on every thread.
+pub fn enable(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// What this thread has counted since the last `take`.
+pub fn take() -> Stats {
+    STATS.with(|s| std::mem::take(&mut *s.borrow_mut()))
+}
+
+pub(crate) fn count<F: FnOnce(&mut Stats)>(f: F) {
+    if is_enabled() {
+        STATS.with(|s| f(&mut s.borrow_mut()));
+    }
+}
+
+/// How rendering went.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// Rays traced along paths by how many bounces in they were, starting
+    /// with camera rays. Rays passing through hidden surfaces of nested
+    /// media count at the same depth again.
+    pub rays: Vec<u64>,
+    /// Rays towards lights and the environment to see if they're blocked.
+    pub shadow_rays: u64,
+    /// Rays tested against an object.
+    pub intersection_tests: u64,
+    /// Intersection tests that hit.
+    pub intersection_hits: u64,
+    pub bvh_nodes_visited: u64,
+    /// Paths that left the scene.
+    pub escaped: u64,
+    /// Paths whose material scattered no light.
+    pub absorbed: u64,
+    /// Paths cut off by the renderer's `MaxDepth`.
+    pub max_depth: u64,
+    /// Paths ended by Russian roulette.
+    pub roulette: u64,
+    pub tiles: Vec<TileTime>,
+    /// Time from the start of rendering to the end.
+    pub wall_time: Duration,
+}
+
+/// Time spent rendering a band of rows, over all the passes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TileTime {
+    pub rows: (usize, usize),
+    pub time: Duration,
+}
+
+impl Stats {
+    pub(crate) fn ray(&mut self, depth: usize) {
+        if self.rays.len() <= depth {
+            self.rays.resize(depth + 1, 0);
+        }
+        self.rays[depth] += 1;
+    }
+
+    /// Adds another thread's or tile's counts to these.
+    pub fn merge(&mut self, other: &Stats) {
+        if self.rays.len() < other.rays.len() {
+            self.rays.resize(other.rays.len(), 0);
+        }
+        for (a, b) in self.rays.iter_mut().zip(other.rays.iter()) {
+            *a += b;
+        }
+        self.shadow_rays += other.shadow_rays;
+        self.intersection_tests += other.intersection_tests;
+        self.intersection_hits += other.intersection_hits;
+        self.bvh_nodes_visited += other.bvh_nodes_visited;
+        self.escaped += other.escaped;
+        self.absorbed += other.absorbed;
+        self.max_depth += other.max_depth;
+        self.roulette += other.roulette;
+        for tile in &other.tiles {
+            self.add_tile(tile.rows, tile.time);
+        }
+        self.wall_time += other.wall_time;
+    }
+
+    /// Adds time spent on the rows `rows.0..rows.1`.
+    pub fn add_tile(&mut self, rows: (usize, usize), time: Duration) {
+        match self.tiles.iter_mut().find(|t| t.rows == rows) {
+            Some(t) => t.time += time,
+            None => self.tiles.push(TileTime { rows, time }),
+        }
+    }
+
+    pub fn paths(&self) -> u64 {
+        self.escaped + self.absorbed + self.max_depth + self.roulette
+    }
+
+    pub fn write_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let list = |v: &[u64]| v.iter().map(u64::to_string).collect::<Vec<_>>().join(", ");
+        writeln!(w, "{{")?;
+        writeln!(w, "  \"wall_time\": {},", self.wall_time.as_secs_f64())?;
+        writeln!(w, "  \"rays_by_depth\": [{}],", list(&self.rays))?;
+        writeln!(w, "  \"shadow_rays\": {},", self.shadow_rays)?;
+        writeln!(w, "  \"intersection_tests\": {},", self.intersection_tests)?;
+        writeln!(w, "  \"intersection_hits\": {},", self.intersection_hits)?;
+        writeln!(w, "  \"bvh_nodes_visited\": {},", self.bvh_nodes_visited)?;
+        writeln!(
+            w,
+            "  \"paths\": {{\"escaped\": {}, \"absorbed\": {}, \"max_depth\": {}, \"roulette\": {}}},",
+            self.escaped, self.absorbed, self.max_depth, self.roulette
+        )?;
+        writeln!(w, "  \"tiles\": [")?;
+        for (i, t) in self.tiles.iter().enumerate() {
+            let comma = if i + 1 < self.tiles.len() { "," } else { "" };
+            writeln!(
+                w,
+                "    {{\"rows\": [{}, {}], \"time\": {}}}{}",
+                t.rows.0,
+                t.rows.1,
+                t.time.as_secs_f64(),
+                comma
+            )?;
+        }
+        writeln!(w, "  ]")?;
+        writeln!(w, "}}")
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |n: u64, total: u64| 100.0 * n as f64 / total.max(1) as f64;
+        let rays: u64 = self.rays.iter().sum();
+
+        writeln!(f, "wall time           {:.2} s", self.wall_time.as_secs_f64())?;
+        writeln!(f, "path rays           {}", rays)?;
+        for (depth, n) in self.rays.iter().enumerate() {
+            writeln!(f, "  depth {:<2}          {} ({:.1}%)", depth, n, percent(*n, rays))?;
+        }
+        writeln!(f, "shadow rays         {}", self.shadow_rays)?;
+        writeln!(
+            f,
+            "intersection tests  {} ({:.1}% hit, {:.1} per ray)",
+            self.intersection_tests,
+            percent(self.intersection_hits, self.intersection_tests),
+            self.intersection_tests as f64 / (rays + self.shadow_rays).max(1) as f64
+        )?;
+        writeln!(
+            f,
+            "bvh nodes visited   {} ({:.1} per ray)",
+            self.bvh_nodes_visited,
+            self.bvh_nodes_visited as f64 / (rays + self.shadow_rays).max(1) as f64
+        )?;
+        let paths = self.paths();
+        writeln!(f, "paths ended by")?;
+        writeln!(f, "  escaping          {} ({:.1}%)", self.escaped, percent(self.escaped, paths))?;
+        writeln!(f, "  absorption        {} ({:.1}%)", self.absorbed, percent(self.absorbed, paths))?;
+        writeln!(f, "  max depth         {} ({:.1}%)", self.max_depth, percent(self.max_depth, paths))?;
+        writeln!(f, "  roulette          {} ({:.1}%)", self.roulette, percent(self.roulette, paths))?;
+
+        let times: Vec<f64> = self.tiles.iter().map(|t| t.time.as_secs_f64()).collect();
+        if !times.is_empty() {
+            let (min, max) = times.iter().fold((f64::MAX, 0.0f64), |(lo, hi), t| (lo.min(*t), hi.max(*t)));
+            let mean = times.iter().sum::<f64>() / times.len() as f64;
+            write!(
+                f,
+                "tile time           {} tiles, {:.3} s min, {:.3} s mean, {:.3} s max",
+                times.len(),
+                min,
+                mean,
+                max
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(depth: usize, tile: (usize, usize), ms: u64) -> Stats {
+        let mut stats = Stats::default();
+        stats.ray(depth);
+        stats.shadow_rays = 2;
+        stats.escaped = 1;
+        stats.roulette = 3;
+        stats.add_tile(tile, Duration::from_millis(ms));
+        stats
+    }
+
+    #[test]
+    fn merges_counts_and_tile_times() {
+        let mut total = stats(0, (0, 8), 10);
+        total.merge(&stats(2, (0, 8), 5));
+        total.merge(&stats(1, (8, 16), 7));
+        assert_eq!(total.rays, [1, 1, 1]);
+        assert_eq!(total.shadow_rays, 6);
+        assert_eq!(total.paths(), 12);
+        let tiles: Vec<_> = total.tiles.iter().map(|t| (t.rows, t.time.as_millis())).collect();
+        assert_eq!(tiles, [((0, 8), 15), ((8, 16), 7)]);
+    }
+
+    #[test]
+    fn counts_only_when_enabled_on_this_thread() {
+        // other tests render on other threads, which keep their own counts
+        std::thread::spawn(|| {
+            count(|s| s.escaped += 1);
+            enable(true);
+            count(|s| s.escaped += 1);
+            enable(false);
+            assert_eq!(take().escaped, 1);
+            assert_eq!(take(), Stats::default());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn writes_json() {
+        let mut json = Vec::new();
+        stats(1, (0, 4), 250).write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains("\"rays_by_depth\": [0, 1],"), "{}", json);
+        assert!(json.contains("\"paths\": {\"escaped\": 1, \"absorbed\": 0, \"max_depth\": 0, \"roulette\": 3},"));
+        assert!(json.contains("{\"rows\": [0, 4], \"time\": 0.25}\n  ]"));
+        assert!(json.starts_with('{') && json.ends_with("}\n"));
+    }
+}